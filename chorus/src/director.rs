@@ -14,61 +14,371 @@
 // If not, see <https://www.gnu.org/licenses/>.
 
 use crate::voice::Voice;
-use crate::filter::Filter;
+use crate::filter::{BiquadFilter, Filter};
 use crate::phonemes::{Consonant, Phonemes};
 use crate::random::Random;
-use crate::syllable::Syllable;
+use crate::syllable::{Syllable, DEFAULT_TRILL_TAPS, REST};
 use crate::exciter::Exciter;
-use crate::VoicePart;
+use crate::ensemble::{self, Ensemble};
+use crate::{VoicePart, SAMPLE_RATE};
+use std::collections::BTreeMap;
 use std::f32::consts::PI;
 use std::sync::mpsc;
 
 /// A message that can be sent to a Director.  Messages roughly correspond to MIDI events:
-/// note on, note off, and various control channels.
+/// note on, note off, and various control channels.  New kinds of Messages may be added in
+/// future versions, so code outside this crate that matches on them must include a wildcard arm.
+#[derive(Clone)]
+#[non_exhaustive]
 pub enum Message {
     Reinitialize {voice_part: VoicePart, voice_count: usize},
-    NoteOn {syllable: String, note_index: i32, velocity: f32, continue_syllable: bool},
+    SetVoiceCount {count: usize},
+    NoteOn {syllable: String, note_index: i32, velocity: f32, continue_syllable: bool, vowel_start: Option<i64>},
     NoteOff,
+    SetSyllableIndex {index: i32},
     SetVolume {volume: f32},
+    SetExpression {value: f32},
     SetPitchBend {semitones: f32},
+    SetTuningTable {cents_per_note: Vec<f32>},
     SetVibrato {vibrato: f32},
+    SetVibratoIntensityCoupling {amount: f32},
+    SetTremolo {rate_hz: f32, amount: f32},
     SetIntensity {intensity: f32},
     SetBrightness {brightness: f32},
+    SetBrightnessMode {mode: BrightnessMode},
+    SetTimbreTargets {dark: char, high: char},
     SetConsonantVolume {volume: f32},
+    SetConsonantGain {consonant: char, gain: f32},
+    SetVowelAmplification {vowel: char, amplification: f32},
     SetAttackRate {attack: f32},
+    SetAttackShape {curve: f32},
     SetReleaseRate {release: f32},
+    SetReleaseShape {curve: f32},
     SetAccent {accent: bool},
+    SetAccentAmount {gain: f32, decay_ms: i64},
+    SetVelocityCurve {amount: f32},
+    SetAspiration {amount: f32},
+    SetTrill {taps: u32},
+    SetGlideTime {samples: i64},
+    SetCustomVowel {vowel: char, shape: Vec<f32>, nasal_coupling: f32},
+    SetIntermediateShape {vowel1: char, vowel2: char, shape: Vec<f32>},
+    SetConsonantSamples {consonant: char, samples: Vec<Vec<i16>>},
+    SetOutOfRangeBehavior {mode: OutOfRangeBehavior},
+    SetLegatoMode {mode: LegatoMode},
+    SetVoiceMute {index: usize, muted: bool},
     SetStereoWidth {width: f32},
     SetExciterStrength {strength: f32},
+    SetEnsemble {depth: f32, voices: usize},
+    SetOutputGain {gain_db: f32},
+    SetAir {gain_db: f32},
+    SetOversampling {factor: usize},
+    SetMono {enabled: bool},
+    SetFormantSpread {amount: f32},
+    SetHum {enabled: bool},
+    SetNasality {coupling: f32},
+    SetWhisper {amount: f32},
+    SetSubglottalCoupling {amount: f32},
+    SetDamping {vocal: f32, nasal: f32},
+    SetVoicing {rd_base: f32, rd_pitch_scale: f32, rd_volume_scale: f32, rd_intensity_scale: f32},
+    SetHumanize {amount: f32},
+    Breath {intensity: f32},
     SetMinVowelStartTime {samples: i64},
     SetMaxVoiceDelay {max_delay: i64},
+    SetEntranceJitter {amount: f32},
     SetDelays {vowel_delay: i64, vowel_transition_time: i64, consonant_delay: i64, consonant_transition_time: i64},
-    SetConsonants {on_time: i64, off_time: i64, volume: f32, position: usize, frequency: f32, bandwidth: f32},
+    SetConsonants {on_time: i64, off_time: i64, volume: f32, frequency: f32, bandwidth: f32},
     SetRandomize {randomize: f32}
 }
 
+/// The largest random delay (in samples) that can be added to a voice's entrance when the
+/// entrance jitter is 1.0.
+const MAX_ENTRANCE_JITTER: i64 = 2400;
+
+/// The minimum time (in samples) over which the voices fade in when starting from silence.
+const COLD_START_FADE_IN: i64 = 240;
+
+/// When starting from silence, the envelope is updated on every sample for up to this many
+/// samples, so a fast attack rises smoothly rather than in steps.
+const COLD_START_SMOOTHING: i64 = 1000;
+
+/// Once nothing is exciting the voices, they stop being computed after their output has stayed
+/// below SILENCE_THRESHOLD for SILENCE_TIME samples.
+const SILENCE_THRESHOLD: f32 = 1e-4;
+const SILENCE_TIME: i64 = 500;
+
+/// The time (in samples) over which a voice added with SetVoiceCount fades in.
+const NEW_VOICE_FADE_IN: i64 = 2400;
+
+/// The largest factor by which the glottal excitation can be oversampled.
+const MAX_OVERSAMPLING: usize = 4;
+
+/// The largest total nasal coupling that SetNasality can produce.  This is the coupling used for
+/// nasal consonants like [m] and [n].
+const MAX_NASAL_COUPLING: f32 = 0.5;
+
+/// The vowels that low brightness and high notes blend toward, unless changed with
+/// SetTimbreTargets.
+const DEFAULT_DARK_VOWEL: char = 'o';
+const DEFAULT_HIGH_VOWEL: char = 'A';
+
+/// In SpectralTilt mode, the output is cut by up to MAX_TILT_DB above TILT_FREQUENCY (in Hz)
+/// when the brightness is 0.
+const TILT_FREQUENCY: f32 = 1000.0;
+const MAX_TILT_DB: f32 = -12.0;
+
+/// The frequency (in Hz) above which the air band set by SetAir boosts or cuts the output.
+const AIR_FREQUENCY: f32 = 10000.0;
+
+/// The range of Rd the voicing set by SetVoicing can produce, before each voice adds its own
+/// small offset.  The glottal model produces invalid waveforms outside it.
+const MIN_RD: f32 = 0.3;
+const MAX_RD: f32 = 2.5;
+
+/// The maximum fractional amount by which each voice's vocal tract length is randomly varied.
+const VOCAL_LENGTH_VARIATION: f32 = 0.04;
+
+/// When a note ends with the same consonants the next one begins with, as in the Italian fat-to
+/// or ter-ra, they are sung only once, at the start of the new note, and the first one's
+/// duration is scaled by this factor.  The same applies to the sonorants l, m, and n, as in
+/// bel-lo, even though they are treated as vowels.
+const GEMINATE_TIME_SCALE: f32 = 1.8;
+
+/// What to do when asked to sing a note that is outside the range of the voice part.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutOfRangeBehavior {
+    /// Do not sing the note.
+    Silent,
+    /// Transpose the note by octaves until it is inside the range.
+    OctaveFold,
+    /// Sing the lowest or highest note in the range instead.
+    Clamp
+}
+
+/// How to connect a new note to one that is already playing.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LegatoMode {
+    /// Continue the syllable only when asked to and the vowels match.  Otherwise blend the
+    /// vowels, or sing the consonants between them.
+    Auto,
+    /// Always continue the syllable when the vowels match, even if not asked to.
+    AlwaysLegato,
+    /// Always release the current note so the new one begins with a fresh attack.
+    AlwaysRetrigger
+}
+
+/// How the brightness setting changes the sound.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BrightnessMode {
+    /// Blend the vocal tract shape toward a darker vowel.  This takes effect with the next note.
+    ShapeBlend,
+    /// Apply a filter to the output that tilts the spectrum, reducing high frequencies.  This
+    /// affects all vowels equally and takes effect immediately.
+    SpectralTilt
+}
+
+/// A DirectorConfig records the values of a Director's settings that can be changed with
+/// Messages.  Call Director::get_config() to retrieve the current settings, and either
+/// Director::apply_config() or messages() to restore them.  When the `serde` feature is
+/// enabled, it can be serialized, which is useful for presets and external tools.  Only consonant
+/// gains and vowel amplifications that differ from the defaults are stored.
+///
+/// It does not include voice mutes, which depend on the number of voices, or custom vowels,
+/// intermediate shapes, and consonant samples, which depend on the voice part and are discarded
+/// when the voices are reinitialized.  Set those again with SetVoiceMute, SetCustomVowel,
+/// SetIntermediateShape, and SetConsonantSamples after applying a configuration.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirectorConfig {
+    pub volume: f32,
+    pub expression: f32,
+    pub pitch_bend: f32,
+    pub tuning_table: Vec<f32>,
+    pub vibrato: f32,
+    pub vibrato_intensity_coupling: f32,
+    pub tremolo_rate: f32,
+    pub tremolo_amount: f32,
+    pub intensity: f32,
+    pub brightness: f32,
+    pub brightness_mode: BrightnessMode,
+    pub dark_vowel: char,
+    pub high_vowel: char,
+    pub consonant_volume: f32,
+    pub consonant_gains: BTreeMap<char, f32>,
+    pub vowel_amplification: BTreeMap<char, f32>,
+    pub attack_rate: f32,
+    pub attack_shape: f32,
+    pub release_rate: f32,
+    pub release_shape: f32,
+    pub accent: bool,
+    pub accent_gain: f32,
+    pub accent_decay_ms: i64,
+    pub velocity_curve: f32,
+    pub aspiration: f32,
+    pub trill_taps: u32,
+    pub glide_time: i64,
+    pub out_of_range_behavior: OutOfRangeBehavior,
+    pub legato_mode: LegatoMode,
+    pub stereo_width: f32,
+    pub exciter_strength: f32,
+    pub ensemble_depth: f32,
+    pub ensemble_voices: usize,
+    pub output_gain_db: f32,
+    pub air_gain_db: f32,
+    pub oversampling: usize,
+    pub mono: bool,
+    pub formant_spread: f32,
+    pub hum: bool,
+    pub nasality: f32,
+    pub whisper: f32,
+    pub subglottal_coupling: f32,
+    pub vocal_damping: f32,
+    pub nasal_damping: f32,
+    pub rd_base: f32,
+    pub rd_pitch_scale: f32,
+    pub rd_volume_scale: f32,
+    pub rd_intensity_scale: f32,
+    pub humanize: f32,
+    pub min_vowel_start: i64,
+    pub max_voice_delay: i64,
+    pub entrance_jitter: f32,
+    pub randomize: f32
+}
+
+impl DirectorConfig {
+    /// Get the Messages that will set a Director to this configuration.
+    pub fn messages(&self) -> Vec<Message> {
+        let mut messages = vec![
+            Message::SetVolume {volume: self.volume},
+            Message::SetExpression {value: self.expression},
+            Message::SetPitchBend {semitones: self.pitch_bend},
+            Message::SetTuningTable {cents_per_note: self.tuning_table.clone()},
+            Message::SetVibrato {vibrato: self.vibrato},
+            Message::SetVibratoIntensityCoupling {amount: self.vibrato_intensity_coupling},
+            Message::SetTremolo {rate_hz: self.tremolo_rate, amount: self.tremolo_amount},
+            Message::SetIntensity {intensity: self.intensity},
+            Message::SetBrightness {brightness: self.brightness},
+            Message::SetBrightnessMode {mode: self.brightness_mode},
+            Message::SetTimbreTargets {dark: self.dark_vowel, high: self.high_vowel},
+            Message::SetConsonantVolume {volume: self.consonant_volume},
+            Message::SetAttackRate {attack: self.attack_rate},
+            Message::SetAttackShape {curve: self.attack_shape},
+            Message::SetReleaseRate {release: self.release_rate},
+            Message::SetReleaseShape {curve: self.release_shape},
+            Message::SetAccent {accent: self.accent},
+            Message::SetAccentAmount {gain: self.accent_gain, decay_ms: self.accent_decay_ms},
+            Message::SetVelocityCurve {amount: self.velocity_curve},
+            Message::SetAspiration {amount: self.aspiration},
+            Message::SetTrill {taps: self.trill_taps},
+            Message::SetGlideTime {samples: self.glide_time},
+            Message::SetOutOfRangeBehavior {mode: self.out_of_range_behavior},
+            Message::SetLegatoMode {mode: self.legato_mode},
+            Message::SetStereoWidth {width: self.stereo_width},
+            Message::SetExciterStrength {strength: self.exciter_strength},
+            Message::SetEnsemble {depth: self.ensemble_depth, voices: self.ensemble_voices},
+            Message::SetOutputGain {gain_db: self.output_gain_db},
+            Message::SetAir {gain_db: self.air_gain_db},
+            Message::SetOversampling {factor: self.oversampling},
+            Message::SetMono {enabled: self.mono},
+            Message::SetFormantSpread {amount: self.formant_spread},
+            Message::SetHum {enabled: self.hum},
+            Message::SetNasality {coupling: self.nasality},
+            Message::SetWhisper {amount: self.whisper},
+            Message::SetSubglottalCoupling {amount: self.subglottal_coupling},
+            Message::SetDamping {vocal: self.vocal_damping, nasal: self.nasal_damping},
+            Message::SetVoicing {rd_base: self.rd_base, rd_pitch_scale: self.rd_pitch_scale, rd_volume_scale: self.rd_volume_scale, rd_intensity_scale: self.rd_intensity_scale},
+            Message::SetHumanize {amount: self.humanize},
+            Message::SetMinVowelStartTime {samples: self.min_vowel_start},
+            Message::SetMaxVoiceDelay {max_delay: self.max_voice_delay},
+            Message::SetEntranceJitter {amount: self.entrance_jitter},
+            Message::SetRandomize {randomize: self.randomize}
+        ];
+
+        // Set the gain of every consonant and the amplification of every vowel, so any that were
+        // changed but are not in this configuration get restored to the default.
+
+        for &c in Phonemes::consonants() {
+            messages.push(Message::SetConsonantGain {consonant: c, gain: self.consonant_gains.get(&c).copied().unwrap_or(1.0)});
+        }
+        for &v in Phonemes::vowels() {
+            messages.push(Message::SetVowelAmplification {vowel: v, amplification: self.vowel_amplification.get(&v).copied().unwrap_or(Phonemes::default_amplification(v))});
+        }
+        messages
+    }
+}
+
+/// A snapshot of what a Director is currently doing, as returned by Director::current_state().
+/// It is intended for displaying in a user interface.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DirectorState {
+    /// The note being sung, or None if no note is playing.
+    pub note: Option<i32>,
+    /// The vowel sustained for the current note, or None if no note is playing.
+    pub vowel: Option<char>,
+    /// The envelope level averaged over all voices.  This is 0 when the voices are silent.
+    pub envelope_level: f32,
+    /// A consonant that is currently being sung, if there is one.
+    pub active_consonant: Option<char>,
+    /// The index within the phrase of the syllable being sung, as last reported with
+    /// SetSyllableIndex by whatever is driving the Director (such as a Performer), or -1 if none
+    /// has been reported.
+    pub syllable_index: i32
+}
+
 /// A Transition describes some type of continuous change to the voices.  It specifies the time
 /// interval (in step indices) over which the change takes place.  The details of what is
-/// changing are specified by the TransitionData.
+/// changing are specified by the TransitionData.  The curve selects how it moves between the
+/// start and end values: 0 for a raised cosine, -1 for linear, 1 for exponential, or anything
+/// in between to blend them.
 struct Transition {
     start: i64,
     end: i64,
+    curve: f32,
     data: TransitionData
 }
 
+impl Transition {
+    /// Get the weight of the end value at step j.  This goes from 0 at the start of the
+    /// Transition to 1 at the end.
+    fn weight(&self, j: i64) -> f32 {
+        if j >= self.end {
+            return 1.0;
+        }
+        let fraction = (j-self.start) as f32 / (self.end-self.start) as f32;
+        let cosine = 0.5-0.5*(fraction*std::f32::consts::PI).cos();
+        if self.curve == 0.0 {
+            cosine
+        }
+        else if self.curve < 0.0 {
+            let blend = (-self.curve).min(1.0);
+            (1.0-blend)*cosine + blend*fraction
+        }
+        else {
+            const RATE: f32 = 5.0;
+            let exponential = (1.0-(-RATE*fraction).exp())/(1.0-(-RATE).exp());
+            let blend = self.curve.min(1.0);
+            (1.0-blend)*cosine + blend*exponential
+        }
+    }
+}
+
 /// A TransitionData is contained in a Transition.  It specifies what aspect of the voices is
 /// changing, and what values it is changing between.
 enum TransitionData {
     EnvelopeChange {start_envelope: f32, end_envelope: f32},
     ShapeChange {start_shape: Vec<Vec<f32>>, end_shape: Vec<Vec<f32>>, start_nasal_coupling: f32, end_nasal_coupling: f32},
-    FrequencyChange {start_frequency: f32, end_frequency: f32}
+    FrequencyChange {start_frequency: f32, end_frequency: f32},
+    AspirationChange {start_aspiration: f32, end_aspiration: f32}
 }
 
 /// A note that is being sung.  It is described by the standard MIDI properties (note index
 /// and velocity), as well as the syllable to sing it on.
 struct Note {
     syllable: Syllable,
-    note_index: i32
+    note_index: i32,
+    min_vowel_start: i64
 }
 
 /// This is the main class you interact with when synthesizing audio.  A Director controls a set
@@ -77,8 +387,16 @@ struct Note {
 ///
 /// When creating a Director with new(), you provide a Receiver<Message> that has been created
 /// with mpsc::channel().  You control it by sending messages from the corresponding Sender.
-/// The only method you call directly on it is generate(), which is used to generate samples.
-/// This design allows control and generation to happen on different threads.
+/// The main method you call directly on it is generate(), which is used to generate samples.
+/// This design allows control and generation to happen on different threads.  A few methods
+/// also are provided for monitoring its current state, such as get_vocal_shape().
+///
+/// If control and generation happen on the same thread, you can instead call methods such as
+/// note_on(), note_off(), and set_volume() directly, or pass any Message to handle_message().
+/// These take effect immediately, rather than waiting for the next time messages are processed.
+/// DirectorBuilder::build_standalone() creates a Director for this purpose without needing a
+/// channel.  Nothing in the Director requires threads, so it can be used this way in
+/// single threaded environments such as WASM.
 pub struct Director {
     voices: Vec<Voice>,
     voice_part: VoicePart,
@@ -90,35 +408,96 @@ pub struct Director {
     random: Random,
     step: i64,
     transitions: Vec<Transition>,
+    /// For each voice, the number of transitions at the start of the list that have finished for
+    /// it.  Their end values have already been applied, so they can be skipped.
+    settled_transitions: Vec<usize>,
     current_note: Option<Note>,
+    syllable_index: i32,
+    /// The consonants that have been queued, ordered by start time.
     consonants: Vec<Consonant>,
+    active_consonants: Vec<(usize, bool)>,
     max_voice_delay: i64,
     voice_delays: Vec<i64>,
+    entrance_jitter: f32,
+    entrance_offsets: Vec<i64>,
     volume: f32,
+    expression: f32,
     envelope: Vec<f32>,
     frequency: Vec<f32>,
+    aspiration_level: Vec<f32>,
     bend: f32,
+    tuning_table: Vec<f32>,
     vibrato: f32,
+    vibrato_intensity_coupling: f32,
+    tremolo_rate: f32,
+    tremolo_amount: f32,
     intensity: f32,
     brightness: f32,
+    brightness_mode: BrightnessMode,
+    left_tilt: BiquadFilter,
+    right_tilt: BiquadFilter,
     consonant_volume: f32,
+    consonant_gains: BTreeMap<char, f32>,
+    vowel_amplification: BTreeMap<char, f32>,
     attack_rate: f32,
+    attack_shape: f32,
     release_rate: f32,
+    release_shape: f32,
     accent: bool,
+    accent_gain: f32,
+    accent_decay: i64,
+    velocity_curve: f32,
+    velocity_scale: f32,
+    aspiration: f32,
+    trill_taps: u32,
+    glide_time: i64,
+    out_of_range_behavior: OutOfRangeBehavior,
+    legato_mode: LegatoMode,
     min_vowel_start: i64,
     off_after_step: i64,
+    fade_in: (i64, i64),
     shape_after_transitions: Vec<Vec<f32>>,
+    shape_buffer: Vec<f32>,
+    shape_pool: Vec<Vec<Vec<f32>>>,
     nasal_coupling_after_transitions: f32,
     envelope_after_transitions: f32,
     frequency_after_transitions: f32,
     message_receiver: mpsc::Receiver<Message>,
+    message_interval: i64,
     stereo_width: f32,
     voice_pan: Vec<f32>,
+    voice_muted: Vec<bool>,
+    voice_fade: Vec<f32>,
+    dark_vowel: char,
+    high_vowel: char,
     dark_shape: Vec<f32>,
     high_shape: Vec<f32>,
     exciter_strength: f32,
     left_exciter: Exciter,
     right_exciter: Exciter,
+    ensemble: Ensemble,
+    ensemble_depth: f32,
+    ensemble_voices: usize,
+    output_gain_db: f32,
+    output_gain: f32,
+    air_gain_db: f32,
+    oversampling: usize,
+    left_air: BiquadFilter,
+    right_air: BiquadFilter,
+    mono: bool,
+    formant_spread: f32,
+    formant_offsets: Vec<f32>,
+    hum: bool,
+    nasality: f32,
+    whisper: f32,
+    subglottal_coupling: f32,
+    vocal_damping: f32,
+    nasal_damping: f32,
+    rd_base: f32,
+    rd_pitch_scale: f32,
+    rd_volume_scale: f32,
+    rd_intensity_scale: f32,
+    humanize: f32,
     vowel_delay: i64,
     vowel_transition_time: i64,
     consonant_delay: i64,
@@ -126,7 +505,6 @@ pub struct Director {
     consonant_on_time: i64,
     consonant_off_time: i64,
     consonant_volume2: f32,
-    consonant_position: usize,
     consonant_frequency: f32,
     consonant_bandwidth: f32,
     randomize: f32
@@ -134,7 +512,16 @@ pub struct Director {
 
 impl Director {
     pub fn new(voice_part: VoicePart, voice_count: usize, message_receiver: mpsc::Receiver<Message>) -> Self {
-        let mut result = Self {
+        let mut result = Self::with_defaults(voice_part, message_receiver, None);
+        result.initialize_voices(voice_part, voice_count);
+        result
+    }
+
+    /// Create a Director with every setting at its default value, but no voices.  Call
+    /// initialize_voices() to create them.  If a seed is specified, it is used for the random
+    /// number generator.
+    fn with_defaults(voice_part: VoicePart, message_receiver: mpsc::Receiver<Message>, seed: Option<u32>) -> Self {
+        Self {
             voices: vec![],
             voice_part: voice_part.clone(),
             lowest_note: 0,
@@ -142,38 +529,99 @@ impl Director {
             high_blend_note: 0,
             high_blend_fraction: 0.0,
             phonemes: Phonemes::new(voice_part),
-            random: Random::new(),
+            random: match seed {
+                Some(seed) => Random::with_seed(seed),
+                None => Random::new()
+            },
             step: 0,
             transitions: vec![],
+            settled_transitions: vec![],
             current_note: None,
+            syllable_index: -1,
             consonants: vec![],
+            active_consonants: vec![],
             max_voice_delay: 2000,
             voice_delays: vec![],
+            entrance_jitter: 0.0,
+            entrance_offsets: vec![],
             volume: 1.0,
+            expression: 1.0,
             envelope: vec![],
             frequency: vec![],
+            aspiration_level: vec![],
             bend: 1.0,
+            tuning_table: vec![],
             vibrato: 0.4,
+            vibrato_intensity_coupling: 0.0,
+            tremolo_rate: 0.0,
+            tremolo_amount: 1.0,
             intensity: 0.5,
             brightness: 1.0,
+            brightness_mode: BrightnessMode::ShapeBlend,
+            left_tilt: BiquadFilter::high_shelf(TILT_FREQUENCY, 0.5, 0.0),
+            right_tilt: BiquadFilter::high_shelf(TILT_FREQUENCY, 0.5, 0.0),
             consonant_volume: 0.5,
+            consonant_gains: BTreeMap::new(),
+            vowel_amplification: BTreeMap::new(),
             attack_rate: 0.8,
+            attack_shape: 0.0,
             release_rate: 0.5,
+            release_shape: 0.0,
             accent: false,
+            accent_gain: 2.5,
+            accent_decay: 4000,
+            velocity_curve: 0.0,
+            velocity_scale: 1.0,
+            aspiration: 0.5,
+            trill_taps: DEFAULT_TRILL_TAPS,
+            glide_time: 12000,
+            out_of_range_behavior: OutOfRangeBehavior::Silent,
+            legato_mode: LegatoMode::Auto,
             min_vowel_start: 0,
             off_after_step: 0,
+            fade_in: (0, 0),
             shape_after_transitions: vec![],
+            shape_buffer: vec![],
+            shape_pool: vec![],
             nasal_coupling_after_transitions: 0.0,
             envelope_after_transitions: 0.0,
             frequency_after_transitions: 0.0,
             message_receiver: message_receiver,
+            message_interval: 16,
             stereo_width: 0.3,
             voice_pan: vec![],
+            voice_muted: vec![],
+            voice_fade: vec![],
+            dark_vowel: DEFAULT_DARK_VOWEL,
+            high_vowel: DEFAULT_HIGH_VOWEL,
             dark_shape: vec![],
             high_shape: vec![],
             exciter_strength: 0.5,
             left_exciter: Exciter::new(1000.0),
             right_exciter: Exciter::new(1000.0),
+            ensemble: Ensemble::new(0.0, 3),
+            ensemble_depth: 0.0,
+            ensemble_voices: 3,
+            output_gain_db: 0.0,
+            output_gain: 1.0,
+            air_gain_db: 0.0,
+            oversampling: 1,
+            left_air: BiquadFilter::high_shelf(AIR_FREQUENCY, 0.707, 0.0),
+            right_air: BiquadFilter::high_shelf(AIR_FREQUENCY, 0.707, 0.0),
+            mono: false,
+            formant_spread: 0.03,
+            formant_offsets: vec![],
+            hum: false,
+            nasality: 0.0,
+            whisper: 0.0,
+            subglottal_coupling: 0.0,
+            vocal_damping: 0.995,
+            nasal_damping: 0.98,
+            rd_base: 1.5,
+            rd_pitch_scale: 0.5,
+            rd_volume_scale: 0.2,
+            rd_intensity_scale: 1.0,
+            humanize: 1.0,
             vowel_delay: 0,
             vowel_transition_time: 3500,
             consonant_delay: 3000,
@@ -181,80 +629,84 @@ impl Director {
             consonant_on_time: 1000,
             consonant_off_time: 1000,
             consonant_volume2: 0.1,
-            consonant_position: 40,
             consonant_frequency: 2000.0,
             consonant_bandwidth: 3000.0,
             randomize: 0.1
-        };
-        result.initialize_voices(voice_part, voice_count);
-        result
+        }
     }
 
     /// Initialize the set of voices controlled by this Director.  This is called when it is first
-    /// created, and again whenever a Reinitialize message is received.
+    /// created, and again whenever a Reinitialize message is received.  Settings such as the pitch
+    /// bend are preserved, so changing the voices does not cause audible jumps.
     fn initialize_voices(&mut self, voice_part: VoicePart, voice_count: usize) {
         self.voice_part = voice_part.clone();
         self.voices.clear();
+        self.formant_offsets.clear();
         for i in 0..voice_count {
-            self.voices.push(Voice::new(voice_part, i));
+            // Real singers differ in the lengths of their vocal tracts.  Varying them keeps the
+            // voices' formants from lining up exactly, which would sound artificial.
+
+            let length_scale = 1.0 + VOCAL_LENGTH_VARIATION*(2.0*self.random.get_uniform()-1.0);
+            self.voices.push(Voice::with_length_scale(voice_part, i, Random::with_seed(self.random.get_int()), length_scale));
+            self.formant_offsets.push(2.0*self.random.get_uniform()-1.0);
         }
         self.phonemes = Phonemes::new(voice_part);
+        for (&vowel, &amplification) in &self.vowel_amplification {
+            self.phonemes.set_amplification(vowel, amplification);
+        }
         self.transitions.clear();
+        self.settled_transitions = vec![0; voice_count];
         self.current_note = None;
         self.consonants.clear();
+        self.fade_in = (0, 0);
         self.voice_delays = vec![0; voice_count];
+        self.entrance_offsets = vec![0; voice_count];
         self.voice_pan = vec![0.0; voice_count];
         self.envelope = vec![0.0; voice_count];
         self.frequency = vec![0.0; voice_count];
-        self.bend = 1.0;
+        self.aspiration_level = vec![0.0; voice_count];
+        self.voice_muted = vec![false; voice_count];
+        self.voice_fade = vec![1.0; voice_count];
         self.nasal_coupling_after_transitions = 0.0;
         self.envelope_after_transitions = 0.0;
         self.frequency_after_transitions = 0.0;
         let vocal_length;
         let exciter_cutoff;
+        (self.lowest_note, self.highest_note) = voice_part.note_range();
         match voice_part {
             VoicePart::Soprano => {
                 vocal_length = 42;
                 exciter_cutoff = 1500.0;
-                self.lowest_note = 57;
-                self.highest_note = 88;
                 self.high_blend_note = 72;
                 self.high_blend_fraction = 0.3;
             }
             VoicePart::Alto => {
                 vocal_length = 45;
                 exciter_cutoff = 1400.0;
-                self.lowest_note = 48;
-                self.highest_note = 79;
                 self.high_blend_note = 72;
                 self.high_blend_fraction = 0.15;
             }
             VoicePart::Tenor => {
                 vocal_length = 48;
                 exciter_cutoff = 1300.0;
-                self.lowest_note = 43;
-                self.highest_note = 72;
                 self.high_blend_note = 64;
                 self.high_blend_fraction = 0.1;
             }
             VoicePart::Bass => {
                 vocal_length = 52;
                 exciter_cutoff = 1200.0;
-                self.lowest_note = 36;
-                self.highest_note = 67;
                 self.high_blend_note = 60;
                 self.high_blend_fraction = 0.1;
             }
         }
         self.shape_after_transitions = vec![vec![0.0; vocal_length]; voice_count];
-        self.dark_shape = self.phonemes.get_vowel_shape('o').unwrap().clone();
-        self.high_shape = self.phonemes.get_vowel_shape('A').unwrap().clone();
-        for i in 0..self.high_shape.len() {
-            self.high_shape[i] = 0.7*self.high_shape[i] + 0.3*self.dark_shape[i];
-        }
+        self.shape_buffer = vec![0.0; vocal_length];
+        self.shape_pool.clear();
+        self.update_timbre_targets();
         self.left_exciter = Exciter::new(exciter_cutoff);
         self.right_exciter = Exciter::new(exciter_cutoff);
         self.update_pan_positions();
+        self.update_formants();
         self.update_vibrato();
         self.update_volume();
         self.update_frequency();
@@ -262,14 +714,123 @@ impl Director {
         self.update_voice_delays();
     }
 
-    /// Start singing a new note.
-    fn note_on(&mut self, syllable: &str, note_index: i32, velocity: f32, continue_syllable: bool) -> Result<(), String> {
-        // If the note index is outside the range of this voice part, just stop the current
-        // note and exit.
+    /// Change the number of voices without disturbing the ones that remain, so the current note
+    /// continues without a break.  New voices join whatever is being sung, copying the state of
+    /// the first voice, and fade in over a short time.
+    fn set_voice_count(&mut self, voice_count: usize) {
+        let voice_count = voice_count.max(1);
+        let old_count = self.voices.len();
+        if voice_count == old_count {
+            return;
+        }
+        if old_count == 0 {
+            // There is no existing voice for the new ones to copy, so start from scratch.
 
+            self.initialize_voices(self.voice_part, voice_count);
+            return;
+        }
+        if voice_count < old_count {
+            self.voices.truncate(voice_count);
+            self.formant_offsets.truncate(voice_count);
+            self.entrance_offsets.truncate(voice_count);
+            self.envelope.truncate(voice_count);
+            self.frequency.truncate(voice_count);
+            self.aspiration_level.truncate(voice_count);
+            self.voice_muted.truncate(voice_count);
+            self.voice_fade.truncate(voice_count);
+            self.shape_after_transitions.truncate(voice_count);
+            self.settled_transitions.truncate(voice_count);
+            for transition in &mut self.transitions {
+                if let TransitionData::ShapeChange {start_shape, end_shape, ..} = &mut transition.data {
+                    start_shape.truncate(voice_count);
+                    end_shape.truncate(voice_count);
+                }
+            }
+        }
+        else {
+            for i in old_count..voice_count {
+                let length_scale = 1.0 + VOCAL_LENGTH_VARIATION*(2.0*self.random.get_uniform()-1.0);
+                let mut voice = Voice::with_length_scale(self.voice_part, i, Random::with_seed(self.random.get_int()), length_scale);
+                voice.set_vocal_shape(&self.shape_after_transitions[0], self.nasal_coupling_after_transitions);
+                voice.set_aspiration(self.aspiration_level[0]);
+                self.voices.push(voice);
+                self.formant_offsets.push(2.0*self.random.get_uniform()-1.0);
+                self.entrance_offsets.push(self.entrance_offsets[0]);
+                self.envelope.push(self.envelope[0]);
+                self.frequency.push(self.frequency[0]);
+                self.aspiration_level.push(self.aspiration_level[0]);
+                self.voice_muted.push(false);
+                self.voice_fade.push(0.0);
+                self.shape_after_transitions.push(self.shape_after_transitions[0].clone());
+                self.settled_transitions.push(0);
+                for transition in &mut self.transitions {
+                    if let TransitionData::ShapeChange {start_shape, end_shape, ..} = &mut transition.data {
+                        start_shape.push(start_shape[0].clone());
+                        end_shape.push(end_shape[0].clone());
+                    }
+                }
+            }
+        }
+        self.shape_pool.clear();
+
+        // The surviving voices keep their delays, so transitions in progress don't jump.
+
+        let old_delays = self.voice_delays.clone();
+        self.voice_delays = vec![0; voice_count];
+        self.voice_pan = vec![0.0; voice_count];
+        self.update_voice_delays();
+        let surviving = voice_count.min(old_count);
+        self.voice_delays[..surviving].copy_from_slice(&old_delays[..surviving]);
+        self.update_pan_positions();
+        self.update_formants();
+        self.update_vibrato();
+        self.update_volume();
+        self.update_frequency();
+        self.update_sound();
+        self.update_transitions();
+    }
+
+    /// Start singing a new note.  An Err is returned if the syllable cannot be parsed.  If the
+    /// syllable is REST, the current note ends and no new one begins.
+    pub fn note_on(&mut self, syllable: &str, note_index: i32, velocity: f32, continue_syllable: bool) -> Result<(), String> {
+        self.note_on_with_vowel_start(syllable, note_index, velocity, continue_syllable, None)
+    }
+
+    /// Start singing a new note, overriding the minimum vowel start time for just this note.  If
+    /// vowel_start is None, the value set with SetMinVowelStartTime is used.  The same value is
+    /// used to delay the final consonants when the note ends.
+    pub fn note_on_with_vowel_start(&mut self, syllable: &str, note_index: i32, velocity: f32, continue_syllable: bool, vowel_start: Option<i64>) -> Result<(), String> {
+        let min_vowel_start = vowel_start.unwrap_or(self.min_vowel_start).max(0);
+
+        // A rest just ends the current note.
+
+        if syllable == REST {
+            if self.current_note.is_some() {
+                self.end_note(false, false);
+            }
+            return Ok(());
+        }
+
+        // If the note index is outside the range of this voice part, either move it into the
+        // range or just stop the current note and exit.
+
+        let note_index = match self.out_of_range_behavior {
+            OutOfRangeBehavior::Silent => note_index,
+            OutOfRangeBehavior::OctaveFold => {
+                let mut index = note_index;
+                while index < self.lowest_note {
+                    index += 12;
+                }
+                while index > self.highest_note {
+                    index -= 12;
+                }
+                index
+            }
+            OutOfRangeBehavior::Clamp => note_index.clamp(self.lowest_note, self.highest_note)
+        };
         if note_index < self.lowest_note || note_index > self.highest_note {
             if self.current_note.is_some() {
-                self.note_off(false, false);
+                self.end_note(false, false);
             }
             return Ok(());
         }
@@ -278,16 +839,44 @@ impl Director {
 
         let num_transitions = self.transitions.len();
         let num_consonants = self.consonants.len();
-        let new_syllable = Syllable::build(syllable)?;
+        let new_syllable = Syllable::build_with_trill(syllable, self.trill_taps)?;
+
+        // The legato mode can override how the new note connects to the current one.
+
+        let continue_syllable = match self.legato_mode {
+            LegatoMode::Auto => continue_syllable,
+            LegatoMode::AlwaysLegato => true,
+            LegatoMode::AlwaysRetrigger => {
+                if self.current_note.is_some() {
+                    self.end_note(false, false);
+                }
+                false
+            }
+        };
+
+        // If the voices are completely silent, pick new random offsets for when they enter.
+
+        let cold_start = self.current_note.is_none() && self.transitions.is_empty() && self.consonants.is_empty();
+        if cold_start {
+            self.randomize_entrances();
+        }
+
+        // Velocity can optionally scale the loudness of the note.
+
+        self.velocity_scale = 1.0 - self.velocity_curve*(1.0-velocity);
+
         let mut delay_for_consonants = false;
         let has_current_note = self.current_note.is_some();
         let mut continuous = false;
+        let mut continued_glide = None;
+        let mut geminate = false;
         let mut sustain = new_syllable.initial_consonants.iter().all(|&c| self.phonemes.is_voiced_consonant(c));
         if let Some(note) = &self.current_note {
             if continue_syllable && note.syllable.main_vowel == new_syllable.main_vowel {
                 // Treat the previous and new notes as a single syllable, continuing on the same vowel.
 
                 continuous = true;
+                continued_glide = note.syllable.glide;
             }
             else if note.syllable.final_consonants.len() > 0 || new_syllable.initial_consonants.len() > 0 {
                 // Smoothly blending the notes isn't possible, since there are consonants between the vowels.
@@ -303,10 +892,33 @@ impl Director {
                     delay_for_consonants = true;
                 }
                 sustain &= note.syllable.final_consonants.iter().all(|&c| self.phonemes.is_voiced_consonant(c));
-                self.note_off(true, sustain);
+
+                // Consonants that end this note and also begin the next one are a geminate.  Sing
+                // them once, held longer, at the start of the new note.  Compare the whole cluster,
+                // since a rolled r is expanded to several taps.
+
+                let finals = &note.syllable.final_consonants;
+                let initials = &new_syllable.initial_consonants;
+                let overlap = (1..=finals.len().min(initials.len())).rev().find(|&n| finals[finals.len()-n..] == initials[..n]).unwrap_or(0);
+                if overlap > 0 {
+                    geminate = true;
+                    let finals = &mut self.current_note.as_mut().unwrap().syllable.final_consonants;
+                    finals.truncate(finals.len()-overlap);
+                }
+                self.end_note(true, sustain);
+            }
+            else if matches!(note.syllable.final_vowels.last(), Some('l' | 'm' | 'n' | 'N')) && note.syllable.final_vowels.last() == new_syllable.initial_vowels.first() {
+                // The sonorants l, m, and n are treated as vowels, so a geminate sonorant ends
+                // this note with a final vowel and begins the next one with the same initial
+                // vowel.  Sing it once, held longer, at the start of the new note.
+
+                geminate = true;
+                let syllable = &mut self.current_note.as_mut().unwrap().syllable;
+                syllable.final_vowels.pop();
+                syllable.long_final_vowels.pop();
             }
         }
-        let frequency = 440.0 * f32::powf(2.0, (note_index-69) as f32/12.0);
+        let frequency = self.get_note_frequency(note_index);
         let mut delay = 0;
         for transition in &self.transitions {
             delay = i64::max(delay, transition.end-self.step);
@@ -323,9 +935,10 @@ impl Director {
             if !continuous {
                 // Play any final vowels from the previous note.
 
-                prev_vowel = Some(note.syllable.main_vowel);
-                for c in &note.syllable.final_vowels.clone() {
-                    let (vowel_delay, vowel_transition_time) = self.get_vowel_timing(*c, true);
+                prev_vowel = Some(note.syllable.sustained_vowel());
+                let long_final_vowels = note.syllable.long_final_vowels.clone();
+                for (i, c) in note.syllable.final_vowels.clone().iter().enumerate() {
+                    let (vowel_delay, vowel_transition_time) = self.get_vowel_timing(*c, true, long_final_vowels[i]);
                     delay = self.add_transient_vowel(delay, 0, prev_vowel, *c, vowel_delay, vowel_transition_time, true, true, current_note_index);
                     prev_vowel = Some(*c);
                 }
@@ -344,7 +957,7 @@ impl Director {
             else {
                 first_vowel = new_syllable.main_vowel;
             }
-            let mut final_envelope = f32::min(1.0, self.phonemes.get_amplification(first_vowel));
+            let mut final_envelope = self.velocity_scale*f32::min(1.0, self.phonemes.get_amplification(first_vowel));
             if current_note_index > note_index {
                 final_envelope = 0.5*(final_envelope+min_envelope);
             }
@@ -364,9 +977,9 @@ impl Director {
                 None => new_syllable.main_vowel
             };
             for i in 0..new_syllable.initial_consonants.len() {
-                let mut time_scale = if has_current_note {0.8} else {1.0};
-                if new_syllable.initial_consonants.len() > 1 {
-                    time_scale *= 0.8;
+                let mut time_scale = Self::cluster_time_scale(new_syllable.initial_consonants.len()) * if has_current_note {0.8} else {1.0};
+                if geminate && i == 0 {
+                    time_scale *= GEMINATE_TIME_SCALE;
                 }
                 let (delay_to_consonant, delay_to_vowel, offset) = self.add_consonant(delay, new_syllable.initial_consonants[i], Some(adjacent_vowel), false, note_index, time_scale, 1.0);
                 envelope_offset = offset;
@@ -384,8 +997,8 @@ impl Director {
         // initial vowel, depending on what it is.
 
         let update_starts = |director: &mut Director, delay: &mut i64, has_updated_starts: &mut bool| {
-            if *delay < director.min_vowel_start {
-                let offset = director.min_vowel_start-*delay;
+            if *delay < min_vowel_start {
+                let offset = min_vowel_start-*delay;
                 for i in num_transitions..director.transitions.len() {
                     director.transitions[i].start += offset;
                     director.transitions[i].end += offset;
@@ -393,7 +1006,7 @@ impl Director {
                 for i in num_consonants..director.consonants.len() {
                     director.consonants[i].start += offset;
                 }
-                *delay = director.min_vowel_start;
+                *delay = min_vowel_start;
             }
             *has_updated_starts = true;
         };
@@ -403,11 +1016,15 @@ impl Director {
         let mut has_updated_starts = false;
         let mut attack_time = if new_syllable.initial_consonants.len() == 0 {1000+(10000.0*(1.0-self.attack_rate)) as i64} else {0};
         if !continuous {
-            for c in &new_syllable.initial_vowels {
+            for (i, c) in new_syllable.initial_vowels.iter().enumerate() {
                 if !has_updated_starts && *c != 'l' && *c != 'm' && *c != 'n' {
                     update_starts(self, &mut delay, &mut has_updated_starts);
                 }
-                let (vowel_delay, vowel_transition_time) = self.get_vowel_timing(*c, false);
+                let (mut vowel_delay, mut vowel_transition_time) = self.get_vowel_timing(*c, false, new_syllable.long_initial_vowels[i]);
+                if geminate && i == 0 && new_syllable.initial_consonants.is_empty() {
+                    vowel_delay = (vowel_delay as f32*GEMINATE_TIME_SCALE) as i64;
+                    vowel_transition_time = (vowel_transition_time as f32*GEMINATE_TIME_SCALE) as i64;
+                }
                 delay = self.add_transient_vowel(delay, envelope_offset, prev_vowel, *c, vowel_delay, vowel_transition_time.max(attack_time), false, has_current_note, note_index);
                 attack_time = 0;
                 prev_vowel = Some(*c);
@@ -417,49 +1034,97 @@ impl Director {
             update_starts(self, &mut delay, &mut has_updated_starts);
         }
 
-        // Start the main vowel playing.
+        // Start the main vowel playing.  If we are continuing a syllable that glided to a different
+        // vowel, stay on that one.
 
-        let shape = self.phonemes.get_vowel_shape(new_syllable.main_vowel).unwrap().clone();
-        let nasal_coupling = self.phonemes.get_nasal_coupling(new_syllable.main_vowel);
-        let transition_time = if has_current_note || new_syllable.initial_vowels.len() > 0 || new_syllable.initial_consonants.len() > 0 {self.vowel_transition_time} else {0};
+        let main_vowel = continued_glide.unwrap_or(new_syllable.main_vowel);
+        let nasal_coupling = self.phonemes.get_nasal_coupling(main_vowel);
+        let mut transition_time = if has_current_note || new_syllable.initial_vowels.len() > 0 || new_syllable.initial_consonants.len() > 0 {self.vowel_transition_time} else {0};
+        if new_syllable.long_main_vowel {
+            transition_time *= 2;
+        }
         if prev_vowel.is_some() {
-            self.add_vowel_transition(delay, prev_vowel.unwrap(), new_syllable.main_vowel, self.vowel_transition_time, note_index);
+            self.add_vowel_transition(delay, prev_vowel.unwrap(), main_vowel, transition_time, note_index);
         }
         else {
-            self.add_shape_transition(delay, transition_time, shape, nasal_coupling, note_index, true)
+            self.shape_buffer.clone_from(self.phonemes.get_vowel_shape(main_vowel).unwrap());
+            self.add_shape_transition(delay, transition_time, nasal_coupling, note_index, true)
+        }
+
+        // If the syllable has a glide, move smoothly to the target vowel once the main vowel is
+        // established.
+
+        if let Some(glide) = new_syllable.glide {
+            if !continuous {
+                self.add_vowel_transition(delay+transition_time, main_vowel, glide, self.glide_time, note_index);
+            }
         }
 
         // Adjust the envelope for the new note.  If accent is enabled, overshoot it then come back down.
 
-        let amplification = self.phonemes.get_amplification(new_syllable.main_vowel);
-        let max_amplitude = if self.accent {amplification*(1.0+2.5*velocity)} else {amplification};
-        let (_vowel_delay, vowel_transition_time) = self.get_vowel_timing(new_syllable.main_vowel, false);
+        let amplification = self.velocity_scale*self.phonemes.get_amplification(new_syllable.main_vowel);
+        let max_amplitude = if self.accent {amplification*(1.0+self.accent_gain*velocity)} else {amplification};
+        let (_vowel_delay, vowel_transition_time) = self.get_vowel_timing(new_syllable.main_vowel, false, new_syllable.long_main_vowel);
         attack_time = vowel_transition_time.max(attack_time);
+        if let Some(c) = new_syllable.initial_consonants.last() {
+            if self.phonemes.is_glottal_stop(*c) && new_syllable.initial_vowels.len() == 0 {
+                // After a glottal stop, the vowel begins with a hard attack.
+
+                attack_time = 500;
+            }
+        }
         self.add_transition(delay-envelope_offset, attack_time, TransitionData::EnvelopeChange {
             start_envelope: self.envelope_after_transitions,
             end_envelope: max_amplitude
         });
         if self.accent {
-            self.add_transition(delay-envelope_offset+attack_time, 4000, TransitionData::EnvelopeChange {
+            self.add_transition(delay-envelope_offset+attack_time, self.accent_decay, TransitionData::EnvelopeChange {
                 start_envelope: max_amplitude,
                 end_envelope: amplification
             });
         }
 
+        // Unless the note is legato, the attack follows the selected curve.  When starting from
+        // silence, also make sure the voices fade in smoothly no matter how fast the attack is.
+        // Otherwise the first note can begin with a click.
+
+        if !has_current_note {
+            let first_rise = self.transitions[num_transitions..].iter_mut().find(|t| match t.data {
+                TransitionData::EnvelopeChange {end_envelope, ..} => end_envelope > 0.0,
+                _ => false
+            });
+            if let Some(t) = first_rise {
+                t.curve = self.attack_shape;
+                if cold_start {
+                    t.end = t.end.max(t.start+COLD_START_FADE_IN);
+                    let max_delay = *self.voice_delays.iter().max().unwrap_or(&0);
+                    self.fade_in = (t.start, t.end.min(t.start+COLD_START_SMOOTHING)+max_delay);
+                }
+            }
+        }
+
         // Record the note we're now playing.
 
         let note = Note {
             syllable: new_syllable,
-            note_index: note_index
+            note_index: note_index,
+            min_vowel_start
         };
         self.current_note = Some(note);
         self.update_sound();
+        self.sort_consonants();
         Ok(())
     }
 
     /// End the current note.  Because this is a monophonic instrument, note_on() automatically
     /// ends the current note as well.
-    fn note_off(&mut self, legato: bool, sustain: bool) {
+    pub fn note_off(&mut self) {
+        self.end_note(false, false);
+    }
+
+    /// This does the work for note_off().  If legato is true, a new note is about to begin
+    /// without a break.
+    fn end_note(&mut self, legato: bool, sustain: bool) {
         let mut delay = 0;
         let num_transitions = self.transitions.len();
         for transition in &self.transitions {
@@ -472,9 +1137,10 @@ impl Director {
         let mut note_index = -1;
         if let Some(note) = &self.current_note {
             note_index = note.note_index;
-            final_vowel = Some(note.syllable.main_vowel);
-            for c in &note.syllable.final_vowels.clone() {
-                let (vowel_delay, vowel_transition_time) = self.get_vowel_timing(*c, true);
+            final_vowel = Some(note.syllable.sustained_vowel());
+            let long_final_vowels = note.syllable.long_final_vowels.clone();
+            for (i, c) in note.syllable.final_vowels.clone().iter().enumerate() {
+                let (vowel_delay, vowel_transition_time) = self.get_vowel_timing(*c, true, long_final_vowels[i]);
                 delay = self.add_transient_vowel(delay, 0, final_vowel, *c, vowel_delay, vowel_transition_time, true, legato, note_index);
                 final_vowel = Some(*c);
             }
@@ -483,13 +1149,17 @@ impl Director {
         // Update the start times of any vowels we just added so the final consonants will be
         // right on the beat.
 
-        if !legato && delay < self.min_vowel_start {
-            let offset = self.min_vowel_start-delay;
+        let min_vowel_start = match &self.current_note {
+            Some(note) => note.min_vowel_start,
+            None => self.min_vowel_start
+        };
+        if !legato && delay < min_vowel_start {
+            let offset = min_vowel_start-delay;
             for i in num_transitions..self.transitions.len() {
                 self.transitions[i].start += offset;
                 self.transitions[i].end += offset;
             }
-            delay = self.min_vowel_start;
+            delay = min_vowel_start;
         }
 
         // Determine how quickly to stop the sound.  This may get modified if the first final consonant
@@ -516,10 +1186,7 @@ impl Director {
                 off_time = off_time.min(first_consonant.transition_time);
             }
             for c in consonants.iter() {
-                let mut time_scale = if legato {0.8} else {1.0};
-                if consonants.len() > 1 {
-                    time_scale *= 0.8;
-                }
+                let time_scale = Self::cluster_time_scale(consonants.len()) * if legato {0.8} else {1.0};
                 // Emphasize the final consonant at the end of a line.
                 let amplify = if legato || consonants.len() > 1 {1.0} else {1.2};
                 let (delay_to_consonant, _delay_to_vowel, _envelope_offset) = self.add_consonant(delay, *c, final_vowel, true, note_index, time_scale, amplify);
@@ -531,7 +1198,23 @@ impl Director {
 
         let end_envelope = if sustain {0.1} else {0.0};
         self.add_transition(stop_envelope_time, off_time, TransitionData::EnvelopeChange {start_envelope: self.envelope_after_transitions, end_envelope: end_envelope});
+        if !legato {
+            self.transitions.last_mut().unwrap().curve = self.release_shape;
+        }
         self.current_note = None;
+        self.sort_consonants();
+    }
+
+    /// Restore the order of the consonant queue after new consonants are added.  They are almost
+    /// always added in order already, so an insertion sort is fast and does not allocate.
+    fn sort_consonants(&mut self) {
+        for i in 1..self.consonants.len() {
+            let mut j = i;
+            while j > 0 && self.consonants[j-1].start > self.consonants[j].start {
+                self.consonants.swap(j-1, j);
+                j -= 1;
+            }
+        }
     }
 
     /// Add the Transitions to play a transient vowel (an initial or final vowel that sounds
@@ -541,12 +1224,12 @@ impl Director {
             self.add_vowel_transition(delay, prev_vowel.unwrap(), c, vowel_transition_time, note_index);
         }
         else {
-            let shape = self.phonemes.get_vowel_shape(c).unwrap();
             let nasal_coupling = self.phonemes.get_nasal_coupling(c);
-            self.add_shape_transition(delay, vowel_transition_time, shape.clone(), nasal_coupling, note_index, true);
+            self.shape_buffer.clone_from(self.phonemes.get_vowel_shape(c).unwrap());
+            self.add_shape_transition(delay, vowel_transition_time, nasal_coupling, note_index, true);
         }
         let scale = if legato {0.9} else if is_final {0.25} else {0.7};
-        let amplification = scale*self.phonemes.get_amplification(c);
+        let amplification = scale*self.velocity_scale*self.phonemes.get_amplification(c);
         self.add_transition(delay-envelope_offset, vowel_transition_time, TransitionData::EnvelopeChange {
             start_envelope: self.envelope_after_transitions,
             end_envelope: amplification
@@ -556,15 +1239,17 @@ impl Director {
 
     /// Add the Transitions to smoothly change the vocal tract shape between two vowels.
     fn add_vowel_transition(&mut self, delay: i64, vowel1: char, vowel2: char, vowel_transition_time: i64, note_index: i32) {
-        let shape = self.phonemes.get_vowel_shape(vowel2).unwrap().clone();
         let nasal_coupling = self.phonemes.get_nasal_coupling(vowel2);
         if let Some(intermediate_shape) = self.phonemes.get_intermediate_shape(vowel1, vowel2) {
             let intermediate_coupling = 0.5*(self.nasal_coupling_after_transitions+nasal_coupling);
-            self.add_shape_transition(delay, vowel_transition_time/2, intermediate_shape.clone(), intermediate_coupling, note_index, true);
-            self.add_shape_transition(delay+vowel_transition_time/2, vowel_transition_time/2, shape, nasal_coupling, note_index, true);
+            self.shape_buffer.clone_from(&intermediate_shape);
+            self.add_shape_transition(delay, vowel_transition_time/2, intermediate_coupling, note_index, true);
+            self.shape_buffer.clone_from(self.phonemes.get_vowel_shape(vowel2).unwrap());
+            self.add_shape_transition(delay+vowel_transition_time/2, vowel_transition_time/2, nasal_coupling, note_index, true);
         }
         else {
-            self.add_shape_transition(delay, vowel_transition_time, shape, nasal_coupling, note_index, true);
+            self.shape_buffer.clone_from(self.phonemes.get_vowel_shape(vowel2).unwrap());
+            self.add_shape_transition(delay, vowel_transition_time, nasal_coupling, note_index, true);
         }
     }
 
@@ -573,7 +1258,16 @@ impl Director {
     fn add_consonant(&mut self, delay: i64, c: char, adjacent_vowel: Option<char>, is_final: bool, note_index: i32, time_scale: f32, amplify: f32) -> (i64, i64, i64) {
         let mut consonant = self.phonemes.get_consonant(c, adjacent_vowel, is_final, time_scale).unwrap();
         consonant.start = self.step+delay+consonant.delay;
-        consonant.volume *= 2.5*self.consonant_volume*amplify;
+        let gain = self.consonant_gains.get(&c).copied().unwrap_or(1.0);
+        consonant.volume *= 2.5*self.consonant_volume*gain*amplify;
+        if self.phonemes.is_unvoiced_stop(c) {
+            // Scale the burst of noise that follows the release.  An aspiration of 0.5 leaves it
+            // unchanged, 0.0 halves it, and 1.0 doubles it.
+
+            let scale = f32::powf(2.0, 2.0*(self.aspiration-0.5));
+            consonant.off_time = (scale*consonant.off_time as f32) as i64;
+            consonant.volume *= scale.sqrt();
+        }
         let delay_to_consonant = consonant.delay+consonant.on_time+consonant.off_time;
         let mut delay_to_vowel = consonant.delay;
         let mut envelope_offset = 0;
@@ -589,91 +1283,270 @@ impl Director {
         if let Some(vowel) = adjacent_vowel {
             let nasal_coupling = self.phonemes.get_nasal_coupling(vowel);
             if is_final {
-                let end_shape = self.phonemes.get_consonant_shape(&consonant, vowel).unwrap().clone();
-                self.add_shape_transition(delay, consonant.transition_time, end_shape, nasal_coupling, note_index, false);
+                self.shape_buffer.clone_from(&self.phonemes.get_consonant_shape(&consonant, vowel).unwrap());
+                self.add_shape_transition(delay, consonant.transition_time, nasal_coupling, note_index, false);
                 delay_to_vowel += consonant.transition_time;
             }
             else {
-                let start_shape = self.phonemes.get_consonant_shape(&consonant, vowel).unwrap().clone();
-                let end_shape = self.phonemes.get_vowel_shape(vowel).unwrap().clone();
-                self.add_shape_transition(delay, 1000, start_shape, 0.0, note_index, false);
-                self.add_shape_transition(delay+1000, consonant.transition_time, end_shape, nasal_coupling, note_index, true);
+                self.shape_buffer.clone_from(&self.phonemes.get_consonant_shape(&consonant, vowel).unwrap());
+                self.add_shape_transition(delay, 1000, 0.0, note_index, false);
+                self.shape_buffer.clone_from(self.phonemes.get_vowel_shape(vowel).unwrap());
+                self.add_shape_transition(delay+1000, consonant.transition_time, nasal_coupling, note_index, true);
                 delay_to_vowel += consonant.transition_time+1000;
             }
             if consonant.voiced {
                 envelope_offset = consonant.transition_time;
             }
         }
+        if self.phonemes.is_aspirate(c) {
+            // Produce noise at the glottis, rising and then falling as the vowel begins.
+
+            let level = 0.3*self.consonant_volume*gain*amplify;
+            let start = delay+consonant.delay;
+            self.add_transition(start, consonant.on_time, TransitionData::AspirationChange {start_aspiration: 0.0, end_aspiration: level});
+            self.add_transition(start+consonant.on_time, consonant.off_time, TransitionData::AspirationChange {start_aspiration: level, end_aspiration: 0.0});
+        }
+        if self.phonemes.is_glottal_stop(c) {
+            // Close the glottis, briefly cutting off the sound.
+
+            self.add_transition(delay, consonant.on_time, TransitionData::EnvelopeChange {
+                start_envelope: self.envelope_after_transitions,
+                end_envelope: 0.0
+            });
+        }
         self.consonants.push(consonant);
         (delay_to_consonant, delay_to_vowel, envelope_offset)
     }
 
     /// Add a Transition to the queue.
     fn add_transition(&mut self, delay: i64, duration: i64, data: TransitionData) {
-        let transition = Transition { start: self.step+delay, end: self.step+delay+duration, data: data };
+        let transition = Transition { start: self.step+delay, end: self.step+delay+duration, curve: 0.0, data: data };
         match &transition.data {
             TransitionData::EnvelopeChange {start_envelope: _, end_envelope} => {
                 self.envelope_after_transitions = *end_envelope;
             }
             TransitionData::ShapeChange {start_shape: _, end_shape, start_nasal_coupling: _, end_nasal_coupling} => {
-                self.shape_after_transitions = end_shape.clone();
+                self.shape_after_transitions.clone_from(end_shape);
                 self.nasal_coupling_after_transitions = *end_nasal_coupling;
             }
             TransitionData::FrequencyChange {start_frequency: _, end_frequency} => {
                 self.frequency_after_transitions = *end_frequency;
             }
+            TransitionData::AspirationChange {..} => {}
         }
         self.transitions.push(transition);
     }
 
-    /// Add a ShapeChange transition to the queue.
-    fn add_shape_transition(&mut self, delay: i64, duration: i64, mut end_shape: Vec<f32>, end_nasal_coupling: f32, note_index: i32, adjust_for_pitch: bool) {
-        if end_nasal_coupling == 0.0 && self.brightness < 1.0 {
+    /// Compute the shapes that low brightness and high notes blend toward.  The high shape is
+    /// mostly high_vowel with some of dark_vowel mixed in.  If either vowel is a custom one that
+    /// no longer exists, the default is restored.
+    fn update_timbre_targets(&mut self) {
+        if self.phonemes.get_vowel_shape(self.dark_vowel).is_none() {
+            self.dark_vowel = DEFAULT_DARK_VOWEL;
+        }
+        if self.phonemes.get_vowel_shape(self.high_vowel).is_none() {
+            self.high_vowel = DEFAULT_HIGH_VOWEL;
+        }
+        self.dark_shape.clone_from(self.phonemes.get_vowel_shape(self.dark_vowel).unwrap());
+        self.high_shape.clone_from(self.phonemes.get_vowel_shape(self.high_vowel).unwrap());
+        for i in 0..self.high_shape.len() {
+            self.high_shape[i] = 0.7*self.high_shape[i] + 0.3*self.dark_shape[i];
+        }
+    }
+
+    /// Add a ShapeChange transition to the queue.  The shape to change to is taken from
+    /// shape_buffer, which the caller must fill in first.  This avoids needing to allocate
+    /// or copy vowel shapes every time a transition is added.
+    fn add_shape_transition(&mut self, delay: i64, duration: i64, mut end_nasal_coupling: f32, note_index: i32, adjust_for_pitch: bool) {
+        if self.hum {
+            // When humming, the mouth stays closed no matter what is being sung.
+
+            self.shape_buffer.clone_from(self.phonemes.get_vowel_shape('m').unwrap());
+            end_nasal_coupling = self.phonemes.get_nasal_coupling('m');
+        }
+        let end_shape = &mut self.shape_buffer;
+        if end_nasal_coupling == 0.0 && self.brightness < 1.0 && self.brightness_mode == BrightnessMode::ShapeBlend {
             let blend = (1.0-self.brightness)*0.2;
             for i in 0..end_shape.len() {
                 end_shape[i] = (1.0-blend)*end_shape[i] + blend*self.dark_shape[i];
             }
         }
         if note_index > self.high_blend_note && end_nasal_coupling == 0.0 && adjust_for_pitch {
-            let blend = self.high_blend_fraction * (note_index-self.high_blend_note) as f32 / (self.highest_note-self.high_blend_note) as f32;
+            let range = i32::max(1, self.highest_note-self.high_blend_note);
+            let blend = (self.high_blend_fraction * (note_index-self.high_blend_note) as f32 / range as f32).clamp(0.0, 1.0);
             for i in 0..end_shape.len() {
                 end_shape[i] = (1.0-blend)*end_shape[i] + blend*self.high_shape[i];
             }
         }
-        let mut end_shapes = vec![end_shape; self.voices.len()];
-        for shape in &mut end_shapes {
-            for x in shape {
-                *x *= 0.9 + 0.2*self.random.get_uniform();
+        let end_nasal_coupling = f32::min(end_nasal_coupling+self.nasality, MAX_NASAL_COUPLING);
+        let mut start_shapes = self.take_shapes();
+        let mut end_shapes = self.take_shapes();
+        for i in 0..self.voices.len() {
+            start_shapes[i].copy_from_slice(&self.shape_after_transitions[i]);
+            for j in 0..self.shape_buffer.len() {
+                end_shapes[i][j] = self.shape_buffer[j]*(0.9 + 0.2*self.random.get_uniform());
             }
         }
         self.add_transition(delay, duration, TransitionData::ShapeChange {
-            start_shape: self.shape_after_transitions.clone(),
+            start_shape: start_shapes,
             end_shape: end_shapes,
             start_nasal_coupling: self.nasal_coupling_after_transitions,
             end_nasal_coupling: end_nasal_coupling
         });
     }
 
+    /// Get a set of per-voice shape buffers for use in a ShapeChange transition.  Buffers from
+    /// finished transitions are reused when available.
+    fn take_shapes(&mut self) -> Vec<Vec<f32>> {
+        match self.shape_pool.pop() {
+            Some(shapes) => shapes,
+            None => vec![vec![0.0; self.phonemes.get_vocal_length()]; self.voices.len()]
+        }
+    }
+
+    /// Get the voice part being sung.
+    pub fn voice_part(&self) -> VoicePart {
+        self.voice_part
+    }
+
+    /// Get the number of voices singing in unison.
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Get the current values of all settings that can be changed with Messages.
+    pub fn get_config(&self) -> DirectorConfig {
+        DirectorConfig {
+            volume: self.volume,
+            expression: self.expression,
+            pitch_bend: 12.0*self.bend.log2(),
+            tuning_table: self.tuning_table.clone(),
+            vibrato: self.vibrato,
+            vibrato_intensity_coupling: self.vibrato_intensity_coupling,
+            tremolo_rate: self.tremolo_rate,
+            tremolo_amount: self.tremolo_amount,
+            intensity: self.intensity,
+            brightness: self.brightness,
+            brightness_mode: self.brightness_mode,
+            dark_vowel: self.dark_vowel,
+            high_vowel: self.high_vowel,
+            consonant_volume: self.consonant_volume,
+            consonant_gains: self.consonant_gains.clone(),
+            vowel_amplification: self.vowel_amplification.clone(),
+            attack_rate: self.attack_rate,
+            attack_shape: self.attack_shape,
+            release_rate: self.release_rate,
+            release_shape: self.release_shape,
+            accent: self.accent,
+            accent_gain: self.accent_gain,
+            accent_decay_ms: (self.accent_decay as f32*1000.0/SAMPLE_RATE as f32).round() as i64,
+            velocity_curve: self.velocity_curve,
+            aspiration: self.aspiration,
+            trill_taps: self.trill_taps,
+            glide_time: self.glide_time,
+            out_of_range_behavior: self.out_of_range_behavior,
+            legato_mode: self.legato_mode,
+            stereo_width: self.stereo_width,
+            exciter_strength: self.exciter_strength,
+            ensemble_depth: self.ensemble_depth,
+            ensemble_voices: self.ensemble_voices,
+            output_gain_db: self.output_gain_db,
+            air_gain_db: self.air_gain_db,
+            oversampling: self.oversampling,
+            mono: self.mono,
+            formant_spread: self.formant_spread,
+            hum: self.hum,
+            nasality: self.nasality,
+            whisper: self.whisper,
+            subglottal_coupling: self.subglottal_coupling,
+            vocal_damping: self.vocal_damping,
+            nasal_damping: self.nasal_damping,
+            rd_base: self.rd_base,
+            rd_pitch_scale: self.rd_pitch_scale,
+            rd_volume_scale: self.rd_volume_scale,
+            rd_intensity_scale: self.rd_intensity_scale,
+            humanize: self.humanize,
+            min_vowel_start: self.min_vowel_start,
+            max_voice_delay: self.max_voice_delay,
+            entrance_jitter: self.entrance_jitter,
+            randomize: self.randomize
+        }
+    }
+
+    /// Change all settings to the values in a DirectorConfig.  This is equivalent to handling
+    /// every Message returned by config.messages().
+    pub fn apply_config(&mut self, config: &DirectorConfig) {
+        for message in config.messages() {
+            self.handle_message(message);
+        }
+    }
+
+    /// Get a snapshot of the Director's current state.  The Director does not know about phrases,
+    /// so the syllable index is whatever was last set with SetSyllableIndex.
+    pub fn current_state(&self) -> DirectorState {
+        let min_delay = *self.voice_delays.iter().min().unwrap_or(&0);
+        let max_delay = *self.voice_delays.iter().max().unwrap_or(&0);
+        let active_consonant = self.consonants.iter().find(|c| {
+            let j = self.step-c.start;
+            j > min_delay && j-max_delay < c.on_time+c.off_time
+        }).map(|c| c.sampa);
+        let envelope_level = if self.envelope.len() == 0 {0.0} else {self.envelope.iter().sum::<f32>()/self.envelope.len() as f32};
+        DirectorState {
+            note: self.current_note.as_ref().map(|note| note.note_index),
+            vowel: self.current_note.as_ref().map(|note| note.syllable.sustained_vowel()),
+            envelope_level: envelope_level,
+            active_consonant: active_consonant,
+            syllable_index: self.syllable_index
+        }
+    }
+
+    /// Get the current shape of the first voice's vocal tract, along with the degree of coupling
+    /// to the nasal cavity.  This is useful for visualizing what the voices are doing.
+    pub fn get_vocal_shape(&self) -> Option<(&Vec<f32>, f32)> {
+        self.voices.first().map(|voice| (voice.get_vocal_shape(), voice.get_nasal_coupling()))
+    }
+
+    /// Get the instantaneous pressure in each segment of the first voice's vocal tract.  The
+    /// values are stored in pressure, replacing its contents.  Together with get_vocal_shape(),
+    /// this can be used to animate the standing waves in the vocal tract.
+    pub fn get_tract_pressure(&self, pressure: &mut Vec<f32>) {
+        match self.voices.first() {
+            Some(voice) => voice.get_tract_pressure(pressure),
+            None => pressure.clear()
+        }
+    }
+
     /// This is called repeated to generate audio data.  Each generates the two channels
     /// (left, right) for the next sample.
     pub fn generate(&mut self) -> (f32, f32) {
-        // Deal with the queues of Messages and Transitions.  This only needs to be done occassionally.
+        // Deal with the queues of Messages and Transitions.  Transitions only need to be updated
+        // occasionally, but Messages are checked more often to keep latency low.  When a Message
+        // arrives, the Transitions are updated immediately so it takes effect without delay.
 
-        if self.step%200 == 0 {
-            self.process_messages();
+        let mut update = self.step%200 == 0 || (self.step >= self.fade_in.0 && self.step < self.fade_in.1);
+        if self.step%self.message_interval == 0 {
+            update |= self.process_messages();
+        }
+        if update {
             self.update_transitions();
         }
 
-        // If there has been no glottal excitation and no consonant for a while, we can just
-        // return without doing any work.
+        // If there has been no glottal excitation and no consonant, and the voices have been
+        // silent for a while, we can just return without doing any work.  Waiting for the output
+        // to decay, rather than a fixed time, avoids cutting off long release tails.
 
         self.step += 1;
         if self.consonants.len() != 0 {
-            self.off_after_step = self.step+500;
+            self.off_after_step = self.step+SILENCE_TIME;
         }
         for e in &self.envelope {
             if *e > 0.0 {
-                self.off_after_step = self.step+500;
+                self.off_after_step = self.step+SILENCE_TIME;
+            }
+        }
+        for a in &self.aspiration_level {
+            if *a > 0.0 {
+                self.off_after_step = self.step+SILENCE_TIME;
             }
         }
         let mut left = 0.0;
@@ -681,7 +1554,18 @@ impl Director {
         let mut left_throat = 0.0;
         let mut right_throat = 0.0;
         if self.step < self.off_after_step {
-            let mut consonant_finished = self.consonants.len() > 0;
+            // Find the Consonants that have started for at least one voice.  The queue is ordered
+            // by start time, so the search can stop at the first one that has not.  Each one is
+            // initially marked as finished, and that gets cleared if any voice is still singing it.
+
+            let min_delay = *self.voice_delays.iter().min().unwrap_or(&0);
+            self.active_consonants.clear();
+            for (k, consonant) in self.consonants.iter().enumerate() {
+                if self.step-consonant.start-min_delay <= 0 {
+                    break;
+                }
+                self.active_consonants.push((k, true));
+            }
 
             // Loop over Voices and generate audio for each one.
 
@@ -690,7 +1574,9 @@ impl Director {
 
                 let mut consonant_noise = 0.0;
                 let mut consonant_position = 0;
-                for (k, consonant) in self.consonants.iter_mut().enumerate() {
+                for (k, consonant_finished) in self.active_consonants.iter_mut() {
+                    let consonant = &mut self.consonants[*k];
+
                     // Mono consonants are only sung by one voice (the one panned to the center).
                     // Others are sung by every voice.
 
@@ -698,7 +1584,10 @@ impl Director {
                         let j = self.step-consonant.start-self.voice_delays[i];
                         let mut consonant_duration = consonant.on_time+consonant.off_time;
                         if j > 0 {
-                            consonant_position = consonant.position;
+                            // Each consonant injects its noise at its own position in the vocal tract.  Only
+                            // take the position from a consonant that is actually sounding, so it always
+                            // matches the noise being injected.
+
                             if i < consonant.sample_indices.len() {
                                 // Use a sampled consonant.
 
@@ -706,6 +1595,7 @@ impl Director {
                                 consonant_duration = consonant.samples[index].len() as i64;
                                 if j < consonant_duration {
                                     consonant_noise = consonant.lowpass.process(50.0*consonant.volume*consonant.samples[index][j as usize]);
+                                    consonant_position = consonant.position;
                                 }
                             }
                             else {
@@ -714,164 +1604,492 @@ impl Director {
                                 if j < consonant.on_time {
                                     let volume = consonant.volume*(j as f32 / consonant.on_time as f32);
                                     consonant_noise = volume*consonant.filter.process(2.0*self.random.get_uniform()-1.0);
+                                    consonant_position = consonant.position;
                                 }
                                 else if j < consonant_duration {
                                     let k = j-consonant.on_time;
                                     let volume = consonant.volume*((consonant.off_time-k) as f32 / consonant.off_time as f32);
                                     consonant_noise = volume*consonant.filter.process(2.0*self.random.get_uniform()-1.0);
+                                    consonant_position = consonant.position;
                                 }
                             }
                         }
                         if consonant.mono {
                             consonant_noise *= (self.voices.len() as f32).sqrt();
                         }
-                        if j < consonant_duration && k == 0 {
-                            consonant_finished = false;
+                        if j < consonant_duration {
+                            *consonant_finished = false;
                         }
                     }
                 }
                 if self.volume < 0.5 {
                     consonant_noise *= 0.25 + 1.5*self.volume;
                 }
+                consonant_noise *= self.expression;
+                if self.hum {
+                    consonant_noise = 0.0;
+                }
 
                 // Generate audio for the voice, injecting the consonant noise if appropriate.
+                // Muted voices are still generated so they remain in sync with the others.
 
-                let (mouth_output, throat_output) = self.voices[i].generate(self.step, consonant_noise, consonant_position);
+                let (mut mouth_output, mut throat_output) = self.voices[i].generate(self.step, consonant_noise, consonant_position);
+                if self.voice_muted[i] {
+                    continue;
+                }
+                if self.voice_fade[i] < 1.0 {
+                    self.voice_fade[i] = (self.voice_fade[i] + 1.0/NEW_VOICE_FADE_IN as f32).min(1.0);
+                    mouth_output *= self.voice_fade[i];
+                    throat_output *= self.voice_fade[i];
+                }
                 left += self.voice_pan[i].cos()*mouth_output;
                 right += self.voice_pan[i].sin()*mouth_output;
                 left_throat += self.voice_pan[i].cos()*throat_output;
                 right_throat += self.voice_pan[i].sin()*throat_output;
             }
-            if consonant_finished {
-                self.consonants.remove(0);
+            for &(k, consonant_finished) in self.active_consonants.iter().rev() {
+                if consonant_finished {
+                    self.consonants.remove(k);
+                }
+            }
+            if left.abs()+right.abs()+left_throat.abs()+right_throat.abs() > SILENCE_THRESHOLD {
+                self.off_after_step = self.step+SILENCE_TIME;
             }
         }
+        if self.brightness_mode == BrightnessMode::SpectralTilt {
+            left = self.left_tilt.process(left);
+            right = self.right_tilt.process(right);
+        }
         left = self.left_exciter.process(left, self.exciter_strength);
         right = self.right_exciter.process(right, self.exciter_strength);
-        (0.08*(left+0.3*left_throat), 0.08*(right+0.3*right_throat))
+        (left, right) = self.ensemble.process(left, right);
+        if self.air_gain_db != 0.0 {
+            left = self.left_air.process(left);
+            right = self.right_air.process(right);
+        }
+        let gain = 0.08*self.output_gain;
+        left = gain*(left+0.3*left_throat);
+        right = gain*(right+0.3*right_throat);
+
+        // In mono mode, sum the two channels with an equal power (-3 dB) downmix.
+
+        if self.mono {
+            let sum = std::f32::consts::FRAC_1_SQRT_2*(left+right);
+            return (sum, sum);
+        }
+        (left, right)
+    }
+
+    /// Generate audio for a block of samples, writing them to buffer as interleaved (left, right)
+    /// pairs.  The number of samples generated is half the length of buffer.
+    pub fn generate_interleaved(&mut self, buffer: &mut [f32]) {
+        for frame in buffer.chunks_exact_mut(2) {
+            (frame[0], frame[1]) = self.generate();
+        }
     }
 
     /// This is called occasionally by generate().  It processes any Messages that have been
     /// received since the last call.
-    fn process_messages(&mut self) {
-        loop {
-            match self.message_receiver.try_recv() {
-                Ok(message) => {
-                    match message {
-                        Message::Reinitialize {voice_part, voice_count} => {
-                            self.initialize_voices(voice_part, voice_count);
-                        }
-                        Message::NoteOn {syllable, note_index, velocity, continue_syllable} => {
-                            let _ = self.note_on(&syllable, note_index, velocity, continue_syllable);
-                        }
-                        Message::NoteOff => {
-                            self.note_off(false, false);
-                        }
-                        Message::SetVolume {volume} => {
-                            self.volume = volume;
-                            self.update_volume();
-                            self.update_sound();
-                        }
-                        Message::SetPitchBend {semitones} => {
-                            self.bend = f32::powf(2.0, semitones as f32/12.0);
-                            self.update_frequency();
-                        }
-                        Message::SetVibrato {vibrato} => {
-                            self.vibrato = vibrato;
-                            self.update_vibrato();
-                        }
-                        Message::SetIntensity {intensity} => {
-                            self.intensity = intensity;
-                            self.update_sound();
-                        }
-                        Message::SetBrightness {brightness} => {
-                            self.brightness = brightness;
-                        }
-                        Message::SetConsonantVolume {volume} => {
-                            self.consonant_volume = volume;
-                        }
-                        Message::SetAttackRate {attack} => {
-                            self.attack_rate = attack;
-                        }
-                        Message::SetReleaseRate {release} => {
-                            self.release_rate = release;
-                        }
-                        Message::SetAccent {accent} => {
-                            self.accent = accent;
-                        }
-                        Message::SetStereoWidth {width} => {
-                            self.stereo_width = width;
-                            self.update_pan_positions();
-                        }
-                        Message::SetExciterStrength {strength} => {
-                            self.exciter_strength = strength;
-                        }
-                        Message::SetMinVowelStartTime {samples} => {
-                            self.min_vowel_start = samples;
-                        }
-                        Message::SetMaxVoiceDelay {max_delay} => {
-                            self.max_voice_delay = max_delay;
-                            self.update_voice_delays();
-                        }
-                        Message::SetDelays {vowel_delay, vowel_transition_time, consonant_delay, consonant_transition_time} => {
-                            // This message is only used for develoment.
-                            self.vowel_delay = vowel_delay;
-                            self.vowel_transition_time = vowel_transition_time;
-                            self.consonant_delay = consonant_delay;
-                            self.consonant_transition_time = consonant_transition_time;
-                        }
-                        Message::SetConsonants {on_time, off_time, volume, position, frequency, bandwidth} => {
-                            // This message is only used for develoment.
-                            self.consonant_on_time = on_time;
-                            self.consonant_off_time = off_time;
-                            self.consonant_volume2 = volume;
-                            self.consonant_position = position;
-                            self.consonant_frequency = frequency;
-                            self.consonant_bandwidth = bandwidth;
-                        }
-                        Message::SetRandomize {randomize} => {
-                            self.randomize = randomize;
-                        }
+    fn process_messages(&mut self) -> bool {
+        let mut received = false;
+        while let Ok(message) = self.message_receiver.try_recv() {
+            self.handle_message(message);
+            received = true;
+        }
+        received
+    }
+
+    /// Process a Message immediately, rather than sending it through the channel.  This is
+    /// useful when the Director is controlled from the same thread that calls generate().
+    pub fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::Reinitialize {voice_part, voice_count} => {
+                self.initialize_voices(voice_part, voice_count);
+            }
+            Message::SetVoiceCount {count} => {
+                self.set_voice_count(count);
+            }
+            Message::NoteOn {syllable, note_index, velocity, continue_syllable, vowel_start} => {
+                let _ = self.note_on_with_vowel_start(&syllable, note_index, velocity, continue_syllable, vowel_start);
+            }
+            Message::NoteOff => {
+                self.note_off();
+            }
+            Message::SetSyllableIndex {index} => {
+                self.syllable_index = index;
+            }
+            Message::SetVolume {volume} => {
+                self.set_volume(volume);
+            }
+            Message::SetExpression {value} => {
+                self.set_expression(value);
+            }
+            Message::SetPitchBend {semitones} => {
+                self.set_pitch_bend(semitones);
+            }
+            Message::SetTuningTable {cents_per_note} => {
+                self.tuning_table = cents_per_note;
+            }
+            Message::SetVibrato {vibrato} => {
+                self.set_vibrato(vibrato);
+            }
+            Message::SetVibratoIntensityCoupling {amount} => {
+                self.vibrato_intensity_coupling = amount;
+                self.update_vibrato();
+            }
+            Message::SetTremolo {rate_hz, amount} => {
+                self.tremolo_rate = rate_hz.max(0.0);
+                self.tremolo_amount = amount.max(0.0);
+                self.update_sound();
+            }
+            Message::SetIntensity {intensity} => {
+                self.set_intensity(intensity);
+            }
+            Message::SetBrightness {brightness} => {
+                self.set_brightness(brightness);
+            }
+            Message::SetConsonantVolume {volume} => {
+                self.consonant_volume = volume;
+            }
+            Message::SetConsonantGain {consonant, gain} => {
+                if gain == 1.0 {
+                    self.consonant_gains.remove(&consonant);
+                }
+                else {
+                    self.consonant_gains.insert(consonant, gain.max(0.0));
+                }
+            }
+            Message::SetVowelAmplification {vowel, amplification} => {
+                // Unlike custom vowels, these are kept when the voices are reinitialized.
+
+                if Phonemes::vowels().contains(&vowel) {
+                    if amplification == Phonemes::default_amplification(vowel) {
+                        self.vowel_amplification.remove(&vowel);
                     }
+                    else {
+                        self.vowel_amplification.insert(vowel, amplification.max(0.0));
+                    }
+                    self.phonemes.set_amplification(vowel, amplification);
                 }
-                Err(_) => {
-                    break;
+            }
+            Message::SetAttackRate {attack} => {
+                self.attack_rate = attack;
+            }
+            Message::SetAttackShape {curve} => {
+                self.attack_shape = curve.clamp(-1.0, 1.0);
+            }
+            Message::SetReleaseRate {release} => {
+                self.release_rate = release;
+            }
+            Message::SetReleaseShape {curve} => {
+                self.release_shape = curve.clamp(-1.0, 1.0);
+            }
+            Message::SetAccent {accent} => {
+                self.accent = accent;
+            }
+            Message::SetAccentAmount {gain, decay_ms} => {
+                self.accent_gain = gain.max(0.0);
+                self.accent_decay = (decay_ms*SAMPLE_RATE as i64/1000).max(1);
+            }
+            Message::SetVelocityCurve {amount} => {
+                self.velocity_curve = amount;
+            }
+            Message::SetAspiration {amount} => {
+                self.aspiration = amount;
+            }
+            Message::SetTrill {taps} => {
+                self.trill_taps = taps;
+            }
+            Message::SetGlideTime {samples} => {
+                self.glide_time = samples;
+            }
+            Message::SetCustomVowel {vowel, shape, nasal_coupling} => {
+                let _ = self.set_custom_vowel(vowel, shape, nasal_coupling);
+            }
+            Message::SetIntermediateShape {vowel1, vowel2, shape} => {
+                // Like custom vowels, these are discarded when the voices are reinitialized.
+                let _ = self.phonemes.set_intermediate_shape(vowel1, vowel2, shape);
+            }
+            Message::SetConsonantSamples {consonant, samples} => {
+                // Like custom vowels, these are discarded when the voices are reinitialized.
+                let _ = self.phonemes.load_consonant_samples(consonant, samples);
+            }
+            Message::SetTimbreTargets {dark, high} => {
+                // Characters that are not vowels are ignored.
+
+                if self.phonemes.get_vowel_shape(dark).is_some() && self.phonemes.get_vowel_shape(high).is_some() {
+                    self.dark_vowel = dark;
+                    self.high_vowel = high;
+                    self.update_timbre_targets();
                 }
             }
+            Message::SetBrightnessMode {mode} => {
+                if mode != self.brightness_mode {
+                    self.left_tilt.reset();
+                    self.right_tilt.reset();
+                }
+                self.brightness_mode = mode;
+            }
+            Message::SetOutOfRangeBehavior {mode} => {
+                self.out_of_range_behavior = mode;
+            }
+            Message::SetLegatoMode {mode} => {
+                self.legato_mode = mode;
+            }
+            Message::SetVoiceMute {index, muted} => {
+                if index < self.voice_muted.len() {
+                    self.voice_muted[index] = muted;
+                }
+            }
+            Message::SetStereoWidth {width} => {
+                self.stereo_width = width;
+                self.update_pan_positions();
+            }
+            Message::SetExciterStrength {strength} => {
+                self.exciter_strength = strength;
+            }
+            Message::Breath {intensity} => {
+                self.breath(intensity);
+            }
+            Message::SetHumanize {amount} => {
+                self.humanize = amount.max(0.0);
+                self.update_sound();
+            }
+            Message::SetDamping {vocal, nasal} => {
+                // Values of 1.0 or more would make the waveguides unstable.
+
+                self.vocal_damping = vocal.clamp(0.9, 0.999);
+                self.nasal_damping = nasal.clamp(0.9, 0.999);
+                self.update_sound();
+            }
+            Message::SetVoicing {rd_base, rd_pitch_scale, rd_volume_scale, rd_intensity_scale} => {
+                self.rd_base = rd_base;
+                self.rd_pitch_scale = rd_pitch_scale;
+                self.rd_volume_scale = rd_volume_scale;
+                self.rd_intensity_scale = rd_intensity_scale;
+                self.update_sound();
+            }
+            Message::SetSubglottalCoupling {amount} => {
+                self.subglottal_coupling = amount.clamp(0.0, 1.0);
+                self.update_sound();
+            }
+            Message::SetWhisper {amount} => {
+                self.whisper = amount.clamp(0.0, 1.0);
+                self.update_sound();
+            }
+            Message::SetHum {enabled} => {
+                self.set_hum(enabled);
+            }
+            Message::SetNasality {coupling} => {
+                self.nasality = coupling.clamp(0.0, MAX_NASAL_COUPLING);
+            }
+            Message::SetFormantSpread {amount} => {
+                self.formant_spread = amount;
+                self.update_formants();
+            }
+            Message::SetEnsemble {depth, voices} => {
+                let depth = depth.clamp(0.0, 1.0);
+                let voices = voices.min(ensemble::MAX_VOICES);
+                self.ensemble_depth = depth;
+                self.ensemble.set_depth(depth);
+                if voices != self.ensemble_voices {
+                    self.ensemble_voices = voices;
+                    self.ensemble.set_voices(voices);
+                }
+            }
+            Message::SetOutputGain {gain_db} => {
+                self.output_gain_db = gain_db;
+                self.output_gain = 10.0_f32.powf(gain_db/20.0);
+            }
+            Message::SetAir {gain_db} => {
+                // While the gain was 0 the filters were bypassed, so their state is stale.
+
+                if self.air_gain_db == 0.0 {
+                    self.left_air.reset();
+                    self.right_air.reset();
+                }
+                self.air_gain_db = gain_db;
+                let filter = BiquadFilter::high_shelf(AIR_FREQUENCY, 0.707, gain_db);
+                self.left_air.copy_coefficients(&filter);
+                self.right_air.copy_coefficients(&filter);
+            }
+            Message::SetOversampling {factor} => {
+                self.oversampling = factor.clamp(1, MAX_OVERSAMPLING);
+                self.update_sound();
+            }
+            Message::SetMono {enabled} => {
+                self.mono = enabled;
+            }
+            Message::SetMinVowelStartTime {samples} => {
+                self.min_vowel_start = samples;
+            }
+            Message::SetEntranceJitter {amount} => {
+                self.entrance_jitter = amount;
+            }
+            Message::SetMaxVoiceDelay {max_delay} => {
+                self.max_voice_delay = max_delay;
+                self.update_voice_delays();
+            }
+            Message::SetDelays {vowel_delay, vowel_transition_time, consonant_delay, consonant_transition_time} => {
+                // This message is only used for develoment.
+                self.vowel_delay = vowel_delay;
+                self.vowel_transition_time = vowel_transition_time;
+                self.consonant_delay = consonant_delay;
+                self.consonant_transition_time = consonant_transition_time;
+            }
+            Message::SetConsonants {on_time, off_time, volume, frequency, bandwidth} => {
+                // This message is only used for develoment.
+                self.consonant_on_time = on_time;
+                self.consonant_off_time = off_time;
+                self.consonant_volume2 = volume;
+                self.consonant_frequency = frequency;
+                self.consonant_bandwidth = bandwidth;
+            }
+            Message::SetRandomize {randomize} => {
+                self.randomize = randomize;
+            }
         }
     }
 
+    /// Set whether to hum.  When humming, the mouth is closed and all sound comes out through
+    /// the nose, as in [m], regardless of the syllable being sung.  Consonants are silent.
+    pub fn set_hum(&mut self, enabled: bool) {
+        if enabled == self.hum {
+            return;
+        }
+        self.hum = enabled;
+        if let Some(note) = &self.current_note {
+            // Smoothly open or close the mouth for the note that is currently playing.
+
+            let vowel = note.syllable.sustained_vowel();
+            let note_index = note.note_index;
+            let nasal_coupling = self.phonemes.get_nasal_coupling(vowel);
+            self.shape_buffer.clone_from(self.phonemes.get_vowel_shape(vowel).unwrap());
+            self.add_shape_transition(0, 2000, nasal_coupling, note_index, true);
+            self.update_transitions();
+        }
+    }
+
+    /// Replace the vocal tract shape and nasal coupling used for a vowel.  The vowel must be one
+    /// of the symbols returned by Phonemes::vowels(), and the length of the shape must equal
+    /// Phonemes::get_vocal_length() for the current voice part.  Otherwise an Err is returned
+    /// and nothing is changed.  Custom shapes are discarded when the voices are reinitialized.
+    pub fn set_custom_vowel(&mut self, vowel: char, shape: Vec<f32>, nasal_coupling: f32) -> Result<(), String> {
+        self.phonemes.set_vowel_shape(vowel, shape)?;
+        self.phonemes.set_nasal_coupling(vowel, nasal_coupling);
+        Ok(())
+    }
+
+    /// Play the sound of a breath being drawn in, as before an entrance.  intensity (between
+    /// 0.0 and 1.0) controls how loud it is.  This produces noise at the glottis without any
+    /// glottal tone, so it is meant to be used while no note is playing.
+    pub fn breath(&mut self, intensity: f32) {
+        // If the voices have not sung yet, open the mouth so the breath can be heard.
+
+        if self.shape_after_transitions.iter().all(|shape| shape.iter().all(|&x| x == 0.0)) {
+            self.shape_buffer.clone_from(self.phonemes.get_vowel_shape('A').unwrap());
+            self.add_shape_transition(0, 0, 0.0, -1, false);
+        }
+
+        // An inhale gradually grows louder, then stops quickly once the lungs are full.
+
+        let level = 0.3*intensity;
+        self.add_transition(0, 12000, TransitionData::AspirationChange {start_aspiration: 0.0, end_aspiration: level});
+        self.add_transition(12000, 1500, TransitionData::AspirationChange {start_aspiration: level, end_aspiration: 0.0});
+        self.update_transitions();
+    }
+
+    /// Set the volume (between 0.0 and 1.0).  This also affects the timbre of the voices.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        self.update_volume();
+        self.update_sound();
+    }
+
+    /// Set the expression, which scales the volume without affecting the timbre.
+    pub fn set_expression(&mut self, value: f32) {
+        self.expression = value;
+        self.update_volume();
+    }
+
+    /// Set the pitch bend in semitones.
+    pub fn set_pitch_bend(&mut self, semitones: f32) {
+        self.bend = f32::powf(2.0, semitones as f32/12.0);
+        self.update_frequency();
+    }
+
+    /// Set the amount of vibrato (between 0.0 and 1.0).
+    pub fn set_vibrato(&mut self, vibrato: f32) {
+        self.vibrato = vibrato;
+        self.update_vibrato();
+    }
+
+    /// Set the intensity (between 0.0 and 1.0).
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+        self.update_sound();
+        if self.vibrato_intensity_coupling != 0.0 {
+            self.update_vibrato();
+        }
+    }
+
+    /// Set the brightness (between 0.0 and 1.0).  In ShapeBlend mode it takes effect with the
+    /// next note, and in SpectralTilt mode it takes effect immediately.
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness;
+        let filter = BiquadFilter::high_shelf(TILT_FREQUENCY, 0.5, MAX_TILT_DB*(1.0-brightness));
+        self.left_tilt.copy_coefficients(&filter);
+        self.right_tilt.copy_coefficients(&filter);
+    }
+
     /// This is called occasionally by generate().  It processes any Transitions in the queue,
     /// updating the voices as appropriate.
     fn update_transitions(&mut self) {
         let mut volume_changed = false;
         let mut frequency_changed = false;
-        for transition in &self.transitions {
-            for i in 0..self.voices.len() {
-                let j = self.step-self.voice_delays[i];
-                if j >= transition.start {
-                    let fraction = (j-transition.start) as f32 / (transition.end-transition.start) as f32;
-                    let weight2 = if j < transition.end {0.5-0.5*(fraction*std::f32::consts::PI).cos()} else {1.0};
-                    let weight1 = 1.0-weight2;
-                    match &transition.data {
-                        TransitionData::EnvelopeChange {start_envelope, end_envelope} => {
-                            self.envelope[i] = weight1*start_envelope + weight2*end_envelope;
-                            volume_changed = true;
-                        }
-                        TransitionData::ShapeChange {start_shape, end_shape, start_nasal_coupling, end_nasal_coupling} => {
-                            let coupling = weight1*start_nasal_coupling + weight2*end_nasal_coupling;
-                            let n = start_shape[i].len();
-                            let mut shape = vec![0.0; n];
-                            for j in 0..n {
-                                shape[j] = weight1*start_shape[i][j] + weight2*end_shape[i][j];
-                            }
-                            self.voices[i].set_vocal_shape(&shape, coupling);
-                        }
-                        TransitionData::FrequencyChange {start_frequency, end_frequency} => {
-                            self.frequency[i] = weight1*start_frequency + weight2*end_frequency;
-                            frequency_changed = true;
-                        }
+        let max_delay = *self.voice_delays.iter().max().unwrap_or(&0);
+        let mut apply = |transition: &Transition, i: usize, weight2: f32| {
+            let weight1 = 1.0-weight2;
+            match &transition.data {
+                TransitionData::EnvelopeChange {start_envelope, end_envelope} => {
+                    self.envelope[i] = weight1*start_envelope + weight2*end_envelope;
+                    volume_changed = true;
+                }
+                TransitionData::ShapeChange {start_shape, end_shape, start_nasal_coupling, end_nasal_coupling} => {
+                    let coupling = weight1*start_nasal_coupling + weight2*end_nasal_coupling;
+                    for j in 0..self.shape_buffer.len() {
+                        self.shape_buffer[j] = weight1*start_shape[i][j] + weight2*end_shape[i][j];
                     }
+                    self.voices[i].set_vocal_shape(&self.shape_buffer, coupling);
+                }
+                TransitionData::FrequencyChange {start_frequency, end_frequency} => {
+                    self.frequency[i] = weight1*start_frequency + weight2*end_frequency;
+                    frequency_changed = true;
+                }
+                TransitionData::AspirationChange {start_aspiration, end_aspiration} => {
+                    self.aspiration_level[i] = weight1*start_aspiration + weight2*end_aspiration;
+                    self.voices[i].set_aspiration(self.aspiration_level[i]);
+                }
+            }
+        };
+        for i in 0..self.settled_transitions.len() {
+            // Transitions must be applied in the order they were added, since later ones override
+            // earlier ones.  Once every transition up to some point has finished for this voice,
+            // their end values are applied one last time and they are skipped from then on.
+
+            let j = self.step-self.voice_delays[i];
+            let settled = &mut self.settled_transitions[i];
+            while *settled < self.transitions.len() && j >= self.transitions[*settled].end {
+                apply(&self.transitions[*settled], i, 1.0);
+                *settled += 1;
+            }
+
+            // The remaining ones are not strictly ordered by start time, so any that have not yet
+            // started for this voice are skipped individually.
+
+            for transition in &self.transitions[*settled..] {
+                if j >= transition.start {
+                    apply(transition, i, transition.weight(j));
                 }
             }
         }
@@ -881,13 +2099,39 @@ impl Director {
         if frequency_changed {
             self.update_frequency();
         }
-        self.transitions.retain(|t| self.step < t.end+self.max_voice_delay);
+        // Remove transitions that have finished for every voice.  Each voice's count of settled
+        // transitions is reduced by the number removed from before it.  A transition being removed
+        // comes before a voice's settled ones exactly when fewer transitions than that have been
+        // kept so far.
+
+        let step = self.step;
+        let shape_pool = &mut self.shape_pool;
+        let settled_transitions = &mut self.settled_transitions;
+        let mut kept = 0;
+        self.transitions.retain_mut(|t| {
+            if step < t.end+max_delay {
+                kept += 1;
+                return true;
+            }
+            for settled in settled_transitions.iter_mut() {
+                if kept < *settled {
+                    *settled -= 1;
+                }
+            }
+            if let TransitionData::ShapeChange {start_shape, end_shape, ..} = &mut t.data {
+                shape_pool.push(std::mem::take(start_shape));
+                shape_pool.push(std::mem::take(end_shape));
+            }
+            false
+        });
     }
 
     /// Update the volumes of all Voices.  This is called whenever the Director's volume or
     /// envelope is changed.
     fn update_volume(&mut self) {
-        let actual_volume = 0.05+0.95*self.volume;
+        // Expression scales the loudness without changing the sound of the voices.
+
+        let actual_volume = (0.05+0.95*self.volume)*self.expression;
         for i in 0..self.voices.len() {
             self.voices[i].set_volume(actual_volume*self.envelope[i]);
         }
@@ -903,7 +2147,11 @@ impl Director {
 
     /// Update the vibrato of all Voices.  This is called whenever the Director's vibrato is changed.
     fn update_vibrato(&mut self) {
-        let amplitude = 0.04*(self.vibrato+0.1);
+        // Singers widen their vibrato as they sing more intensely.  The coupling scales the depth
+        // up or down relative to what it is at the default intensity of 0.5.
+
+        let intensity_scale = (1.0 + self.vibrato_intensity_coupling*(self.intensity-0.5)).max(0.0);
+        let amplitude = 0.04*(self.vibrato+0.1)*intensity_scale;
         let n = self.voices.len();
         for (i, voice) in &mut self.voices.iter_mut().enumerate() {
             if n < 4 {
@@ -919,20 +2167,35 @@ impl Director {
     /// being played.
     fn update_sound(&mut self) {
         let noise = 0.05*(1.0-self.volume)*(1.0-self.volume);
-        let tremolo = 0.2*self.intensity;
+        let tremolo = 0.2*self.intensity*self.tremolo_amount;
         for voice in &mut self.voices {
             voice.set_noise(noise);
             voice.set_tremolo_amplitude(tremolo);
+            voice.set_tremolo_frequency(self.tremolo_rate);
+            voice.set_oversampling(self.oversampling);
+            voice.set_whisper(self.whisper);
+            voice.set_subglottal_coupling(self.subglottal_coupling);
+            voice.set_damping(self.vocal_damping, self.nasal_damping);
+            voice.set_humanize(self.humanize);
         }
         if let Some(note) = &self.current_note {
             let x = (self.highest_note-note.note_index) as f32 / (self.highest_note-self.lowest_note) as f32;
-            let rd = 1.5 + 0.5*x - 0.2*self.volume - (self.intensity-0.5);
+            let rd = self.rd_base + self.rd_pitch_scale*x - self.rd_volume_scale*self.volume - self.rd_intensity_scale*(self.intensity-0.5);
+            let rd = rd.clamp(MIN_RD, MAX_RD);
             for (i, voice) in &mut self.voices.iter_mut().enumerate() {
                 voice.set_rd(rd + 0.1*(i%4) as f32);
             }
         }
     }
 
+    /// Update the singer's formant of each voice.  Each one is randomly shifted by up to
+    /// formant_spread, so the formants of different singers don't line up exactly.
+    fn update_formants(&mut self) {
+        for (voice, offset) in self.voices.iter_mut().zip(&self.formant_offsets) {
+            voice.set_formant_shift(1.0+self.formant_spread*offset);
+        }
+    }
+
     /// Update the position each voice is panned to.
     fn update_pan_positions(&mut self) {
         let voice_count = self.voices.len();
@@ -950,18 +2213,58 @@ impl Director {
     fn update_voice_delays(&mut self) {
         let voice_count = self.voices.len();
         if voice_count == 1 {
-            self.voice_delays[0] = 0;
+            self.voice_delays[0] = self.entrance_offsets[0];
         }
         else {
             for i in 0..voice_count {
                 let index = ((i+(voice_count/2)) % voice_count) as i64;
-                self.voice_delays[i] = self.max_voice_delay*index/(voice_count-1) as i64;
+                self.voice_delays[i] = self.max_voice_delay*index/(voice_count-1) as i64 + self.entrance_offsets[i];
             }
         }
     }
 
-    /// Get the timing parameters (delay, transition time) for a transient vowel.
-    fn get_vowel_timing(&self, vowel: char, is_final: bool) -> (i64, i64) {
+    /// Select a random extra delay for each voice, so they enter slightly raggedly.  This may only
+    /// be called while the voices are silent, since changing the delays in the middle of a note
+    /// would cause discontinuities.
+    fn randomize_entrances(&mut self) {
+        for i in 0..self.entrance_offsets.len() {
+            self.entrance_offsets[i] = (self.entrance_jitter*MAX_ENTRANCE_JITTER as f32*self.random.get_uniform()) as i64;
+        }
+        self.update_voice_delays();
+    }
+
+    /// Get the frequency of a note.  If a tuning table has been set, it gives the pitch of each
+    /// note in cents relative to A4 (440 Hz).  Otherwise equal temperament is used.
+    fn get_note_frequency(&self, note_index: i32) -> f32 {
+        match self.tuning_table.get(note_index as usize) {
+            Some(cents) => 440.0 * f32::powf(2.0, cents/1200.0),
+            None => 440.0 * f32::powf(2.0, (note_index-69) as f32/12.0)
+        }
+    }
+
+    /// Get the factor by which to scale the duration of each consonant in a cluster.  Consonants
+    /// in longer clusters are shortened so the cluster doesn't take too long, but only down to a
+    /// limit, so each one remains intelligible.
+    fn cluster_time_scale(count: usize) -> f32 {
+        match count {
+            0 | 1 => 1.0,
+            2 => 0.8,
+            _ => f32::max(0.65, 0.8-0.05*(count-2) as f32)
+        }
+    }
+
+    /// Get the timing parameters (delay, transition time) for a transient vowel.  A vowel that
+    /// has been marked as long takes twice as long.
+    fn get_vowel_timing(&self, vowel: char, is_final: bool, long: bool) -> (i64, i64) {
+        let (delay, transition_time) = self.get_base_vowel_timing(vowel, is_final);
+        if long {
+            return (2*delay, 2*transition_time);
+        }
+        (delay, transition_time)
+    }
+
+    /// Get the timing parameters (delay, transition time) for a transient vowel of normal length.
+    fn get_base_vowel_timing(&self, vowel: char, is_final: bool) -> (i64, i64) {
         if vowel == 'm' {
             if is_final {
                 return (500, 3200);
@@ -991,4 +2294,117 @@ impl Director {
         }
         (self.vowel_delay, self.vowel_transition_time)
     }
-}
\ No newline at end of file
+}
+
+/// A DirectorBuilder provides a convenient way of creating a Director whose settings differ
+/// from the defaults.  Call the methods for whatever settings you want to change, then call
+/// build().
+pub struct DirectorBuilder {
+    voice_part: VoicePart,
+    voice_count: usize,
+    vibrato: Option<f32>,
+    intensity: Option<f32>,
+    brightness: Option<f32>,
+    stereo_width: Option<f32>,
+    max_voice_delay: Option<i64>,
+    message_interval: Option<i64>,
+    seed: Option<u32>
+}
+
+impl DirectorBuilder {
+    /// Create a DirectorBuilder.  Any setting that is not specified keeps the same default value
+    /// as a Director created with Director::new().
+    pub fn new(voice_part: VoicePart, voice_count: usize) -> Self {
+        Self {
+            voice_part: voice_part,
+            voice_count: voice_count,
+            vibrato: None,
+            intensity: None,
+            brightness: None,
+            stereo_width: None,
+            max_voice_delay: None,
+            message_interval: None,
+            seed: None
+        }
+    }
+
+    /// Set the amount of vibrato.  This is equivalent to sending Message::SetVibrato.
+    pub fn vibrato(mut self, vibrato: f32) -> Self {
+        self.vibrato = Some(vibrato);
+        self
+    }
+
+    /// Set the intensity.  This is equivalent to sending Message::SetIntensity.
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = Some(intensity);
+        self
+    }
+
+    /// Set the brightness.  This is equivalent to sending Message::SetBrightness.
+    pub fn brightness(mut self, brightness: f32) -> Self {
+        self.brightness = Some(brightness);
+        self
+    }
+
+    /// Set the stereo width.  This is equivalent to sending Message::SetStereoWidth.
+    pub fn stereo_width(mut self, width: f32) -> Self {
+        self.stereo_width = Some(width);
+        self
+    }
+
+    /// Set the maximum delay between voices.  This is equivalent to sending Message::SetMaxVoiceDelay.
+    pub fn max_voice_delay(mut self, max_delay: i64) -> Self {
+        self.max_voice_delay = Some(max_delay);
+        self
+    }
+
+    /// Set how often (in samples) to check for new Messages.  Smaller values reduce latency at
+    /// the cost of slightly more overhead.
+    pub fn message_interval(mut self, steps: i64) -> Self {
+        self.message_interval = Some(steps.max(1));
+        self
+    }
+
+    /// Set the seed for the random number generator.  Directors built with the same seed and
+    /// sent the same messages produce identical output.  If this is not called, a random seed
+    /// is chosen.
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Create the Director.
+    pub fn build(self, message_receiver: mpsc::Receiver<Message>) -> Director {
+        // Apply the settings before creating the voices, since some of them affect how the voices
+        // are initialized.
+
+        let mut director = Director::with_defaults(self.voice_part, message_receiver, self.seed);
+        if let Some(vibrato) = self.vibrato {
+            director.vibrato = vibrato;
+        }
+        if let Some(intensity) = self.intensity {
+            director.intensity = intensity;
+        }
+        if let Some(brightness) = self.brightness {
+            director.set_brightness(brightness);
+        }
+        if let Some(width) = self.stereo_width {
+            director.stereo_width = width;
+        }
+        if let Some(max_delay) = self.max_voice_delay {
+            director.max_voice_delay = max_delay;
+        }
+        if let Some(steps) = self.message_interval {
+            director.message_interval = steps;
+        }
+        director.initialize_voices(self.voice_part, self.voice_count);
+        director
+    }
+
+    /// Create a Director that is controlled only by calling its methods directly, rather than
+    /// by sending Messages through a channel.
+    pub fn build_standalone(self) -> Director {
+        let (_sender, receiver) = mpsc::channel();
+        self.build(receiver)
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use crate::trig::sin_cycles;
+use crate::SAMPLE_RATE;
+
+/// The length of the delay line in seconds.  It must be longer than the longest delay of any tap.
+const BUFFER_LENGTH: f32 = 0.05;
+
+/// The maximum number of delayed copies of the input.
+pub const MAX_VOICES: usize = 8;
+
+/// An Ensemble is a stereo chorus effect.  It mixes the input with several copies of itself,
+/// each delayed by a slowly varying amount.  This makes a small number of voices sound like a
+/// larger group.  Unlike the Voices, it adds almost no computational cost.
+///
+/// Copies are alternately sent to the left and right channels, so the effect also widens the
+/// stereo image.  depth controls both the amount the delays vary and the volume of the copies.
+/// A depth of 0 disables the effect.
+pub struct Ensemble {
+    buffer: Vec<f32>,
+    position: usize,
+    phases: Vec<f32>,
+    depth: f32
+}
+
+impl Ensemble {
+    pub fn new(depth: f32, voices: usize) -> Self {
+        let mut result = Self {
+            buffer: vec![0.0; (BUFFER_LENGTH*SAMPLE_RATE as f32) as usize],
+            position: 0,
+            phases: vec![],
+            depth: depth.clamp(0.0, 1.0)
+        };
+        result.set_voices(voices);
+        result
+    }
+
+    /// Set the depth of the effect.  It is clamped to the range [0.0, 1.0], since a larger
+    /// depth would move the delays beyond the end of the buffer.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// Set how many delayed copies of the input to add, up to MAX_VOICES.
+    pub fn set_voices(&mut self, voices: usize) {
+        let voices = voices.min(MAX_VOICES);
+
+        // Spread out the initial phases so the copies don't all move together.
+
+        self.phases = (0..voices).map(|i| i as f32/voices as f32).collect();
+    }
+
+    /// Process the next sample and return the output (left, right).
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let n = self.buffer.len();
+        self.buffer[self.position] = 0.5*(left+right);
+        if self.depth == 0.0 || self.phases.len() == 0 {
+            self.position = (self.position+1)%n;
+            return (left, right);
+        }
+        let mut wet = [0.0, 0.0];
+        let modulation = 0.003*self.depth*SAMPLE_RATE as f32;
+        let voices = self.phases.len();
+        for (i, phase) in self.phases.iter_mut().enumerate() {
+            // Each copy has a different base delay (10 to 25 ms) and modulation rate.
+
+            let rate = 0.2+0.13*i as f32;
+            *phase = (*phase+rate/SAMPLE_RATE as f32)%1.0;
+            let base = (0.010+0.015*(i as f32/voices as f32))*SAMPLE_RATE as f32;
+            let delay = base + modulation*sin_cycles(*phase);
+            let offset = delay.floor();
+            let fraction = delay-offset;
+            let index1 = (self.position+n-offset as usize)%n;
+            let index2 = (index1+n-1)%n;
+            wet[i%2] += (1.0-fraction)*self.buffer[index1] + fraction*self.buffer[index2];
+        }
+        self.position = (self.position+1)%n;
+        let scale = 0.7*self.depth/(voices as f32).sqrt();
+        (left+scale*wet[0], right+scale*wet[1])
+    }
+}
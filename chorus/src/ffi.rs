@@ -0,0 +1,193 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+// A C interface to the Director, for embedding the engine in hosts written in other languages.
+// It is only compiled when the `ffi` feature is enabled.  To build a shared library, run
+//
+//     cargo rustc -p chorus --release --features ffi --crate-type cdylib
+//
+// A Director created through this interface is controlled directly from the thread that calls
+// director_generate(), so all functions taking a Director must be called from that thread or
+// otherwise synchronized by the caller.
+
+use crate::director::{BrightnessMode, Director, DirectorBuilder, Message};
+use crate::VoicePart;
+use std::ffi::{c_char, CStr};
+
+// The kinds of message that can be sent with director_send_message().  Each one takes a single
+// value.
+
+pub const MESSAGE_SET_VOLUME: u32 = 0;
+pub const MESSAGE_SET_EXPRESSION: u32 = 1;
+pub const MESSAGE_SET_PITCH_BEND: u32 = 2;
+pub const MESSAGE_SET_VIBRATO: u32 = 3;
+pub const MESSAGE_SET_INTENSITY: u32 = 4;
+pub const MESSAGE_SET_BRIGHTNESS: u32 = 5;
+pub const MESSAGE_SET_CONSONANT_VOLUME: u32 = 6;
+pub const MESSAGE_SET_ATTACK_RATE: u32 = 7;
+pub const MESSAGE_SET_RELEASE_RATE: u32 = 8;
+pub const MESSAGE_SET_ACCENT: u32 = 9;
+pub const MESSAGE_SET_STEREO_WIDTH: u32 = 10;
+pub const MESSAGE_SET_EXCITER_STRENGTH: u32 = 11;
+pub const MESSAGE_SET_MAX_VOICE_DELAY: u32 = 12;
+pub const MESSAGE_SET_MIN_VOWEL_START_TIME: u32 = 13;
+pub const MESSAGE_SET_HUM: u32 = 14;
+pub const MESSAGE_SET_WHISPER: u32 = 15;
+pub const MESSAGE_SET_HUMANIZE: u32 = 16;
+pub const MESSAGE_BREATH: u32 = 17;
+pub const MESSAGE_SET_OUTPUT_GAIN: u32 = 18;
+pub const MESSAGE_SET_MONO: u32 = 19;
+pub const MESSAGE_SET_VIBRATO_INTENSITY_COUPLING: u32 = 20;
+pub const MESSAGE_SET_RELEASE_SHAPE: u32 = 21;
+pub const MESSAGE_SET_ATTACK_SHAPE: u32 = 22;
+pub const MESSAGE_SET_AIR: u32 = 23;
+pub const MESSAGE_SET_BRIGHTNESS_MODE: u32 = 24;
+pub const MESSAGE_SET_OVERSAMPLING: u32 = 25;
+pub const MESSAGE_SET_NASALITY: u32 = 26;
+
+/// Create a new Director.  voice_part is 0 for soprano, 1 for alto, 2 for tenor, or 3 for bass.
+/// Returns a null pointer if voice_part is invalid.  The Director must eventually be released
+/// with director_free().
+#[no_mangle]
+pub extern "C" fn director_new(voice_part: u32, voice_count: usize) -> *mut Director {
+    let voice_part = match VoicePart::all().get(voice_part as usize) {
+        Some(part) => *part,
+        None => return std::ptr::null_mut()
+    };
+    Box::into_raw(Box::new(DirectorBuilder::new(voice_part, voice_count.max(1)).build_standalone()))
+}
+
+/// Release a Director created by director_new().
+///
+/// # Safety
+///
+/// director must be null or a pointer returned by director_new() that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn director_free(director: *mut Director) {
+    if !director.is_null() {
+        drop(Box::from_raw(director));
+    }
+}
+
+/// Change the voice part and number of voices.  Returns 0 on success, or -1 if voice_part is
+/// invalid.
+///
+/// # Safety
+///
+/// director must be a valid pointer returned by director_new().
+#[no_mangle]
+pub unsafe extern "C" fn director_reinitialize(director: *mut Director, voice_part: u32, voice_count: usize) -> i32 {
+    let director = &mut *director;
+    match VoicePart::all().get(voice_part as usize) {
+        Some(part) => {
+            director.handle_message(Message::Reinitialize {voice_part: *part, voice_count: voice_count.max(1)});
+            0
+        }
+        None => -1
+    }
+}
+
+/// Start singing a note.  syllable is a null terminated UTF-8 string in X-SAMPA.  Returns 0 on
+/// success, or -1 if the syllable cannot be parsed.
+///
+/// # Safety
+///
+/// director must be a valid pointer returned by director_new(), and syllable must point to a
+/// null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn director_note_on(director: *mut Director, syllable: *const c_char, note_index: i32, velocity: f32, continue_syllable: bool) -> i32 {
+    let director = &mut *director;
+    let syllable = match CStr::from_ptr(syllable).to_str() {
+        Ok(s) => s,
+        Err(_) => return -1
+    };
+    match director.note_on(syllable, note_index, velocity, continue_syllable) {
+        Ok(()) => 0,
+        Err(_) => -1
+    }
+}
+
+/// End the current note.
+///
+/// # Safety
+///
+/// director must be a valid pointer returned by director_new().
+#[no_mangle]
+pub unsafe extern "C" fn director_note_off(director: *mut Director) {
+    (*director).note_off();
+}
+
+/// Send a message to the Director.  kind is one of the MESSAGE_* constants.  For messages that
+/// take a boolean, any nonzero value is treated as true.  For MESSAGE_SET_BRIGHTNESS_MODE, 0
+/// selects BrightnessMode::ShapeBlend and any other value selects BrightnessMode::SpectralTilt.
+/// For ones that take a time, the value is in samples.  Returns 0 on success, or -1 if kind is not
+/// recognized.
+///
+/// # Safety
+///
+/// director must be a valid pointer returned by director_new().
+#[no_mangle]
+pub unsafe extern "C" fn director_send_message(director: *mut Director, kind: u32, value: f32) -> i32 {
+    let message = match kind {
+        MESSAGE_SET_VOLUME => Message::SetVolume {volume: value},
+        MESSAGE_SET_EXPRESSION => Message::SetExpression {value},
+        MESSAGE_SET_PITCH_BEND => Message::SetPitchBend {semitones: value},
+        MESSAGE_SET_VIBRATO => Message::SetVibrato {vibrato: value},
+        MESSAGE_SET_INTENSITY => Message::SetIntensity {intensity: value},
+        MESSAGE_SET_BRIGHTNESS => Message::SetBrightness {brightness: value},
+        MESSAGE_SET_CONSONANT_VOLUME => Message::SetConsonantVolume {volume: value},
+        MESSAGE_SET_ATTACK_RATE => Message::SetAttackRate {attack: value},
+        MESSAGE_SET_RELEASE_RATE => Message::SetReleaseRate {release: value},
+        MESSAGE_SET_ACCENT => Message::SetAccent {accent: value != 0.0},
+        MESSAGE_SET_STEREO_WIDTH => Message::SetStereoWidth {width: value},
+        MESSAGE_SET_EXCITER_STRENGTH => Message::SetExciterStrength {strength: value},
+        MESSAGE_SET_MAX_VOICE_DELAY => Message::SetMaxVoiceDelay {max_delay: value as i64},
+        MESSAGE_SET_MIN_VOWEL_START_TIME => Message::SetMinVowelStartTime {samples: value as i64},
+        MESSAGE_SET_HUM => Message::SetHum {enabled: value != 0.0},
+        MESSAGE_SET_WHISPER => Message::SetWhisper {amount: value},
+        MESSAGE_SET_HUMANIZE => Message::SetHumanize {amount: value},
+        MESSAGE_BREATH => Message::Breath {intensity: value},
+        MESSAGE_SET_OUTPUT_GAIN => Message::SetOutputGain {gain_db: value},
+        MESSAGE_SET_MONO => Message::SetMono {enabled: value != 0.0},
+        MESSAGE_SET_VIBRATO_INTENSITY_COUPLING => Message::SetVibratoIntensityCoupling {amount: value},
+        MESSAGE_SET_RELEASE_SHAPE => Message::SetReleaseShape {curve: value},
+        MESSAGE_SET_ATTACK_SHAPE => Message::SetAttackShape {curve: value},
+        MESSAGE_SET_AIR => Message::SetAir {gain_db: value},
+        MESSAGE_SET_BRIGHTNESS_MODE => Message::SetBrightnessMode {mode: if value != 0.0 {BrightnessMode::SpectralTilt} else {BrightnessMode::ShapeBlend}},
+        MESSAGE_SET_OVERSAMPLING => Message::SetOversampling {factor: value as usize},
+        MESSAGE_SET_NASALITY => Message::SetNasality {coupling: value},
+        _ => return -1
+    };
+    (*director).handle_message(message);
+    0
+}
+
+/// Generate audio.  buffer receives frames stereo samples, interleaved as (left, right), so it
+/// must have room for 2*frames values.  Output is always at SAMPLE_RATE.
+///
+/// # Safety
+///
+/// director must be a valid pointer returned by director_new(), and buffer must point to at
+/// least 2*frames writable values.
+#[no_mangle]
+pub unsafe extern "C" fn director_generate(director: *mut Director, buffer: *mut f32, frames: usize) {
+    (*director).generate_interleaved(std::slice::from_raw_parts_mut(buffer, 2*frames));
+}
+
+/// Get the sample rate (in Hz) of the audio produced by director_generate().
+#[no_mangle]
+pub extern "C" fn director_sample_rate() -> i32 {
+    crate::SAMPLE_RATE
+}
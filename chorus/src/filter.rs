@@ -17,7 +17,27 @@ use crate::SAMPLE_RATE;
 use std::f32::consts::PI;
 
 pub trait Filter {
+    /// Process the next input sample and return the filtered output.
     fn process(&mut self, x: f32) -> f32;
+
+    /// Clear all internal state, returning the filter to the condition it was in when first created.
+    fn reset(&mut self);
+
+    /// Compute the gain the filter applies to a sine wave of a given frequency (in Hz) when it
+    /// runs at the specified sample rate.  This is computed analytically from the coefficients.
+    fn magnitude_response(&self, frequency: f32, sample_rate: f32) -> f32;
+}
+
+/// Evaluate the magnitude of a polynomial in z^-1 at z = e^(iw).  The transfer function of an IIR
+/// filter is the ratio of two such polynomials.
+fn polynomial_magnitude(coefficients: &[f32], w: f32) -> f32 {
+    let mut re = 0.0;
+    let mut im = 0.0;
+    for (k, c) in coefficients.iter().enumerate() {
+        re += c*(k as f32*w).cos();
+        im -= c*(k as f32*w).sin();
+    }
+    (re*re + im*im).sqrt()
 }
 
 /// An IIR lowpass filter.
@@ -44,6 +64,15 @@ impl Filter for LowpassFilter {
         self.y += self.alpha*(x-self.y);
         self.y
     }
+
+    fn reset(&mut self) {
+        self.y = 0.0;
+    }
+
+    fn magnitude_response(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let w = 2.0*PI*frequency/sample_rate;
+        self.alpha/polynomial_magnitude(&[1.0, self.alpha-1.0], w)
+    }
 }
 
 /// An IIR highpass filter.
@@ -73,6 +102,16 @@ impl Filter for HighpassFilter {
         self.x = x;
         self.y
     }
+
+    fn reset(&mut self) {
+        self.x = 0.0;
+        self.y = 0.0;
+    }
+
+    fn magnitude_response(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let w = 2.0*PI*frequency/sample_rate;
+        self.alpha*polynomial_magnitude(&[1.0, -1.0], w)/polynomial_magnitude(&[1.0, -self.alpha], w)
+    }
 }
 
 /// An IIR bandpass filter.
@@ -95,6 +134,15 @@ impl Filter for BandpassFilter {
     fn process(&mut self, x: f32) -> f32 {
         self.highpass.process(self.lowpass.process(x))
     }
+
+    fn reset(&mut self) {
+        self.lowpass.reset();
+        self.highpass.reset();
+    }
+
+    fn magnitude_response(&self, frequency: f32, sample_rate: f32) -> f32 {
+        self.lowpass.magnitude_response(frequency, sample_rate)*self.highpass.magnitude_response(frequency, sample_rate)
+    }
 }
 
 /// An IIR resonant filter.
@@ -126,4 +174,143 @@ impl Filter for ResonantFilter {
         self.y1 = y;
         y
     }
+
+    fn reset(&mut self) {
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    fn magnitude_response(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let w = 2.0*PI*frequency/sample_rate;
+        1.0/polynomial_magnitude(&[1.0, self.b1, self.b2], w)
+    }
+}
+
+/// A second order IIR (biquad) filter.  The coefficients are computed with the formulas from
+/// Robert Bristow-Johnson's "Audio EQ Cookbook".  Create one by calling the constructor for the
+/// type of filter you want: lowpass(), highpass(), peaking(), low_shelf(), or high_shelf().
+#[derive(Copy, Clone)]
+pub struct BiquadFilter {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32
+}
+
+impl BiquadFilter {
+    /// Create a filter from the unnormalized coefficients.
+    fn from_coefficients(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0/a0,
+            b1: b1/a0,
+            b2: b2/a0,
+            a1: a1/a0,
+            a2: a2/a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0
+        }
+    }
+
+    /// Compute the normalized frequency and the alpha parameter used by all the filter types.
+    fn compute_w_alpha(frequency: f32, q: f32) -> (f32, f32) {
+        let w = 2.0*PI*frequency/SAMPLE_RATE as f32;
+        (w, w.sin()/(2.0*q))
+    }
+
+    /// Create a lowpass filter.  Q=0.707 gives a maximally flat passband.
+    pub fn lowpass(cutoff: f32, q: f32) -> Self {
+        let (w, alpha) = Self::compute_w_alpha(cutoff, q);
+        let cos = w.cos();
+        Self::from_coefficients(0.5*(1.0-cos), 1.0-cos, 0.5*(1.0-cos), 1.0+alpha, -2.0*cos, 1.0-alpha)
+    }
+
+    /// Create a highpass filter.  Q=0.707 gives a maximally flat passband.
+    pub fn highpass(cutoff: f32, q: f32) -> Self {
+        let (w, alpha) = Self::compute_w_alpha(cutoff, q);
+        let cos = w.cos();
+        Self::from_coefficients(0.5*(1.0+cos), -(1.0+cos), 0.5*(1.0+cos), 1.0+alpha, -2.0*cos, 1.0-alpha)
+    }
+
+    /// Create a peaking filter that boosts or cuts frequencies near a center frequency.  The gain
+    /// is measured in dB.
+    pub fn peaking(center: f32, q: f32, gain: f32) -> Self {
+        let (w, alpha) = Self::compute_w_alpha(center, q);
+        let cos = w.cos();
+        let a = 10.0_f32.powf(gain/40.0);
+        Self::from_coefficients(1.0+alpha*a, -2.0*cos, 1.0-alpha*a, 1.0+alpha/a, -2.0*cos, 1.0-alpha/a)
+    }
+
+    /// Create a shelf filter that boosts or cuts frequencies below a cutoff.  The gain is measured
+    /// in dB.
+    pub fn low_shelf(cutoff: f32, q: f32, gain: f32) -> Self {
+        let (w, alpha) = Self::compute_w_alpha(cutoff, q);
+        let cos = w.cos();
+        let a = 10.0_f32.powf(gain/40.0);
+        let beta = 2.0*a.sqrt()*alpha;
+        Self::from_coefficients(
+            a*((a+1.0) - (a-1.0)*cos + beta),
+            2.0*a*((a-1.0) - (a+1.0)*cos),
+            a*((a+1.0) - (a-1.0)*cos - beta),
+            (a+1.0) + (a-1.0)*cos + beta,
+            -2.0*((a-1.0) + (a+1.0)*cos),
+            (a+1.0) + (a-1.0)*cos - beta
+        )
+    }
+
+    /// Create a shelf filter that boosts or cuts frequencies above a cutoff.  The gain is measured
+    /// in dB.
+    pub fn high_shelf(cutoff: f32, q: f32, gain: f32) -> Self {
+        let (w, alpha) = Self::compute_w_alpha(cutoff, q);
+        let cos = w.cos();
+        let a = 10.0_f32.powf(gain/40.0);
+        let beta = 2.0*a.sqrt()*alpha;
+        Self::from_coefficients(
+            a*((a+1.0) + (a-1.0)*cos + beta),
+            -2.0*a*((a-1.0) + (a+1.0)*cos),
+            a*((a+1.0) + (a-1.0)*cos - beta),
+            (a+1.0) - (a-1.0)*cos + beta,
+            2.0*((a-1.0) - (a+1.0)*cos),
+            (a+1.0) - (a-1.0)*cos - beta
+        )
+    }
+
+    /// Replace the coefficients of this filter with the ones from another filter, while keeping
+    /// the internal state.  This allows the response to be changed without a discontinuity.
+    pub fn copy_coefficients(&mut self, other: &BiquadFilter) {
+        self.b0 = other.b0;
+        self.b1 = other.b1;
+        self.b2 = other.b2;
+        self.a1 = other.a1;
+        self.a2 = other.a2;
+    }
+}
+
+impl Filter for BiquadFilter {
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0*x + self.b1*self.x1 + self.b2*self.x2 - self.a1*self.y1 - self.a2*self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+
+    fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    fn magnitude_response(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let w = 2.0*PI*frequency/sample_rate;
+        polynomial_magnitude(&[self.b0, self.b1, self.b2], w)/polynomial_magnitude(&[1.0, self.a1, self.a2], w)
+    }
 }
\ No newline at end of file
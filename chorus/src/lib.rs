@@ -21,13 +21,60 @@ pub mod random;
 pub mod resampler;
 pub mod filter;
 pub mod exciter;
+pub mod ensemble;
+pub mod tuning;
+pub mod trig;
+pub mod text;
+pub mod performer;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 pub const SAMPLE_RATE: i32 = 48000;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VoicePart {
     Soprano,
     Alto,
     Tenor,
     Bass
-}
\ No newline at end of file
+}
+
+impl VoicePart {
+    /// Get all the voice parts, ordered from highest to lowest.
+    pub fn all() -> [VoicePart; 4] {
+        [VoicePart::Soprano, VoicePart::Alto, VoicePart::Tenor, VoicePart::Bass]
+    }
+
+    /// Get the lowest and highest MIDI note indices this voice part can sing.  Notes outside
+    /// this range are not played.
+    pub fn note_range(self) -> (i32, i32) {
+        match self {
+            VoicePart::Soprano => (57, 88),
+            VoicePart::Alto => (48, 79),
+            VoicePart::Tenor => (43, 72),
+            VoicePart::Bass => (36, 67)
+        }
+    }
+}
+
+impl std::fmt::Display for VoicePart {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            VoicePart::Soprano => "Soprano",
+            VoicePart::Alto => "Alto",
+            VoicePart::Tenor => "Tenor",
+            VoicePart::Bass => "Bass"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for VoicePart {
+    type Err = String;
+
+    /// Parse the name of a voice part.  This is case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VoicePart::all().into_iter().find(|part| part.to_string().eq_ignore_ascii_case(s.trim())).ok_or(format!("Unknown voice part: {s}"))
+    }
+}
@@ -0,0 +1,336 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+// A Performer turns note and controller events, such as those received from a MIDI keyboard,
+// into Messages for a Director.  It keeps track of which keys are held, the sustain pedal, and
+// which syllable of the phrase to sing next, so every program that plays the Director from a
+// keyboard behaves the same way.
+
+use crate::director::Message;
+use std::sync::mpsc;
+
+/// The longest phrase (in bytes) a Performer can hold.  Its buffer is allocated up front with this
+/// capacity, so set_phrase() never allocates.  Longer phrases are truncated.
+pub const MAX_PHRASE_LENGTH: usize = 4096;
+
+/// Which note to play when several keys are held at once.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NotePriority {
+    /// Play the most recently pressed key.
+    Last,
+    /// Play the highest key.
+    Highest,
+    /// Play the lowest key.
+    Lowest
+}
+
+/// How many syllables of the phrase each note sings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SyllableGrouping {
+    /// Each note sings one syllable.
+    Syllable,
+    /// Each note sings one word.  The syllables of a word are separated by periods, as in
+    /// `glo.4i.A`, and are sung one after another on the same note.
+    Word
+}
+
+/// An event that controls a performance.  Velocities and controller values are in the range
+/// [0, 1].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    NoteOn {note: u8, velocity: f32},
+    NoteOff {note: u8, velocity: f32},
+    Sustain {pressed: bool},
+    Volume {value: f32},
+    Expression {value: f32},
+    Pressure {pressure: f32},
+    /// The pitch wheel position.  0.5 is the center, and the full range is two semitones in
+    /// each direction.
+    PitchBend {value: f32}
+}
+
+impl Event {
+    /// Convert a raw MIDI message to an Event.  The modulation wheel (CC 1) controls the
+    /// volume.  Returns None for messages that are not recognized.
+    pub fn from_midi(message: &[u8]) -> Option<Event> {
+        let status = message.first()? & 0xF0;
+        let data1 = *message.get(1).unwrap_or(&0);
+        let data2 = *message.get(2).unwrap_or(&0);
+        let value = data2 as f32 / 127.0;
+        match (status, data1) {
+            (0x90, _) if data2 > 0 => Some(Event::NoteOn {note: data1, velocity: value}),
+            (0x90, _) => Some(Event::NoteOff {note: data1, velocity: 0.5}),
+            (0x80, _) => Some(Event::NoteOff {note: data1, velocity: value}),
+            (0xB0, 1) => Some(Event::Volume {value}),
+            (0xB0, 11) => Some(Event::Expression {value}),
+            (0xB0, 64) => Some(Event::Sustain {pressed: data2 >= 64}),
+            (0xD0, _) => Some(Event::Pressure {pressure: data1 as f32 / 127.0}),
+            (0xE0, _) => Some(Event::PitchBend {value: (data1 as f32 + 128.0*data2 as f32) / 16384.0}),
+            _ => None
+        }
+    }
+}
+
+/// Split a phrase into syllables.  They may be separated by whitespace or periods.
+pub fn phrase_syllables(phrase: &str) -> impl Iterator<Item = &str> {
+    phrase.split(|c: char| c.is_whitespace() || c == '.').filter(|s| !s.is_empty())
+}
+
+/// Find the index of the last syllable in the word that contains a syllable.  Words are
+/// separated by whitespace, and the syllables within a word by periods.
+fn word_end(phrase: &str, index: usize) -> usize {
+    let mut end = 0;
+    for word in phrase.split_whitespace() {
+        end += phrase_syllables(word).count();
+        if end > index {
+            return end-1;
+        }
+    }
+    index
+}
+
+pub struct Performer {
+    sender: mpsc::Sender<Message>,
+    /// Which note to play when several keys are held at once.
+    pub note_priority: NotePriority,
+    /// Whether each new note advances to the next syllable of the phrase.  If false, every note
+    /// continues the current syllable.
+    pub advance_syllable: bool,
+    /// How many syllables each note advances by.
+    pub grouping: SyllableGrouping,
+    /// The number of semitones to add to every note.
+    pub transpose: i32,
+    /// The minimum vowel start time to use for notes started by the following events, or None
+    /// to use the Director's value.
+    pub vowel_start: Option<i64>,
+    phrase: String,
+    syllable_index: i32,
+    held_notes: Vec<(u8, f32)>,
+    last_note: Option<u8>,
+    sustain_pedal: bool,
+    pending_note_off: bool,
+    send_note_off: bool,
+    release_velocity: f32,
+    release_rate: f32,
+    intensity: f32,
+    pressure: f32
+}
+
+impl Performer {
+    /// Create a Performer that sends Messages to a Director through a channel.
+    pub fn new(sender: mpsc::Sender<Message>) -> Self {
+        Self {
+            sender,
+            note_priority: NotePriority::Last,
+            advance_syllable: true,
+            grouping: SyllableGrouping::Syllable,
+            transpose: 0,
+            vowel_start: None,
+            phrase: String::with_capacity(MAX_PHRASE_LENGTH),
+            syllable_index: -1,
+            held_notes: Vec::with_capacity(128),
+            last_note: None,
+            sustain_pedal: false,
+            pending_note_off: false,
+            send_note_off: false,
+            release_velocity: 0.5,
+            release_rate: 0.5,
+            intensity: 0.5,
+            pressure: 0.0
+        }
+    }
+
+    /// Set the phrase to sing.  If it differs from the current one, the next note continues from
+    /// the same position in it.  Call restart_phrase() to start again from the beginning.  Only the
+    /// first MAX_PHRASE_LENGTH bytes of the phrase are used.
+    pub fn set_phrase(&mut self, phrase: &str) {
+        let mut length = phrase.len().min(MAX_PHRASE_LENGTH);
+        while !phrase.is_char_boundary(length) {
+            length -= 1;
+        }
+        let phrase = &phrase[..length];
+        if self.phrase != phrase {
+            self.phrase.clear();
+            self.phrase.push_str(phrase);
+        }
+    }
+
+    /// Make the next note sing the first syllable of the phrase.
+    pub fn restart_phrase(&mut self) {
+        self.syllable_index = -1;
+    }
+
+    /// Get the index within the phrase of the syllable currently being sung, or -1 if no note
+    /// has been played since the phrase was started.
+    pub fn syllable_index(&self) -> i32 {
+        self.syllable_index
+    }
+
+    /// Set the intensity.  Channel pressure raises it above this value.
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+        self.send_intensity();
+    }
+
+    /// Set the release rate.  The release velocity of each note adjusts it for that note.
+    pub fn set_release_rate(&mut self, release: f32) {
+        self.release_rate = release;
+        let _ = self.sender.send(Message::SetReleaseRate {release});
+    }
+
+    /// Forget all held notes and controller state, and restart the phrase.
+    pub fn reset(&mut self) {
+        self.held_notes.clear();
+        self.last_note = None;
+        self.sustain_pedal = false;
+        self.pending_note_off = false;
+        self.send_note_off = false;
+        self.pressure = 0.0;
+        self.syllable_index = -1;
+    }
+
+    /// Process an event.  A NoteOff is not sent until flush() is called, so that if a new note
+    /// starts at the same time, the two notes can be played legato.
+    pub fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::NoteOn {note, velocity} => {
+                self.held_notes.retain(|n| n.0 != note);
+                self.held_notes.push((note, velocity));
+                if self.select_note() == Some((note, velocity)) {
+                    self.start_note(note, velocity, false);
+                }
+            }
+            Event::NoteOff {note, velocity} => {
+                self.held_notes.retain(|n| n.0 != note);
+                if Some(note) != self.last_note {
+                    return;
+                }
+                if let Some((n, v)) = self.select_note() {
+                    // Return to a note that is still held, continuing the same syllable.
+
+                    self.start_note(n, v, true);
+                }
+                else {
+                    // Many hosts and keyboards send a release velocity of 0 when they don't
+                    // measure it.  Treat that the same as 0.5, which leaves the release unchanged.
+
+                    self.release_velocity = if velocity > 0.0 {velocity} else {0.5};
+
+                    // While the sustain pedal is held, defer the NoteOff until it is released.
+                    // If another note starts first, they are played legato.
+
+                    if self.sustain_pedal {
+                        self.pending_note_off = true;
+                    }
+                    else {
+                        self.send_note_off = true;
+                    }
+                }
+            }
+            Event::Sustain {pressed} => {
+                self.sustain_pedal = pressed;
+                if !pressed && self.pending_note_off {
+                    self.pending_note_off = false;
+                    self.send_note_off = true;
+                }
+            }
+            Event::Volume {value} => {
+                let _ = self.sender.send(Message::SetVolume {volume: value});
+            }
+            Event::Expression {value} => {
+                let _ = self.sender.send(Message::SetExpression {value});
+            }
+            Event::Pressure {pressure} => {
+                self.pressure = pressure;
+                self.send_intensity();
+            }
+            Event::PitchBend {value} => {
+                let _ = self.sender.send(Message::SetPitchBend {semitones: 4.0*(value-0.5)});
+            }
+        }
+    }
+
+    /// Send the NoteOff for a note that was released, if any.  Call this after all events that
+    /// happen at the same time have been passed to handle_event().
+    pub fn flush(&mut self) {
+        if self.send_note_off {
+            // The release velocity adjusts the release rate for this note.  A velocity of 0.5
+            // leaves it unchanged.
+
+            self.send_note_off = false;
+            let release = (self.release_rate + self.release_velocity - 0.5).clamp(0.0, 1.0);
+            let _ = self.sender.send(Message::SetReleaseRate {release});
+            let _ = self.sender.send(Message::NoteOff);
+        }
+    }
+
+    /// Select which of the held notes should be played.
+    fn select_note(&self) -> Option<(u8, f32)> {
+        match self.note_priority {
+            NotePriority::Last => self.held_notes.last().copied(),
+            NotePriority::Highest => self.held_notes.iter().copied().max_by_key(|n| n.0),
+            NotePriority::Lowest => self.held_notes.iter().copied().min_by_key(|n| n.0)
+        }
+    }
+
+    /// Start singing a note on the next syllable or word of the phrase, or continue the current
+    /// syllable if return_to_held is true.
+    fn start_note(&mut self, note: u8, velocity: f32, return_to_held: bool) {
+        let count = phrase_syllables(&self.phrase).count();
+        if count == 0 {
+            return;
+        }
+        let advance = self.advance_syllable && !return_to_held;
+        let (first, last) = if advance {
+            let first = ((self.syllable_index+1)%count as i32) as usize;
+            let last = match self.grouping {
+                SyllableGrouping::Syllable => first,
+                SyllableGrouping::Word => word_end(&self.phrase, first)
+            };
+            self.syllable_index = last as i32;
+            (first, last)
+        }
+        else {
+            let index = if self.syllable_index < 0 || self.syllable_index >= count as i32 {0} else {self.syllable_index as usize};
+            (index, index)
+        };
+
+        // When singing a whole word, each syllable after the first immediately follows the
+        // previous one, and the last one is held for the rest of the note.
+
+        for (i, syllable) in phrase_syllables(&self.phrase).enumerate().take(last+1).skip(first) {
+            let _ = self.sender.send(Message::SetSyllableIndex {index: i as i32});
+            let _ = self.sender.send(Message::NoteOn {
+                syllable: syllable.to_string(),
+                note_index: note as i32 + self.transpose,
+                velocity,
+                continue_syllable: !advance && i == first,
+                vowel_start: self.vowel_start});
+        }
+        self.last_note = Some(note);
+        self.pending_note_off = false;
+
+        // If we get both a NoteOn and a NoteOff at the same time, skip the NoteOff to allow
+        // legato playing.
+
+        self.send_note_off = false;
+    }
+
+    fn send_intensity(&self) {
+        let _ = self.sender.send(Message::SetIntensity {intensity: self.intensity+(1.0-self.intensity)*self.pressure});
+    }
+}
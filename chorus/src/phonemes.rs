@@ -14,21 +14,30 @@
 // If not, see <https://www.gnu.org/licenses/>.
 
 use crate::filter::{ResonantFilter, LowpassFilter};
-use crate::VoicePart;
+use crate::{VoicePart, SAMPLE_RATE};
+use crate::syllable::{VOWELS, CONSONANTS};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::f32::consts::PI;
 
+/// How far the midpoint of a transition between two vowels is pulled toward a neutral schwa.
+const SCHWA_BIAS: f32 = 0.3;
+
+/// The nasal coupling of the French nasal vowels.  This is less than for [m] and [n], since the
+/// mouth stays open and most of the sound still comes from it.
+const NASAL_VOWEL_COUPLING: f32 = 0.25;
+
 /// A Phonemes object acts as a database of information on how to pronounce vowels and consonants.
 pub struct Phonemes {
     voice_part: VoicePart,
     shape_map: HashMap<char, Vec<f32>>,
-    nasal_vowels: HashSet<char>,
+    nasal_coupling: HashMap<char, f32>,
     consonant_map: HashMap<char, Consonant>,
     final_consonant_map: HashMap<char, Consonant>,
     voiced_consonants: HashSet<char>,
     amplification: HashMap<char, f32>,
-    g_position: HashMap<char, usize>
+    g_position: HashMap<char, usize>,
+    intermediate_shapes: HashMap<(char, char), Vec<f32>>
 }
 
 impl Phonemes {
@@ -63,6 +72,10 @@ impl Phonemes {
                 shape_map.insert('u', vec![1.66, 1.68, 1.54, 1.32, 1.12, 1.9, 4.55, 5.39, 5.46, 5.22, 5.37, 5.75, 6.37, 6.54, 6.44, 5.92, 5.12, 4.49, 4.25, 4.34, 3.87, 2.61, 2.22, 1.66, 1.2, 1.11, 1.42, 2.24, 3.82, 5.83, 7.89, 9.55, 10.7, 10.7, 10.6, 12.1, 9.52, 5.02, 2.82, 1.48, 0.865, 0.674]);
                 shape_map.insert('y', vec![0.991, 1.02, 0.965, 0.877, 0.771, 0.795, 2.49, 4.28, 4.86, 4.55, 4.13, 3.84, 4.22, 5.28, 5.97, 6.58, 7.15, 7.67, 8.25, 7.85, 6.91, 6.67, 5.46, 4.14, 2.85, 1.66, 1.0, 0.878, 0.989, 1.23, 1.56, 1.72, 1.79, 1.73, 1.79, 1.91, 1.96, 2.09, 1.79, 1.14, 0.657, 0.533]);
                 shape_map.insert('{', vec![1.06, 1.0, 0.932, 0.885, 0.945, 0.946, 0.949, 0.956, 0.863, 0.778, 0.732, 0.807, 0.964, 1.15, 1.41, 1.6, 1.67, 1.85, 2.25, 2.59, 2.8, 3.04, 3.38, 3.85, 4.37, 5.01, 5.47, 5.78, 5.96, 5.99, 5.87, 5.54, 5.37, 5.31, 5.18, 4.89, 4.37, 3.8, 3.54, 3.37, 3.22, 3.09]);
+                shape_map.insert('ɑ', vec![1.34, 1.14, 0.879, 0.692, 0.548, 0.449, 0.541, 1.1, 1.41, 1.16, 0.719, 0.426, 0.323, 0.322, 0.376, 0.465, 0.564, 0.747, 1.16, 1.82, 2.33, 3.26, 4.74, 6.36, 7.88, 9.01, 9.78, 10.4, 10.7, 10.4, 9.83, 8.82, 7.9, 6.99, 6.04, 5.09, 4.15, 3.41, 3.12, 2.78, 2.74, 2.84]);
+                shape_map.insert('ɛ', vec![0.666, 0.591, 0.546, 0.547, 0.573, 0.534, 0.514, 0.573, 0.605, 0.52, 0.559, 0.618, 0.774, 0.912, 0.997, 0.97, 0.941, 0.987, 1.24, 1.44, 1.62, 1.74, 1.83, 2.16, 2.51, 2.66, 2.75, 2.98, 3.27, 3.52, 3.5, 3.4, 3.49, 3.45, 3.56, 3.54, 3.31, 2.7, 2.3, 2.19, 2.03, 1.73]);
+                shape_map.insert('ɔ', vec![1.37, 1.31, 1.1, 0.844, 0.617, 0.495, 0.902, 1.65, 1.99, 1.68, 1.21, 0.912, 0.916, 0.836, 0.68, 0.449, 0.313, 0.342, 0.505, 0.757, 0.892, 0.913, 1.55, 2.44, 3.38, 4.16, 5.19, 6.52, 8.42, 10.3, 11.9, 12.0, 12.0, 12.0, 10.1, 8.3, 6.27, 4.1, 2.42, 1.42, 0.978, 0.771]);
+                shape_map.insert('œ', vec![1.07, 0.973, 0.815, 0.671, 0.559, 0.498, 1.08, 1.98, 2.22, 1.99, 1.55, 1.29, 1.45, 1.81, 2.01, 2.12, 2.26, 2.63, 3.15, 3.73, 3.98, 3.55, 3.5, 3.47, 3.43, 3.43, 3.36, 3.38, 3.8, 4.24, 4.43, 4.54, 4.77, 4.82, 4.64, 4.48, 4.16, 3.47, 3.34, 2.32, 1.81, 1.62]);
             }
             VoicePart::Alto => {
                 shape_map.insert('&', vec![0.609, 0.552, 0.458, 0.376, 0.32, 0.334, 0.607, 1.54, 2.12, 2.3, 2.03, 1.75, 1.54, 1.59, 1.71, 1.74, 1.83, 1.94, 2.02, 2.18, 2.47, 2.83, 3.21, 3.34, 3.45, 3.77, 3.93, 4.01, 4.16, 4.37, 4.7, 5.0, 5.2, 5.27, 5.42, 5.51, 5.34, 4.99, 4.48, 3.88, 3.23, 2.64, 2.24, 2.19, 2.14]);
@@ -92,6 +105,10 @@ impl Phonemes {
                 shape_map.insert('u', vec![1.66, 1.68, 1.56, 1.36, 1.15, 1.34, 3.7, 5.11, 5.62, 5.3, 5.23, 5.43, 5.92, 6.41, 6.54, 6.43, 5.97, 5.19, 4.56, 4.27, 4.3, 4.2, 3.12, 2.39, 2.01, 1.47, 1.13, 1.13, 1.47, 2.28, 3.76, 5.62, 7.54, 9.2, 10.4, 10.8, 10.3, 11.7, 11.6, 7.78, 4.13, 2.48, 1.32, 0.835, 0.675]);
                 shape_map.insert('y', vec![0.991, 1.02, 0.971, 0.894, 0.797, 0.718, 1.57, 3.82, 4.54, 4.84, 4.38, 4.03, 3.86, 4.34, 5.35, 5.97, 6.54, 7.06, 7.54, 8.13, 8.14, 7.14, 6.82, 6.18, 4.9, 3.71, 2.5, 1.47, 0.959, 0.874, 0.98, 1.2, 1.52, 1.69, 1.79, 1.74, 1.75, 1.85, 1.95, 2.0, 2.03, 1.72, 0.985, 0.638, 0.533]);
                 shape_map.insert('{', vec![1.06, 1.0, 0.939, 0.876, 0.96, 0.926, 0.971, 0.941, 0.933, 0.826, 0.755, 0.75, 0.814, 1.01, 1.16, 1.41, 1.59, 1.64, 1.82, 2.13, 2.49, 2.74, 2.93, 3.17, 3.49, 4.01, 4.54, 5.09, 5.51, 5.78, 5.95, 5.99, 5.91, 5.61, 5.43, 5.28, 5.27, 5.04, 4.7, 4.12, 3.7, 3.47, 3.36, 3.2, 3.09]);
+                shape_map.insert('ɑ', vec![1.32, 1.14, 0.893, 0.719, 0.578, 0.482, 0.414, 0.854, 1.36, 1.38, 1.0, 0.616, 0.385, 0.316, 0.32, 0.372, 0.451, 0.54, 0.694, 1.04, 1.61, 2.13, 2.81, 3.98, 5.45, 6.94, 8.24, 9.13, 9.73, 10.2, 10.5, 10.3, 9.82, 9.1, 8.26, 7.45, 6.63, 5.72, 4.79, 3.89, 3.29, 3.05, 2.69, 2.68, 2.77]);
+                shape_map.insert('ɛ', vec![0.679, 0.608, 0.56, 0.535, 0.609, 0.55, 0.522, 0.539, 0.62, 0.587, 0.494, 0.582, 0.66, 0.795, 0.918, 0.994, 0.979, 0.932, 0.972, 1.15, 1.35, 1.54, 1.64, 1.76, 1.92, 2.25, 2.52, 2.62, 2.73, 2.95, 3.21, 3.47, 3.46, 3.41, 3.44, 3.46, 3.5, 3.53, 3.51, 3.1, 2.58, 2.24, 2.22, 2.01, 1.74]);
+                shape_map.insert('ɔ', vec![1.44, 1.39, 1.2, 0.967, 0.726, 0.553, 0.637, 1.36, 1.75, 1.76, 1.32, 1.02, 0.869, 0.944, 0.924, 0.796, 0.549, 0.381, 0.339, 0.429, 0.634, 0.734, 0.726, 0.967, 1.7, 2.8, 4.03, 5.31, 6.75, 8.24, 9.87, 11.0, 11.4, 11.2, 11.1, 10.4, 9.07, 7.95, 6.68, 5.25, 3.46, 2.04, 1.35, 0.954, 0.775]);
+                shape_map.insert('œ', vec![1.08, 0.985, 0.837, 0.697, 0.579, 0.525, 0.708, 1.7, 2.1, 2.25, 1.81, 1.47, 1.26, 1.52, 1.82, 2.01, 2.12, 2.22, 2.57, 3.01, 3.56, 3.99, 3.77, 3.48, 3.51, 3.41, 3.39, 3.38, 3.32, 3.37, 3.79, 4.21, 4.41, 4.5, 4.71, 4.81, 4.64, 4.57, 4.27, 3.87, 3.36, 3.15, 2.2, 1.81, 1.63]);
             }
             VoicePart::Tenor => {
                 shape_map.insert('&', vec![0.609, 0.553, 0.467, 0.388, 0.332, 0.327, 0.427, 1.14, 1.88, 2.27, 2.22, 1.94, 1.67, 1.52, 1.61, 1.72, 1.74, 1.82, 1.93, 2.01, 2.12, 2.36, 2.69, 3.04, 3.32, 3.34, 3.58, 3.84, 3.94, 4.02, 4.17, 4.38, 4.69, 4.97, 5.17, 5.26, 5.39, 5.52, 5.42, 5.17, 4.73, 4.24, 3.64, 3.06, 2.54, 2.21, 2.2, 2.14]);
@@ -121,6 +138,10 @@ impl Phonemes {
                 shape_map.insert('u', vec![1.66, 1.68, 1.58, 1.4, 1.2, 1.13, 2.7, 4.83, 5.42, 5.49, 5.23, 5.28, 5.48, 6.07, 6.44, 6.54, 6.43, 6.0, 5.28, 4.64, 4.3, 4.25, 4.32, 3.72, 2.59, 2.27, 1.81, 1.32, 1.08, 1.16, 1.52, 2.31, 3.7, 5.43, 7.23, 8.87, 10.1, 10.8, 10.5, 10.8, 12.2, 10.3, 6.28, 3.56, 2.21, 1.21, 0.815, 0.675]);
                 shape_map.insert('y', vec![0.991, 1.01, 0.976, 0.906, 0.818, 0.723, 1.01, 2.95, 4.31, 4.84, 4.64, 4.25, 3.94, 3.9, 4.47, 5.39, 5.98, 6.51, 7.0, 7.44, 7.97, 8.27, 7.53, 6.84, 6.66, 5.63, 4.44, 3.34, 2.23, 1.31, 0.935, 0.872, 0.972, 1.17, 1.48, 1.65, 1.78, 1.76, 1.73, 1.81, 1.91, 1.95, 2.06, 1.91, 1.63, 0.892, 0.625, 0.534]);
                 shape_map.insert('{', vec![1.06, 1.0, 0.944, 0.896, 0.938, 0.935, 0.971, 0.947, 0.957, 0.872, 0.795, 0.741, 0.77, 0.825, 1.04, 1.18, 1.41, 1.58, 1.62, 1.8, 2.02, 2.39, 2.66, 2.82, 3.03, 3.33, 3.68, 4.13, 4.67, 5.17, 5.55, 5.78, 5.94, 5.98, 5.93, 5.68, 5.47, 5.29, 5.3, 5.15, 4.9, 4.46, 3.94, 3.68, 3.42, 3.35, 3.19, 3.09]);
+                shape_map.insert('ɑ', vec![1.32, 1.15, 0.918, 0.745, 0.604, 0.501, 0.417, 0.616, 1.11, 1.41, 1.27, 0.861, 0.537, 0.343, 0.309, 0.317, 0.368, 0.442, 0.529, 0.66, 0.936, 1.42, 2.02, 2.51, 3.36, 4.66, 6.01, 7.26, 8.29, 9.01, 9.6, 10.2, 10.5, 10.5, 10.2, 9.64, 8.75, 7.88, 7.04, 6.1, 5.21, 4.32, 3.6, 3.18, 2.97, 2.68, 2.73, 2.8]);
+                shape_map.insert('ɛ', vec![0.664, 0.598, 0.552, 0.511, 0.625, 0.555, 0.538, 0.526, 0.583, 0.613, 0.549, 0.526, 0.56, 0.694, 0.811, 0.922, 1.0, 0.987, 0.933, 0.98, 1.08, 1.32, 1.47, 1.62, 1.71, 1.77, 2.04, 2.36, 2.57, 2.66, 2.76, 3.0, 3.22, 3.51, 3.48, 3.45, 3.42, 3.51, 3.44, 3.56, 3.52, 3.4, 2.85, 2.45, 2.18, 2.2, 1.96, 1.71]);
+                shape_map.insert('ɔ', vec![1.44, 1.4, 1.21, 1.01, 0.778, 0.576, 0.524, 1.06, 1.63, 1.86, 1.66, 1.24, 0.948, 0.887, 0.945, 0.907, 0.779, 0.553, 0.399, 0.317, 0.431, 0.624, 0.737, 0.788, 0.782, 1.28, 2.05, 3.0, 3.97, 5.03, 6.25, 7.6, 9.23, 10.6, 11.4, 11.7, 11.9, 11.4, 10.2, 8.84, 7.64, 6.17, 4.54, 3.13, 1.75, 1.25, 0.903, 0.759]);
+                shape_map.insert('œ', vec![1.07, 0.985, 0.85, 0.71, 0.609, 0.538, 0.536, 1.28, 2.02, 2.22, 2.12, 1.69, 1.41, 1.25, 1.57, 1.83, 1.99, 2.09, 2.18, 2.46, 2.88, 3.41, 3.91, 4.02, 3.59, 3.52, 3.5, 3.42, 3.37, 3.35, 3.29, 3.36, 3.77, 4.19, 4.39, 4.49, 4.68, 4.79, 4.73, 4.53, 4.41, 4.1, 3.59, 3.34, 2.94, 2.09, 1.78, 1.63]);
             }
             VoicePart::Bass => {
                 shape_map.insert('&', vec![0.609, 0.554, 0.477, 0.404, 0.346, 0.32, 0.338, 0.674, 1.53, 2.03, 2.3, 2.17, 1.91, 1.66, 1.52, 1.59, 1.7, 1.74, 1.77, 1.88, 1.98, 2.04, 2.22, 2.48, 2.82, 3.2, 3.53, 3.62, 3.89, 4.21, 4.37, 4.44, 4.49, 4.63, 4.84, 5.02, 5.18, 5.29, 5.31, 5.41, 5.52, 5.44, 5.25, 4.91, 4.47, 3.96, 3.35, 2.84, 2.42, 2.19, 2.2, 2.14]);
@@ -150,6 +171,10 @@ impl Phonemes {
                 shape_map.insert('u', vec![1.66, 1.69, 1.59, 1.44, 1.27, 1.12, 1.64, 4.02, 5.1, 5.59, 5.37, 5.21, 5.29, 5.48, 6.04, 6.41, 6.54, 6.46, 6.22, 5.59, 4.94, 4.47, 4.24, 4.27, 4.28, 3.63, 2.58, 2.29, 1.89, 1.4, 1.13, 1.08, 1.29, 1.78, 2.73, 4.15, 5.77, 7.41, 8.92, 10.1, 10.7, 10.7, 10.4, 12.1, 11.6, 8.32, 4.8, 3.07, 1.9, 1.12, 0.796, 0.676]);
                 shape_map.insert('y', vec![0.991, 1.01, 0.979, 0.92, 0.843, 0.763, 0.736, 1.79, 3.78, 4.4, 4.93, 4.56, 4.2, 3.93, 3.88, 4.33, 5.25, 5.79, 6.33, 6.76, 7.19, 7.62, 8.14, 8.21, 7.31, 6.83, 6.65, 5.72, 4.6, 3.61, 2.56, 1.61, 1.05, 0.865, 0.898, 1.02, 1.22, 1.51, 1.66, 1.77, 1.77, 1.73, 1.78, 1.88, 1.95, 1.98, 2.1, 1.79, 1.45, 0.804, 0.609, 0.535]);
                 shape_map.insert('{', vec![1.06, 0.999, 0.949, 0.918, 0.917, 0.948, 0.946, 0.968, 0.946, 0.949, 0.851, 0.78, 0.736, 0.773, 0.818, 1.01, 1.12, 1.39, 1.51, 1.63, 1.68, 1.84, 2.15, 2.46, 2.69, 2.83, 3.03, 3.31, 3.58, 4.02, 4.48, 4.98, 5.31, 5.68, 5.86, 5.94, 5.98, 5.9, 5.65, 5.46, 5.29, 5.3, 5.19, 4.99, 4.71, 4.18, 3.76, 3.6, 3.4, 3.33, 3.17, 3.08]);
+                shape_map.insert('ɑ', vec![1.37, 1.19, 0.983, 0.811, 0.665, 0.551, 0.47, 0.423, 0.861, 1.3, 1.43, 1.2, 0.841, 0.539, 0.352, 0.316, 0.319, 0.352, 0.411, 0.493, 0.564, 0.727, 1.05, 1.53, 2.0, 2.44, 3.22, 4.42, 5.63, 6.84, 7.89, 8.69, 9.17, 9.74, 10.2, 10.4, 10.2, 9.8, 9.3, 8.55, 7.8, 7.12, 6.35, 5.59, 4.79, 3.98, 3.39, 3.17, 2.9, 2.68, 2.74, 2.79]);
+                shape_map.insert('ɛ', vec![0.683, 0.617, 0.566, 0.544, 0.61, 0.58, 0.553, 0.522, 0.543, 0.617, 0.604, 0.532, 0.543, 0.565, 0.695, 0.805, 0.912, 0.976, 1.03, 0.922, 0.944, 0.972, 1.16, 1.32, 1.48, 1.59, 1.67, 1.75, 1.96, 2.26, 2.48, 2.59, 2.65, 2.8, 3.08, 3.29, 3.46, 3.43, 3.4, 3.38, 3.47, 3.4, 3.5, 3.51, 3.49, 3.15, 2.65, 2.34, 2.23, 2.2, 1.96, 1.75]);
+                shape_map.insert('ɔ', vec![1.62, 1.57, 1.38, 1.17, 0.897, 0.68, 0.548, 0.701, 1.36, 1.71, 1.82, 1.51, 1.17, 0.908, 0.844, 0.905, 0.884, 0.802, 0.636, 0.449, 0.346, 0.388, 0.456, 0.682, 0.769, 0.785, 0.768, 1.21, 1.84, 2.65, 3.54, 4.37, 5.37, 6.63, 8.04, 9.49, 10.7, 11.4, 11.7, 11.9, 11.5, 10.4, 9.07, 7.97, 6.71, 5.4, 3.73, 2.57, 1.62, 1.17, 0.895, 0.773]);
+                shape_map.insert('œ', vec![1.05, 0.968, 0.842, 0.711, 0.618, 0.527, 0.477, 0.727, 1.59, 1.98, 2.18, 1.97, 1.6, 1.37, 1.22, 1.5, 1.76, 1.94, 2.02, 2.12, 2.3, 2.66, 3.11, 3.66, 4.14, 4.21, 3.78, 3.73, 3.74, 3.61, 3.56, 3.54, 3.45, 3.42, 3.62, 4.05, 4.39, 4.55, 4.61, 4.75, 4.82, 4.76, 4.52, 4.41, 4.13, 3.83, 3.26, 3.3, 2.6, 1.91, 1.72, 1.6]);
             }
         }
         shape_map.insert('b', shape_map.get(&'m').unwrap().clone());
@@ -158,28 +183,16 @@ impl Phonemes {
         shape_map.insert('k', shape_map.get(&'N').unwrap().clone());
         shape_map.insert('p', shape_map.get(&'m').unwrap().clone());
         shape_map.insert('t', shape_map.get(&'l').unwrap().clone());
-        let mut nasal_vowels = HashSet::new();
-        nasal_vowels.insert('m');
-        nasal_vowels.insert('n');
-        nasal_vowels.insert('N');
-        let mut amplification = HashMap::new();
-        amplification.insert('a', 1.0);
-        amplification.insert('e', 1.3);
-        amplification.insert('i', 1.5);
-        amplification.insert('o', 1.5);
-        amplification.insert('u', 2.0);
-        amplification.insert('y', 2.2);
-        amplification.insert('A', 0.9);
-        amplification.insert('I', 1.2);
-        amplification.insert('O', 1.2);
-        amplification.insert('U', 1.3);
-        amplification.insert('V', 0.9);
-        amplification.insert('Y', 1.5);
-        amplification.insert('2', 1.6);
-        amplification.insert('3', 1.3);
-        amplification.insert('6', 1.3);
-        amplification.insert('9', 1.3);
-        amplification.insert('&', 1.3);
+        let mut nasal_coupling = HashMap::new();
+        nasal_coupling.insert('m', 0.5);
+        nasal_coupling.insert('n', 0.5);
+        nasal_coupling.insert('N', 0.5);
+
+        // The French nasal vowels are sung with the nasal cavity partly open.
+
+        for nasal in ['ɑ', 'ɛ', 'ɔ', 'œ'] {
+            nasal_coupling.insert(nasal, NASAL_VOWEL_COUPLING);
+        }
         let mut g_position = HashMap::new();
         let ratio = match voice_part {
             VoicePart::Soprano => 51.0/42.0,
@@ -191,18 +204,19 @@ impl Phonemes {
         for (c, pos) in [('&', 36), ('2', 36), ('3', 33), ('9', 36), ('@', 27), ('A', 25),
                          ('E', 32), ('I', 36), ('N', 32), ('O', 31), ('U', 31), ('V', 31),
                          ('Y', 37), ('a', 26), ('e', 37), ('i', 33), ('o', 32), ('u', 34),
-                         ('y', 33), ('{', 32)] {
+                         ('y', 33), ('{', 32), ('ɑ', 28), ('ɛ', 32), ('ɔ', 31), ('œ', 36)] {
             g_position.insert(c, (pos as f32/ratio).round() as usize);
         }
         let mut result = Self {
             voice_part: voice_part,
             shape_map: shape_map,
-            nasal_vowels: nasal_vowels,
+            nasal_coupling: nasal_coupling,
             consonant_map: HashMap::new(),
             final_consonant_map: HashMap::new(),
             voiced_consonants: HashSet::new(),
-            amplification: amplification,
-            g_position: g_position
+            amplification: HashMap::new(),
+            g_position: g_position,
+            intermediate_shapes: HashMap::new()
         };
         // Scaling doesn't give good results for all voice parts for d.
         let dpos = match voice_part {
@@ -219,7 +233,7 @@ impl Phonemes {
         result.add_consonant('f', 2500, 1500, 2000, 2000, 0.008, 46, 5000.0, 5000.0, 0.0, true, false, ' ', 1.0, true, true);
         result.add_consonant('g', 600, 2700, 200, 750, 0.005, 44, 1025.0, 5200.0, 1.0, false, true, 'k', 0.25, true, false);
         result.add_consonant('g', 600, 1200, 200, 750, 0.015, 44, 1025.0, 5200.0, 1.0, false, true, 'k', 0.5, true, true);
-        result.add_consonant('h', 1500, 1000, 2000, 4000, 0.003, 11, 1000.0, 5000.0, 1.0, false, false, ' ', 1.0, false, false);
+        result.add_consonant('h', 1500, 1000, 2000, 4000, 0.0, 11, 1000.0, 5000.0, 1.0, false, false, ' ', 1.0, false, false);
         result.add_consonant('j', 0, 3000, 0, 0, 0.0, 1, 4150.0, 6500.0, 1.0, false, true, 'i', 0.0, false, false);
         result.add_consonant('k', 500, 1500, 900, 1100, 0.012, 35, 4200.0, 4000.0, 1.0, false, false, 'k', 0.8, true, false);
         result.add_consonant('k', 0, 1500, 900, 1100, 0.012, 35, 4200.0, 4000.0, 1.0, false, false, 'U', 0.5, true, true);
@@ -255,6 +269,8 @@ impl Phonemes {
         result.add_consonant('ʦ', 3000, 2700, 1600, 3300, 0.01, 47, 5000.0, 900.0, 1.0, true, false, 't', 0.5, false, true);
         result.add_consonant('ʧ', 200, 2700, 2100, 2300, 0.009, 45, 2000.0, 3000.0, 1.0, true, false, 'S', 0.1, false, false);
         result.add_consonant('ʧ', 3000, 2700, 2100, 2300, 0.015, 45, 2000.0, 3000.0, 1.0, true, false, 'S', 0.1, false, true);
+        // A glottal stop produces no noise.  The Director realizes it by briefly cutting off the glottal excitation.
+        result.add_consonant('?', 0, 300, 300, 300, 0.0, 1, 1000.0, 1000.0, 1.0, false, false, ' ', 1.0, false, false);
         match voice_part {
             VoicePart::Soprano => {
                 result.set_consonant_samples('b', vec![parse_flac(include_bytes!("consonants/soprano/b0.flac")), parse_flac(include_bytes!("consonants/soprano/b1.flac")),
@@ -439,27 +455,112 @@ impl Phonemes {
         }
 }
 
+    /// Replace the recorded samples used for a consonant, for example to better match a particular
+    /// choir.  Each element of samples is one recording, as 16 bit audio at SAMPLE_RATE.  When the
+    /// consonant is sung, most voices play a randomly selected recording, so providing several
+    /// of them gives a more natural sound.  Consonants that do not have built in samples can be
+    /// given them too.  An Err is returned if the consonant is not recognized or no samples are
+    /// provided.
+    pub fn load_consonant_samples(&mut self, consonant: char, samples: Vec<Vec<i16>>) -> Result<(), String> {
+        if !self.consonant_map.contains_key(&consonant) {
+            return Err(format!("Unknown consonant '{consonant}'"));
+        }
+        if samples.iter().all(|s| s.is_empty()) {
+            return Err("No samples provided".to_string());
+        }
+        let samples = samples.iter().filter(|s| !s.is_empty()).map(|s| s.iter().map(|&x| x as f32/32768.0).collect()).collect();
+        self.set_consonant_samples(consonant, samples, 1.0);
+        Ok(())
+    }
+
     /// Get the vocal tract shape (cross-sectional areas for each segment) corresponding to a vowel.
     /// The return value can be passed to voice::set_vocal_shape().
     pub fn get_vowel_shape(&self, vowel: char) -> Option<&Vec<f32>> {
         self.shape_map.get(&vowel)
     }
 
+    /// Get the amplification a vowel has by default, before any change with set_amplification().
+    pub fn default_amplification(vowel: char) -> f32 {
+        match vowel {
+            'e' => 1.3,
+            'i' => 1.5,
+            'o' => 1.5,
+            'u' => 2.0,
+            'y' => 2.2,
+            'A' => 0.9,
+            'I' => 1.2,
+            'O' => 1.2,
+            'U' => 1.3,
+            'V' => 0.9,
+            'Y' => 1.5,
+            '2' => 1.6,
+            '3' => 1.3,
+            '6' => 1.3,
+            '9' => 1.3,
+            '&' => 1.3,
+            'ɑ' => 1.05,
+            'ɔ' => 1.35,
+            'œ' => 1.3,
+            _ => 1.0
+        }
+    }
+
     /// Get the amount of amplification to use when pronouncing a vowel.  This is used to maintain
     /// a consistent volume.
     pub fn get_amplification(&self, vowel: char) -> f32 {
         match self.amplification.get(&vowel) {
             Some(a) => *a,
-            None => 1.0
+            None => Self::default_amplification(vowel)
         }
     }
 
+    /// Override the amount of amplification to use when pronouncing a vowel.  Use this to balance
+    /// the loudness of vowels that sound too quiet or too loud relative to the others.
+    pub fn set_amplification(&mut self, vowel: char, amplification: f32) {
+        self.amplification.insert(vowel, amplification.max(0.0));
+    }
+
     /// Get the amount of nasal coupling for a vowel.  The return value can be passed to voice::set_vocal_shape().
     pub fn get_nasal_coupling(&self, vowel: char) -> f32 {
-        if self.nasal_vowels.contains(&vowel) {
-            return 0.5;
+        match self.nasal_coupling.get(&vowel) {
+            Some(c) => *c,
+            None => 0.0
+        }
+    }
+
+    /// Get the number of segments in the vocal tract for this voice part.
+    pub fn get_vocal_length(&self) -> usize {
+        match self.voice_part {
+            VoicePart::Soprano => 42,
+            VoicePart::Alto => 45,
+            VoicePart::Tenor => 48,
+            VoicePart::Bass => 52
+        }
+    }
+
+    /// Override the vocal tract shape used for a vowel.  Only the vowels returned by vowels() can
+    /// appear in a syllable, so those are the only ones whose shapes can be replaced.  An Err is
+    /// returned for any other symbol, or if the length of the shape does not equal
+    /// get_vocal_length().
+    pub fn set_vowel_shape(&mut self, vowel: char, shape: Vec<f32>) -> Result<(), String> {
+        if !VOWELS.contains(&vowel) {
+            return Err(format!("Unknown vowel: {vowel}"));
+        }
+        if shape.len() != self.get_vocal_length() {
+            return Err(format!("Shape has length {} but the vocal tract has {} segments", shape.len(), self.get_vocal_length()));
+        }
+        self.shape_map.insert(vowel, shape);
+        Ok(())
+    }
+
+    /// Set the amount of nasal coupling to use for a vowel.
+    pub fn set_nasal_coupling(&mut self, vowel: char, nasal_coupling: f32) {
+        if nasal_coupling == 0.0 {
+            self.nasal_coupling.remove(&vowel);
+        }
+        else {
+            self.nasal_coupling.insert(vowel, nasal_coupling);
         }
-        0.0
     }
 
     /// Get a description of how to synthesize a consonant.
@@ -495,11 +596,58 @@ impl Phonemes {
         result
     }
 
+    /// Get a summary of how a consonant is pronounced, either at the start of a syllable or at the
+    /// end.  This is useful for tools that need to predict phrasing, such as whether two notes can
+    /// be connected legato across the consonant.  Returns None if the consonant is not recognized.
+    pub fn get_consonant_info(&self, consonant: char, is_final: bool) -> Option<ConsonantInfo> {
+        let c = self.get_consonant(consonant, None, is_final, 1.0)?;
+        Some(ConsonantInfo {
+            voiced: c.voiced,
+            position: c.position,
+            delay: c.delay,
+            transition_time: c.transition_time,
+            on_time: c.on_time,
+            off_time: c.off_time,
+            sampled: !c.samples.is_empty()
+        })
+    }
+
+    /// Get the symbols for all vowels that can appear in a syllable.  The French nasal vowels are
+    /// represented by single characters: `ɑ` for `A~`, `ɛ` for `E~`, `ɔ` for `O~`, and `œ` for
+    /// `9~`.
+    pub fn vowels() -> &'static [char] {
+        VOWELS
+    }
+
+    /// Get the symbols for all consonants that can appear in a syllable.  The affricates are
+    /// represented by single characters: `ʤ` for `dZ`, `ʦ` for `ts`, and `ʧ` for `tS`.  Use
+    /// is_voiced_consonant() to find which ones are voiced.
+    pub fn consonants() -> &'static [char] {
+        CONSONANTS
+    }
+
     /// Get whether a consonant is voiced.
     pub fn is_voiced_consonant(&self, consonant: char) -> bool {
         self.voiced_consonants.contains(&consonant)
     }
 
+    /// Get whether a consonant is an aspirate ([h]).  Rather than injecting noise into the vocal
+    /// tract, it is produced by turbulence at the glottis.
+    pub fn is_aspirate(&self, consonant: char) -> bool {
+        consonant == 'h'
+    }
+
+    /// Get whether a consonant is a glottal stop.
+    pub fn is_glottal_stop(&self, consonant: char) -> bool {
+        consonant == '?'
+    }
+
+    /// Get whether a consonant is an unvoiced stop (p, t, or k).  These are released with a burst
+    /// of aspiration noise whose strength can be varied.
+    pub fn is_unvoiced_stop(&self, consonant: char) -> bool {
+        consonant == 'p' || consonant == 't' || consonant == 'k'
+    }
+
     /// Get the vocal tract shape to use when pronouncing a consonant.  This is created by blending
     /// the consonant's base shape with that of the adjacent vowel, then narrowing it to create a
     /// constriction at the point where the noise is generated.
@@ -537,14 +685,18 @@ impl Phonemes {
         Some(shape)
     }
 
-    /// Get the shape of an intermediate point between two vowels.  In most cases this returns
-    /// None, indicating that we can simply interpolate between the shapes of the two vowels.
-    /// In certain cases that leads to unrealistic results, and defining an intermediate point
-    /// is necessary to get a smooth sounding transition.
+    /// Get the shape of an intermediate point between two vowels.  A transition passes through
+    /// this point rather than morphing directly from one shape to the other, which is more like
+    /// how a real singer moves between them.  This returns None if the vowels are the same, or
+    /// if either one is a consonant-like vowel ([l], [n], or [N]), indicating that we can simply
+    /// interpolate between the shapes.
     pub fn get_intermediate_shape(&self, vowel1: char, vowel2: char) -> Option<Vec<f32>> {
         if vowel1 == vowel2 {
             return None;
         }
+        if let Some(shape) = self.intermediate_shapes.get(&(vowel1, vowel2)) {
+            return Some(shape.clone());
+        }
         if vowel1 == 'm' || vowel2 == 'm' {
             let mut shape = (if vowel1 == 'm' {self.shape_map.get(&vowel2)?} else {self.shape_map.get(&vowel1)?}).clone();
             let n = shape.len();
@@ -554,7 +706,32 @@ impl Phonemes {
             }
             return Some(shape);
         }
-        None
+        if !VOWELS.contains(&vowel1) || !VOWELS.contains(&vowel2) || "lnN".contains(vowel1) || "lnN".contains(vowel2) {
+            return None;
+        }
+
+        // Use the average of the two shapes, pulled partway toward a schwa.
+
+        let shape1 = self.shape_map.get(&vowel1)?;
+        let shape2 = self.shape_map.get(&vowel2)?;
+        let schwa = self.shape_map.get(&'@')?;
+        Some((0..shape1.len()).map(|i| (1.0-SCHWA_BIAS)*0.5*(shape1[i]+shape2[i]) + SCHWA_BIAS*schwa[i]).collect())
+    }
+
+    /// Set the intermediate shape to pass through when changing from vowel1 to vowel2,
+    /// overriding the one returned by get_intermediate_shape().  This only affects transitions in
+    /// that direction.  An Err is returned if the shape has the wrong length.
+    pub fn set_intermediate_shape(&mut self, vowel1: char, vowel2: char, shape: Vec<f32>) -> Result<(), String> {
+        if shape.len() != self.get_vocal_length() {
+            return Err(format!("Shape has length {} but the vocal tract has {} segments", shape.len(), self.get_vocal_length()));
+        }
+        self.intermediate_shapes.insert((vowel1, vowel2), shape);
+        Ok(())
+    }
+
+    /// Remove a shape set with set_intermediate_shape(), restoring the default.
+    pub fn clear_intermediate_shape(&mut self, vowel1: char, vowel2: char) {
+        self.intermediate_shapes.remove(&(vowel1, vowel2));
     }
 }
 
@@ -571,6 +748,37 @@ pub fn parse_flac(file: &[u8]) -> Vec<f32> {
     samples
 }
 
+/// Read a recording from a FLAC file, so it can be passed to Phonemes::load_consonant_samples().
+/// It must be mono, 16 bit, and sampled at SAMPLE_RATE.
+pub fn read_flac(path: &str) -> Result<Vec<i16>, String> {
+    let mut reader = claxon::FlacReader::open(path).map_err(|e| e.to_string())?;
+    let info = reader.streaminfo();
+    if info.sample_rate != SAMPLE_RATE as u32 || info.channels != 1 || info.bits_per_sample != 16 {
+        return Err(format!("{path} must be mono 16 bit audio at {SAMPLE_RATE} Hz"));
+    }
+    reader.samples().map(|sample| sample.map(|x| x as i16).map_err(|e| e.to_string())).collect()
+}
+
+/// A summary of how a consonant is pronounced, as returned by Phonemes::get_consonant_info().
+/// Times are measured in samples.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ConsonantInfo {
+    /// Whether the glottis continues to sound during the consonant.
+    pub voiced: bool,
+    /// The segment of the vocal tract at which the noise is injected.
+    pub position: usize,
+    /// The delay between when the consonant is scheduled and when its noise begins.
+    pub delay: i64,
+    /// The time taken to move the vocal tract between the consonant and the adjacent vowel.
+    pub transition_time: i64,
+    /// The time over which the noise grows louder.
+    pub on_time: i64,
+    /// The time over which the noise fades away.
+    pub off_time: i64,
+    /// Whether the consonant is produced from recorded samples rather than synthesized noise.
+    pub sampled: bool
+}
+
 /// A description of how to form the vocal tract shape.  This involves
 ///
 /// 1. Starting from a fixed shape specific to the consonant.
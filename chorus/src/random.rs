@@ -13,7 +13,10 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
+#[cfg(feature = "getrandom")]
 use getrandom::getrandom;
+#[cfg(not(feature = "getrandom"))]
+use std::sync::atomic::{AtomicU32, Ordering};
 
 const UNIFORM_SCALE: f32 = 1.0/(0x100000000i64 as f32);
 
@@ -28,8 +31,12 @@ pub struct Random {
 
 impl Random {
     pub fn new() -> Self {
-        // Select a seed.
+        Self::with_seed(Self::select_seed())
+    }
 
+    /// Select a seed for new().  This asks the operating system for random data.
+    #[cfg(feature = "getrandom")]
+    fn select_seed() -> u32 {
         let mut data = [0u8; 4];
         let mut seed = 0;
         if let Ok(_) = getrandom(&mut data) {
@@ -41,6 +48,21 @@ impl Random {
             // This should only happen in strange situations when something went wrong
             // at the OS level.  Just use 0.
         }
+        seed
+    }
+
+    /// Select a seed for new().  Without the getrandom feature there is no source of entropy,
+    /// as on wasm32-unknown-unknown, so each generator gets a different but predictable seed.
+    /// Use with_seed() to vary the output between runs.
+    #[cfg(not(feature = "getrandom"))]
+    fn select_seed() -> u32 {
+        static NEXT_SEED: AtomicU32 = AtomicU32::new(0x2545f491);
+        NEXT_SEED.fetch_add(0x9e3779b9, Ordering::Relaxed)
+    }
+
+    /// Create a generator with a specific seed.  Generators created with the same seed always
+    /// produce the same sequence of values.
+    pub fn with_seed(seed: u32) -> Self {
         Self {i: seed, next_normal: 0.0, next_normal_valid: false}
     }
 
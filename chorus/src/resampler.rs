@@ -13,42 +13,89 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
+use crate::trig::{sin_cycles, cos_cycles};
 use crate::SAMPLE_RATE;
 
+/// The longest filter a Resampler can use.  The history is stored in a fixed size array, so
+/// Resamplers can be copied and created without allocating memory.
+pub const MAX_FILTER_LENGTH: usize = 64;
+
 /// Convert output from the synthesizer's native sample rate (48 kHz) to a different sample rate.
-/// The method used by this class is very fast and doesn't introduce latency, but the results may
-/// not always be the best.  When possible, it is preferable to output at the native sample rate.
+/// By default it uses linear interpolation, which is very fast and doesn't introduce latency, but
+/// the results may not always be the best.  Create it with with_filter_length() to use a windowed
+/// sinc filter instead, trading CPU time and latency for cleaner high frequencies.  When possible,
+/// it is preferable to output at the native sample rate.
 #[derive(Copy, Clone)]
 pub struct Resampler {
     output_interval: f32,
+    half_width: usize,
+    cutoff: f32,
+    history: [f32; MAX_FILTER_LENGTH],
     x2: f32,
-    y1: f32,
-    y2: f32,
     next_output_time: f32
 }
 
 impl Resampler {
-    /// Create a Resampler that converts to a specified sample rate, measured in Hz.
+    /// Create a Resampler that converts to a specified sample rate, measured in Hz.  It uses
+    /// linear interpolation.
     pub fn new(sample_rate: f32) -> Self {
+        Self::with_filter_length(sample_rate, 2)
+    }
+
+    /// Create a Resampler that converts to a specified sample rate, measured in Hz, using a
+    /// filter that spans a specified number of input samples.  A length of 2 or less gives linear
+    /// interpolation.  Longer filters remove more aliasing but take more time to compute, and
+    /// delay the output by latency() samples.  The length is limited to MAX_FILTER_LENGTH.
+    pub fn with_filter_length(sample_rate: f32, length: usize) -> Self {
+        let half_width = length.min(MAX_FILTER_LENGTH).div_ceil(2).max(1);
         Self {
             output_interval: SAMPLE_RATE as f32/sample_rate,
+            half_width,
+            cutoff: (sample_rate/SAMPLE_RATE as f32).min(1.0),
+            history: [0.0; MAX_FILTER_LENGTH],
             x2: 0.0,
-            y1: 0.0,
-            y2: 0.0,
             next_output_time: 0.0
         }
     }
 
+    /// Get the delay introduced by the filter, measured in samples at the native rate.  This is 0
+    /// for linear interpolation.
+    pub fn latency(&self) -> usize {
+        self.half_width-1
+    }
+
     /// Get whether there is output ready.
     pub fn has_output(&self) -> bool {
-        self.next_output_time <= self.x2
+        self.next_output_time + self.latency() as f32 <= self.x2
     }
 
     /// Get the next output sample.  This will panic if no output is ready.
     pub fn get_output(&mut self) -> f32 {
         assert!(self.has_output(), "No output ready");
-        let f = self.x2-self.next_output_time;
-        let result = f*self.y1 + (1.0-f)*self.y2;
+        let f = self.x2-self.next_output_time-self.latency() as f32;
+        let result = if self.half_width == 1 {
+            f*self.history[0] + (1.0-f)*self.history[1]
+        }
+        else {
+            // Apply a Hann windowed sinc filter centered on the output time.  When downsampling,
+            // the cutoff is lowered to the output's Nyquist frequency to avoid aliasing.  The
+            // weights are normalized so a constant input passes through unchanged.
+
+            let n = 2*self.half_width;
+            let mut sum = 0.0;
+            let mut weight_sum = 0.0;
+            for (i, y) in self.history[..n].iter().enumerate() {
+                let x = (n-1-i) as f32 - (f+self.latency() as f32);
+                if x.abs() >= self.half_width as f32 {
+                    continue;
+                }
+                let sinc = if x == 0.0 {1.0} else {sin_cycles(0.5*self.cutoff*x)/(std::f32::consts::PI*self.cutoff*x)};
+                let window = 0.5+0.5*cos_cycles(0.5*x/self.half_width as f32);
+                sum += y*sinc*window;
+                weight_sum += sinc*window;
+            }
+            sum/weight_sum
+        };
         self.next_output_time += self.output_interval;
         while self.x2 > 1.0 && self.next_output_time > 1.0 {
             self.x2 -= 1.0;
@@ -60,8 +107,9 @@ impl Resampler {
     /// Add an input value.  This will panic if there is output waiting to be retreived.
     pub fn add_input(&mut self, y: f32) {
         assert!(!self.has_output(), "Cannot add input when output is ready");
-        self.y1 = self.y2;
-        self.y2 = y;
+        let n = 2*self.half_width;
+        self.history.copy_within(1..n, 0);
+        self.history[n-1] = y;
         self.x2 += 1.0;
     }
-}
\ No newline at end of file
+}
@@ -13,8 +13,8 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
-const VOWELS: &str = "aeilmnouyAEINOUVY239&{@";
-const CONSONANTS: &str = "bdfghjkprstvwxzCDSTZ4ʤʦʧ";
+pub(crate) const VOWELS: &[char] = &['a', 'e', 'i', 'l', 'm', 'n', 'o', 'u', 'y', 'A', 'E', 'I', 'N', 'O', 'U', 'V', 'Y', '2', '3', '9', '&', '{', '@', 'ɑ', 'ɛ', 'ɔ', 'œ'];
+pub(crate) const CONSONANTS: &[char] = &['b', 'd', 'f', 'g', 'h', 'j', 'k', 'p', 'r', 's', 't', 'v', 'w', 'x', 'z', 'C', 'D', 'S', 'T', 'Z', '4', 'ʤ', 'ʦ', 'ʧ', '?'];
 
 /// A Syllable consists of:
 ///
@@ -24,28 +24,62 @@ const CONSONANTS: &str = "bdfghjkprstvwxzCDSTZ4ʤʦʧ";
 /// - zero or more final vowels
 /// - zero or final consonants
 ///
+/// The main vowel may optionally be followed by a glide target, indicated with `>`.  For example,
+/// `de>I` glides from `e` to `I` during the note, rather than touching `I` briefly at the end.
+///
+/// Any vowel may be followed by `:` to lengthen it.  A syllabic consonant (`l`, `m`, `n`, or `N`)
+/// may be followed by `=` to mark it as the main vowel.
+///
 /// Syllables are created by calling Syllable::build(), which parses an X-SAMPA description.
+/// A rolled R (`r`) is expanded into a series of trill taps followed by a flip (`4`).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Syllable {
     pub initial_consonants: Vec<char>,
     pub initial_vowels: Vec<char>,
     pub main_vowel: char,
+    pub glide: Option<char>,
     pub final_vowels: Vec<char>,
-    pub final_consonants: Vec<char>
+    pub final_consonants: Vec<char>,
+    pub long_initial_vowels: Vec<bool>,
+    pub long_main_vowel: bool,
+    pub long_final_vowels: Vec<bool>
 }
 
+/// A syllable that indicates a rest.  Passing it to Director::note_on() ends the current note
+/// without starting a new one, so a rest in the melody can occupy a slot in a phrase.
+pub const REST: &str = "_";
+
+/// The number of taps a rolled R receives when no other number is specified.
+pub const DEFAULT_TRILL_TAPS: u32 = 2;
+
 impl Syllable {
     pub fn build(sampa: &str) -> Result<Syllable, String> {
+        Syllable::build_with_trill(sampa, DEFAULT_TRILL_TAPS)
+    }
+
+    /// Parse an X-SAMPA description, specifying how many taps to use for a rolled R.  Zero
+    /// taps reduces it to a single flip.
+    pub fn build_with_trill(sampa: &str, trill_taps: u32) -> Result<Syllable, String> {
         // First split the string into initial consonants, vowels, and final consonants.
 
-        let sampa = sampa.replace("ts", "ʦ").replace("tS", "ʧ").replace("dZ", "ʤ").replace("r", "rr4");
+        let trill = "r".repeat(trill_taps as usize) + "4";
+        let sampa = sampa.replace("ts", "ʦ").replace("tS", "ʧ").replace("dZ", "ʤ").replace("r", &trill);
+        let sampa = sampa.replace("A~", "ɑ").replace("E~", "ɛ").replace("O~", "ɔ").replace("9~", "œ");
         let mut initial_consonants: Vec<char> = Vec::new();
         let mut vowels: Vec<char> = Vec::new();
+        let mut long: Vec<bool> = Vec::new();
         let mut final_consonants: Vec<char> = Vec::new();
         let mut stage = 0;
         let mut explicit_main = 0;
         let mut has_explicit_main = false;
+        let mut glide = None;
+        let mut expect_glide = false;
+        let mut after_vowel = false;
         for c in sampa.chars() {
-            if CONSONANTS.contains(c) {
+            if expect_glide && !VOWELS.contains(&c) {
+                return Err("> must be followed by a vowel".to_string());
+            }
+            if CONSONANTS.contains(&c) {
                 if stage == 0 {
                     initial_consonants.push(c);
                 }
@@ -53,12 +87,22 @@ impl Syllable {
                     final_consonants.push(c);
                     stage = 2;
                 }
+                after_vowel = false;
             }
-            else if VOWELS.contains(c) {
+            else if VOWELS.contains(&c) {
                 if stage == 2 {
                     return Err("Vowel after final consonant".to_string());
                 }
-                vowels.push(c);
+                if expect_glide {
+                    glide = Some(c);
+                    expect_glide = false;
+                    after_vowel = false;
+                }
+                else {
+                    vowels.push(c);
+                    long.push(false);
+                    after_vowel = true;
+                }
                 stage = 1;
             }
             else if c == '-' {
@@ -70,6 +114,36 @@ impl Syllable {
                 }
                 explicit_main = vowels.len()-1;
                 has_explicit_main = true;
+                after_vowel = false;
+            }
+            else if c == ':' {
+                if !after_vowel || long[vowels.len()-1] {
+                    return Err(": must follow a vowel".to_string());
+                }
+                long[vowels.len()-1] = true;
+            }
+            else if c == '=' {
+                let last = vowels.last();
+                if !after_vowel || !(last == Some(&'l') || last == Some(&'m') || last == Some(&'n') || last == Some(&'N')) {
+                    return Err("= must follow l, m, n, or N".to_string());
+                }
+                if has_explicit_main {
+                    return Err("Only one sound can be marked as the main vowel".to_string())
+                }
+                explicit_main = vowels.len()-1;
+                has_explicit_main = true;
+            }
+            else if c == '>' {
+                if vowels.len() == 0 || stage == 2 {
+                    return Err("> must follow a vowel".to_string());
+                }
+                if has_explicit_main {
+                    return Err("Only one sound can be marked as the main vowel".to_string())
+                }
+                explicit_main = vowels.len()-1;
+                has_explicit_main = true;
+                expect_glide = true;
+                after_vowel = false;
             }
             else {
                 return Err(format!("Illegal character '{}'", c));
@@ -78,6 +152,9 @@ impl Syllable {
 
         // Identify the main vowel.
 
+        if expect_glide {
+            return Err("> must be followed by a vowel".to_string());
+        }
         if vowels.len() == 0 {
             return Err("No vowel in syllable".to_string());
         }
@@ -102,9 +179,22 @@ impl Syllable {
             initial_consonants: initial_consonants,
             initial_vowels: vowels[..main].iter().cloned().collect(),
             main_vowel: vowels[main],
+            glide: glide,
             final_vowels: vowels[main+1..].iter().cloned().collect(),
-            final_consonants: final_consonants
+            final_consonants: final_consonants,
+            long_initial_vowels: long[..main].to_vec(),
+            long_main_vowel: long[main],
+            long_final_vowels: long[main+1..].to_vec()
         };
         Ok(syllable)
     }
+
+    /// Get the vowel that is sustained for most of the note.  This is the glide target if there
+    /// is one, or otherwise the main vowel.
+    pub fn sustained_vowel(&self) -> char {
+        match self.glide {
+            Some(c) => c,
+            None => self.main_vowel
+        }
+    }
 }
\ No newline at end of file
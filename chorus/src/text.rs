@@ -0,0 +1,197 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+// Functions for converting ordinary text to phrases of X-SAMPA syllables.  The result can be
+// edited by hand if a word is not pronounced as desired.
+
+/// A single sound in a word.
+#[derive(Copy, Clone, PartialEq)]
+enum Sound {
+    Vowel(&'static str),
+    Consonant(&'static str),
+    Glide(&'static str)
+}
+
+/// Convert Italian text to a phrase of X-SAMPA syllables separated by spaces.  Each syllable is
+/// sung on one note.  Punctuation is ignored, and an apostrophe joins a word to the next one, as
+/// in l'anima.
+///
+/// Italian spelling does not distinguish open and closed e and o.  They are sung closed unless
+/// written with a grave accent: è and ò give the open vowels `E` and `O`.  Double consonants are
+/// split between syllables, as in `bel lo`.  When the two halves are sung legato, the Director
+/// joins them into a single consonant held longer.  A single r is sung as a flip `4`, and a
+/// double or initial r as a rolled `r`.
+pub fn italian_to_sampa(text: &str) -> String {
+    let text = text.to_lowercase().replace(['\'', '’'], "");
+    let mut syllables = vec![];
+    for word in text.split(|c: char| !c.is_alphabetic()) {
+        let letters: Vec<char> = word.chars().collect();
+        syllables.extend(syllabify(&italian_sounds(&letters)));
+    }
+    syllables.join(" ")
+}
+
+/// Get the X-SAMPA symbol for an Italian vowel, or None if the letter is not a vowel.
+fn italian_vowel(c: char) -> Option<&'static str> {
+    match c {
+        'a' | 'à' | 'á' => Some("A"),
+        'e' | 'é' => Some("e"),
+        'è' => Some("E"),
+        'i' | 'ì' | 'í' | 'î' | 'y' => Some("i"),
+        'o' | 'ó' => Some("o"),
+        'ò' => Some("O"),
+        'u' | 'ù' | 'ú' => Some("u"),
+        _ => None
+    }
+}
+
+/// Convert the letters of an Italian word to the sequence of sounds it contains.
+fn italian_sounds(letters: &[char]) -> Vec<Sound> {
+    let is_vowel = |i: usize| letters.get(i).and_then(|&c| italian_vowel(c)).is_some();
+    let is_front = |i: usize| matches!(letters.get(i).and_then(|&c| italian_vowel(c)), Some("e" | "E" | "i"));
+    let is_letter = |i: usize, c: char| letters.get(i) == Some(&c);
+    let mut sounds = vec![];
+    let mut i = 0;
+    while i < letters.len() {
+        if let Some(vowel) = italian_vowel(letters[i]) {
+            sounds.push(Sound::Vowel(vowel));
+            i += 1;
+            continue;
+        }
+
+        // A doubled consonant is a geminate.  Italian writes a double q as cq.
+
+        let start = i;
+        let double = is_letter(i+1, letters[i]) || (letters[i] == 'c' && is_letter(i+1, 'q'));
+        if double {
+            i += 1;
+        }
+
+        // After c, g, or sc, an i followed by another vowel is not pronounced.  It only shows
+        // that the consonant is soft.
+
+        let silent_i = |j: usize| is_letter(j, 'i') && is_vowel(j+1);
+        let (consonant, glide, length) = match letters[i] {
+            'c' if is_letter(i+1, 'h') => ("k", None, 2),
+            'c' if is_front(i+1) => ("tS", None, if silent_i(i+1) {2} else {1}),
+            'c' | 'k' => ("k", None, 1),
+            'g' if is_letter(i+1, 'h') => ("g", None, 2),
+            'g' if is_letter(i+1, 'n') => ("n", Some("j"), 2),
+            'g' if is_letter(i+1, 'l') && is_letter(i+2, 'i') => ("l", Some("j"), if is_vowel(i+3) {3} else {2}),
+            'g' if is_letter(i+1, 'u') && is_vowel(i+2) => ("g", Some("w"), 2),
+            'g' if is_front(i+1) => ("dZ", None, if silent_i(i+1) {2} else {1}),
+            'g' => ("g", None, 1),
+            's' if is_letter(i+1, 'c') && is_front(i+2) => ("S", None, if silent_i(i+2) {3} else {2}),
+            'q' => ("k", Some("w"), if is_letter(i+1, 'u') {2} else {1}),
+            'r' => (if double || start == 0 {"r"} else {"4"}, None, 1),
+            'z' => ("ts", None, 1),
+            'x' => ("ks", None, 1),
+            'j' => ("", Some("j"), 1),
+            'w' | 'v' => ("v", None, 1),
+            'b' => ("b", None, 1),
+            'd' => ("d", None, 1),
+            'f' => ("f", None, 1),
+            'l' => ("l", None, 1),
+            'm' => ("m", None, 1),
+            'n' => ("n", None, 1),
+            'p' => ("p", None, 1),
+            's' => ("s", None, 1),
+            't' => ("t", None, 1),
+            _ => ("", None, 1)
+        };
+        if !consonant.is_empty() {
+            sounds.push(Sound::Consonant(consonant));
+            if double {
+                sounds.push(Sound::Consonant(consonant));
+            }
+        }
+        if let Some(glide) = glide {
+            sounds.push(Sound::Glide(glide));
+        }
+        i += length;
+    }
+
+    // An i or u before another vowel becomes a glide if it follows a vowel, or in the common
+    // diphthongs ie and uo when they are followed by a consonant, as in pieno and cuore.
+    // Otherwise the two vowels are sung in separate syllables, as in Maria.
+
+    for j in 0..sounds.len() {
+        if let (Sound::Vowel(vowel @ ("i" | "u")), Some(Sound::Vowel(next))) = (sounds[j], sounds.get(j+1).copied()) {
+            let after_vowel = j > 0 && matches!(sounds[j-1], Sound::Vowel(_));
+            let before_consonant = matches!(sounds.get(j+2), Some(Sound::Consonant(_)));
+            let diphthong = (vowel == "i" && (next == "e" || next == "E")) || (vowel == "u" && (next == "o" || next == "O"));
+            if after_vowel || (diphthong && before_consonant) {
+                sounds[j] = Sound::Glide(if vowel == "i" {"j"} else {"w"});
+            }
+        }
+    }
+    sounds
+}
+
+/// Divide the sounds of a word into syllables, returning the X-SAMPA for each one.
+fn syllabify(sounds: &[Sound]) -> Vec<String> {
+    // Find the vowel at the center of each syllable.  A vowel followed by an i or u that is not
+    // itself followed by a vowel forms a falling diphthong with it, as in mai.
+
+    let mut nuclei = vec![];
+    let mut i = 0;
+    while i < sounds.len() {
+        if let Sound::Vowel(vowel) = sounds[i] {
+            let falling = vowel != "i" && vowel != "u" && matches!(sounds.get(i+1), Some(Sound::Vowel("i" | "u"))) && !matches!(sounds.get(i+2), Some(Sound::Vowel(_)));
+            let end = if falling {i+2} else {i+1};
+            nuclei.push((i, end));
+            i = end;
+        }
+        else {
+            i += 1;
+        }
+    }
+
+    // Divide the sounds between each pair of vowels.  The next syllable begins with the glides
+    // before its vowel and the consonant before them, or two consonants if they are an obstruent
+    // followed by l or r, as in a-pri-le.  Any other consonants end the previous syllable.
+
+    let mut boundaries = vec![0];
+    for k in 1..nuclei.len() {
+        let start = nuclei[k-1].1;
+        let mut boundary = nuclei[k].0;
+        while boundary > start && matches!(sounds[boundary-1], Sound::Glide(_)) {
+            boundary -= 1;
+        }
+        if boundary > start && matches!(sounds[boundary-1], Sound::Consonant(_)) {
+            boundary -= 1;
+            let liquid = matches!(sounds[boundary], Sound::Consonant("l" | "r" | "4"));
+            if liquid && boundary > start && matches!(sounds[boundary-1], Sound::Consonant("p" | "b" | "t" | "d" | "k" | "g" | "f" | "v")) {
+                boundary -= 1;
+            }
+        }
+        boundaries.push(boundary);
+    }
+    boundaries.push(sounds.len());
+    nuclei.iter().enumerate().map(|(k, &(vowel_start, vowel_end))| {
+        let mut syllable = String::new();
+        for (j, sound) in sounds.iter().enumerate().take(boundaries[k+1]).skip(boundaries[k]) {
+            let (Sound::Vowel(symbol) | Sound::Consonant(symbol) | Sound::Glide(symbol)) = sound;
+            syllable.push_str(symbol);
+
+            // Mark the first vowel of a diphthong as the main one.
+
+            if j == vowel_start && vowel_end == vowel_start+2 {
+                syllable.push('-');
+            }
+        }
+        syllable
+    }).collect()
+}
@@ -0,0 +1,44 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+// Fast approximations to trigonometric functions, based on a shared lookup table.  They are
+// accurate to about 1e-6, which is plenty for modulation and windowing, and much faster than
+// calling sin() or cos() for every sample of every voice.
+
+use std::f32::consts::PI;
+use std::sync::LazyLock;
+
+/// The number of points per period in the sine table.
+const TABLE_SIZE: usize = 4096;
+
+static SINE_TABLE: LazyLock<Vec<f32>> = LazyLock::new(|| {
+    (0..=TABLE_SIZE).map(|i| (2.0*PI*i as f32/TABLE_SIZE as f32).sin()).collect()
+});
+
+/// Compute sin(2*PI*x).  The argument is measured in periods rather than radians, and may be
+/// any finite value.
+pub fn sin_cycles(x: f32) -> f32 {
+    let x = (x-x.floor())*TABLE_SIZE as f32;
+    let index = (x as usize).min(TABLE_SIZE-1);
+    let fraction = x-index as f32;
+    let table = &*SINE_TABLE;
+    table[index] + fraction*(table[index+1]-table[index])
+}
+
+/// Compute cos(2*PI*x).  The argument is measured in periods rather than radians, and may be
+/// any finite value.
+pub fn cos_cycles(x: f32) -> f32 {
+    sin_cycles(x+0.25)
+}
@@ -0,0 +1,157 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+// Functions for creating tuning tables from Scala scale (.scl) and keyboard mapping (.kbm) files.
+// A tuning table gives the pitch of every MIDI note in cents relative to A4 (440 Hz).  It can be
+// passed to a Director with Message::SetTuningTable.
+
+/// A KeyboardMapping describes how MIDI notes are assigned to the degrees of a scale.  It
+/// corresponds to the contents of a Scala .kbm file.
+pub struct KeyboardMapping {
+    /// The MIDI note that is mapped to scale degree 0.
+    pub middle_note: i32,
+    /// The MIDI note whose frequency is specified.
+    pub reference_note: i32,
+    /// The frequency of the reference note in Hz.
+    pub reference_frequency: f32,
+    /// The scale degree that is treated as the octave.
+    pub octave_degree: usize,
+    /// The scale degree assigned to each key in the mapping, or None for keys that are not
+    /// mapped.  If this is empty, keys are mapped linearly to scale degrees.
+    pub mapping: Vec<Option<usize>>
+}
+
+impl KeyboardMapping {
+    /// Create the standard mapping, where middle C is scale degree 0 and A4 is 440 Hz.
+    pub fn standard(scale_size: usize) -> Self {
+        Self {
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: scale_size,
+            mapping: vec![]
+        }
+    }
+}
+
+/// Get the lines of a Scala file, skipping comments.
+fn data_lines(text: &str) -> impl Iterator<Item=&str> {
+    text.lines().filter(|line| !line.starts_with('!'))
+}
+
+/// Parse the contents of a Scala .scl file.  The return value contains the size of each scale
+/// degree in cents, starting from degree 1.  The last element is the period of the scale
+/// (usually an octave).
+pub fn parse_scale(text: &str) -> Result<Vec<f32>, String> {
+    let mut lines = data_lines(text);
+    lines.next().ok_or("Missing description")?;
+    let count_line = lines.next().ok_or("Missing number of notes")?;
+    let count: usize = count_line.trim().parse().map_err(|_| format!("Invalid number of notes: {count_line}"))?;
+    let mut degrees = vec![];
+    for line in lines.take(count) {
+        let value = line.split_whitespace().next().ok_or("Empty line in scale")?;
+        let cents = if value.contains('.') {
+            value.parse::<f32>().map_err(|_| format!("Invalid pitch: {value}"))?
+        }
+        else {
+            let (numerator, denominator) = match value.split_once('/') {
+                Some((n, d)) => (n, d),
+                None => (value, "1")
+            };
+            let n = numerator.parse::<f32>().map_err(|_| format!("Invalid pitch: {value}"))?;
+            let d = denominator.parse::<f32>().map_err(|_| format!("Invalid pitch: {value}"))?;
+            if n <= 0.0 || d <= 0.0 {
+                return Err(format!("Invalid pitch: {value}"));
+            }
+            1200.0*(n/d).log2()
+        };
+        degrees.push(cents);
+    }
+    if degrees.len() != count || count == 0 {
+        return Err("Scale contains the wrong number of notes".to_string());
+    }
+    Ok(degrees)
+}
+
+/// Parse the contents of a Scala .kbm file.
+pub fn parse_keyboard_mapping(text: &str) -> Result<KeyboardMapping, String> {
+    let values: Vec<&str> = data_lines(text).map(|line| line.split_whitespace().next().unwrap_or("")).collect();
+    if values.len() < 7 {
+        return Err("Keyboard mapping is incomplete".to_string());
+    }
+    let parse_int = |s: &str| s.parse::<i32>().map_err(|_| format!("Invalid value in keyboard mapping: {s}"));
+    let parse_degree = |s: &str| match parse_int(s)? {
+        degree if degree >= 0 => Ok(degree as usize),
+        _ => Err(format!("Invalid scale degree in keyboard mapping: {s}"))
+    };
+
+    // A mapping never needs to be larger than the number of MIDI notes.
+
+    let size = parse_int(values[0])?;
+    if !(0..=128).contains(&size) {
+        return Err(format!("Invalid keyboard mapping size: {size}"));
+    }
+    let mut mapping = vec![];
+    for i in 0..size as usize {
+        match values.get(7+i) {
+            Some(&"x") | None => mapping.push(None),
+            Some(value) => mapping.push(Some(parse_degree(value)?))
+        }
+    }
+    Ok(KeyboardMapping {
+        middle_note: parse_int(values[3])?,
+        reference_note: parse_int(values[4])?,
+        reference_frequency: values[5].parse::<f32>().map_err(|_| format!("Invalid reference frequency: {}", values[5]))?,
+        octave_degree: parse_degree(values[6])?,
+        mapping
+    })
+}
+
+/// Compute the pitch of a note in cents relative to the middle note, or None if it is not mapped.
+fn note_cents(note: i32, scale: &[f32], mapping: &KeyboardMapping) -> Option<f32> {
+    let period = scale[scale.len()-1];
+    let degree_cents = |degree: usize| {
+        let octaves = (degree/scale.len()) as f32;
+        let degree = degree%scale.len();
+        octaves*period + if degree == 0 {0.0} else {scale[degree-1]}
+    };
+    let steps = note-mapping.middle_note;
+    if mapping.mapping.is_empty() {
+        let size = scale.len() as i32;
+        return Some(steps.div_euclid(size) as f32*period + degree_cents(steps.rem_euclid(size) as usize));
+    }
+    let size = mapping.mapping.len() as i32;
+    let octave_cents = degree_cents(mapping.octave_degree);
+    let degree = mapping.mapping[steps.rem_euclid(size) as usize]?;
+    Some(steps.div_euclid(size) as f32*octave_cents + degree_cents(degree))
+}
+
+/// Build a tuning table for all 128 MIDI notes.  If mapping is None, the standard mapping is used.
+/// Notes that are not mapped to any scale degree are left in equal temperament.  Returns an error
+/// if the scale is empty.
+pub fn build_tuning_table(scale: &[f32], mapping: Option<&KeyboardMapping>) -> Result<Vec<f32>, String> {
+    if scale.is_empty() {
+        return Err("Scale contains no notes".to_string());
+    }
+    let standard = KeyboardMapping::standard(scale.len());
+    let mapping = mapping.unwrap_or(&standard);
+    let reference_cents = note_cents(mapping.reference_note, scale, mapping).unwrap_or(0.0) - 1200.0*(mapping.reference_frequency/440.0).log2();
+    Ok((0..128).map(|note| {
+        match note_cents(note, scale, mapping) {
+            Some(cents) => cents-reference_cents,
+            None => 100.0*(note-69) as f32
+        }
+    }).collect())
+}
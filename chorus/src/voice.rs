@@ -15,10 +15,25 @@
 
 use std::f32::consts::PI;
 use crate::random::Random;
-use crate::filter::{Filter, LowpassFilter, BandpassFilter};
+use crate::trig::{sin_cycles, cos_cycles};
+use crate::filter::{Filter, LowpassFilter, BandpassFilter, BiquadFilter};
 use crate::VoicePart;
 use crate::SAMPLE_RATE;
 
+/// The number of points per period in the table of LF waveform values.
+const LF_TABLE_SIZE: usize = 512;
+
+/// When the glottal excitation is oversampled, frequencies above this (in Hz) are removed before
+/// decimating to the output rate.
+const OVERSAMPLING_CUTOFF: f32 = 20000.0;
+
+// The standard amplitudes of the random fluctuations added by the Glottis.
+
+const FREQUENCY_DRIFT: f32 = 0.005;
+const VOLUME_DRIFT: f32 = 0.1;
+const VIBRATO_FREQUENCY_DRIFT: f32 = 0.05;
+const VIBRATO_AMPLITUDE_DRIFT: f32 = 0.4;
+
 /// This implements the glottal excitation of the source-filter model.  It consists of
 /// a Liljencrants-Fant (LF) model plus pulsed noise.  To improve realism, it adds
 /// random fluctuations to several aspects of the output: frequency, amplitude,
@@ -34,13 +49,21 @@ pub struct Glottis {
     pub vibrato_frequency_drift_amplitude: f32,
     pub vibrato_amplitude_drift_amplitude: f32,
     pub tremolo_amplitude: f32,
+    pub tremolo_frequency: f32,
+    pub aspiration: f32,
+    pub whisper: f32,
     phase: f32,
+    oversampling: usize,
+    decimation_filters: [BiquadFilter; 2],
     frequency_drift: f32,
     volume_drift: f32,
     vibrato_phase: f32,
+    tremolo_phase: f32,
     vibrato_amplitude_drift: f32,
     random: Random,
     noise_filter: LowpassFilter,
+    aspiration_filter: LowpassFilter,
+    whisper_filter: LowpassFilter,
     pub formant: BandpassFilter,
     last_rd: f32,
     alpha: f32,
@@ -49,30 +72,43 @@ pub struct Glottis {
     ta: f32,
     tp: f32,
     te: f32,
-    shift: f32
+    shift: f32,
+    lf_table: Vec<f32>
 }
 
 impl Glottis {
     pub fn new(index: usize, formant_frequency: f32) -> Self {
-        let mut random = Random::new();
+        Glottis::with_random(index, formant_frequency, Random::new())
+    }
+
+    /// Create a Glottis that uses a specific random number generator for its fluctuations.
+    pub fn with_random(index: usize, formant_frequency: f32, mut random: Random) -> Self {
         Self {
             frequency: 220.0,
             rd: 1.7,
             noise: 0.01,
-            frequency_drift_amplitude: 0.005,
-            volume_drift_amplitude: 0.1,
+            frequency_drift_amplitude: FREQUENCY_DRIFT,
+            volume_drift_amplitude: VOLUME_DRIFT,
             vibrato_frequency: 5.0,
             vibrato_amplitude: 0.02,
-            vibrato_frequency_drift_amplitude: 0.05,
-            vibrato_amplitude_drift_amplitude: 0.4,
+            vibrato_frequency_drift_amplitude: VIBRATO_FREQUENCY_DRIFT,
+            vibrato_amplitude_drift_amplitude: VIBRATO_AMPLITUDE_DRIFT,
             tremolo_amplitude: 0.2,
+            tremolo_frequency: 0.0,
+            aspiration: 0.0,
+            whisper: 0.0,
             phase: random.get_uniform(),
+            oversampling: 1,
+            decimation_filters: [BiquadFilter::lowpass(OVERSAMPLING_CUTOFF, 0.541); 2],
             frequency_drift: random.get_normal(),
             volume_drift: random.get_normal(),
             vibrato_phase: 0.3*index as f32,
+            tremolo_phase: (0.3*index as f32) % 1.0,
             vibrato_amplitude_drift: random.get_normal(),
             random: random,
             noise_filter: LowpassFilter::new(2000.0),
+            aspiration_filter: LowpassFilter::new(4000.0),
+            whisper_filter: LowpassFilter::new(6000.0),
             formant: BandpassFilter::new(0.5*formant_frequency, 2.0*formant_frequency),
             last_rd: 0.0,
             alpha: 0.0,
@@ -81,7 +117,8 @@ impl Glottis {
             ta: 0.0,
             tp: 0.0,
             te: 0.0,
-            shift: 0.0
+            shift: 0.0,
+            lf_table: vec![0.0; LF_TABLE_SIZE+1]
         }
     }
 
@@ -105,6 +142,20 @@ impl Glottis {
             self.e0 = 1.0/((self.alpha*self.te).exp()*(PI*self.te/self.tp).sin());
             self.shift = (-self.epsilon*(1.0-self.te)).exp();
             self.last_rd = self.rd;
+
+            // Evaluating the waveform involves transcendental functions, so precompute it over
+            // one period and interpolate.  The extra point at the end allows interpolating
+            // right up to the end of the period.
+
+            for i in 0..=LF_TABLE_SIZE {
+                let t = i as f32 / LF_TABLE_SIZE as f32;
+                self.lf_table[i] = if t < self.te {
+                    self.e0*(self.alpha*t).exp()*(PI*t/self.tp).sin()
+                }
+                else {
+                    ((-self.epsilon*(t-self.te)).exp() - self.shift)/(self.epsilon*self.ta)
+                };
+            }
         }
 
         // Randomly vary aspects of the output to make it sound more natural.
@@ -119,37 +170,97 @@ impl Glottis {
         // This depends on the primary frequency of the note, vibrato, and
         // random drift.
 
-        let vibrato_freq = self.vibrato_frequency * (1.0+self.vibrato_frequency_drift_amplitude*(0.5*PI*self.vibrato_phase).cos());
+        let vibrato_freq = self.vibrato_frequency * (1.0+self.vibrato_frequency_drift_amplitude*cos_cycles(0.25*self.vibrato_phase));
         let vibrato_amplitude = self.vibrato_amplitude * (1.0+self.vibrato_amplitude_drift_amplitude*self.vibrato_amplitude_drift);
         let vibrato_offset = vibrato_freq / SAMPLE_RATE as f32;
         self.vibrato_phase = (self.vibrato_phase+vibrato_offset) % 4.0;
-        let vibrato = (2.0*PI*self.vibrato_phase).sin();
+        let vibrato = sin_cycles(self.vibrato_phase);
         let vibrato = vibrato*vibrato*vibrato;
         let freq = self.frequency * (1.0+self.frequency_drift_amplitude*self.frequency_drift) * (1.0+vibrato_amplitude*vibrato);
         let offset = freq / SAMPLE_RATE as f32;
-        self.phase = (self.phase+offset) % 1.0;
+        let pulse = if self.oversampling > 1 {
+            // Evaluate the waveform at several points within the sample, and remove frequencies
+            // that would alias before decimating to the output rate.
+
+            let mut pulse = 0.0;
+            for _ in 0..self.oversampling {
+                self.phase = (self.phase+offset/self.oversampling as f32) % 1.0;
+                let value = self.decimation_filters[0].process(self.lf_value(self.phase));
+                pulse = self.decimation_filters[1].process(value);
+            }
+            pulse
+        }
+        else {
+            self.phase = (self.phase+offset) % 1.0;
+            self.lf_value(self.phase)
+        };
         let t = self.phase;
 
         // Add noise consisting of regular peaks plus a uniform background.
 
         let x = (t-0.5-(t-0.5).round()).abs();
-        let window = if x < 0.25 {0.5+0.5*(4.0*PI*x).cos()} else {0.0};
+        let window = if x < 0.25 {0.5+0.5*cos_cycles(2.0*x)} else {0.0};
         let noise = self.noise_filter.process(self.noise*(0.2+window)*(2.0*self.random.get_uniform()-1.0));
 
         // Compute the output.
 
         let volume = 1.0 + self.volume_drift_amplitude*self.volume_drift;
-        let mut excitation;
-        if t < self.te {
-            excitation = noise + self.e0*(self.alpha*t).exp()*(PI*t/self.tp).sin();
+        let mut excitation = noise + pulse;
+
+        // Tremolo follows the vibrato unless it has its own frequency.
+
+        let tremolo_phase = if self.tremolo_frequency > 0.0 {
+            self.tremolo_phase = (self.tremolo_phase+self.tremolo_frequency/SAMPLE_RATE as f32) % 1.0;
+            self.tremolo_phase
         }
         else {
-            excitation = noise + ((-self.epsilon*(t-self.te)).exp() - self.shift)/(self.epsilon*self.ta)
+            self.vibrato_phase
+        };
+        excitation *= 1.0+self.tremolo_amplitude*sin_cycles(tremolo_phase);
+
+        // When whispering, the periodic excitation is replaced by turbulent noise.
+
+        if self.whisper > 0.0 {
+            let turbulence = self.whisper_filter.process(2.0*self.random.get_uniform()-1.0);
+            excitation = (1.0-self.whisper)*excitation + 0.5*self.whisper*turbulence;
         }
-        excitation *= 1.0+self.tremolo_amplitude*((2.0*PI*self.vibrato_phase).sin());
         excitation += 1.2*self.formant.process(excitation);
         volume*excitation
     }
+
+    /// Get the value of the LF waveform at a point in its period, interpolating from the table.
+    fn lf_value(&self, t: f32) -> f32 {
+        let x = t*LF_TABLE_SIZE as f32;
+        let index = (x as usize).min(LF_TABLE_SIZE-1);
+        let fraction = x-index as f32;
+        self.lf_table[index] + fraction*(self.lf_table[index+1]-self.lf_table[index])
+    }
+
+    /// Set the factor by which the glottal waveform is oversampled.  Higher values reduce
+    /// aliasing on high notes at the cost of more computation.
+    pub fn set_oversampling(&mut self, factor: usize) {
+        let factor = factor.max(1);
+        if factor == self.oversampling {
+            return;
+        }
+        self.oversampling = factor;
+
+        // The filters run at the oversampled rate, but compute their coefficients assuming the
+        // output rate, so the cutoff must be scaled down by the same factor.  Together they form
+        // a fourth order Butterworth filter.
+
+        let cutoff = OVERSAMPLING_CUTOFF/factor as f32;
+        self.decimation_filters = [BiquadFilter::lowpass(cutoff, 0.541), BiquadFilter::lowpass(cutoff, 1.307)];
+    }
+
+    /// Generate turbulent noise at the glottis, as in the consonant [h].  Unlike the noise added
+    /// by generate(), this is produced even when there is no glottal tone.
+    pub fn generate_aspiration(&mut self) -> f32 {
+        if self.aspiration == 0.0 {
+            return 0.0;
+        }
+        self.aspiration*self.aspiration_filter.process(2.0*self.random.get_uniform()-1.0)
+    }
 }
 
 /// A 1D waveguide along which an audio signal can propagate.  A Voice uses two of these,
@@ -179,6 +290,18 @@ impl Waveguide {
         self.compute_reflections();
     }
 
+    /// Get the shape of the waveguide, specified as the area of each segment.
+    pub fn get_shape(&self) -> &Vec<f32> {
+        &self.area
+    }
+
+    /// Get the instantaneous pressure in each segment, which is the sum of the waves traveling
+    /// in the two directions.  The values are stored in pressure, replacing its contents.
+    pub fn get_pressure(&self, pressure: &mut Vec<f32>) {
+        pressure.clear();
+        pressure.extend(self.right.iter().zip(&self.left).map(|(right, left)| right+left));
+    }
+
     /// Compute the reflection coefficients for the segments.  This is called automatically
     /// whenever the shape changes.
     pub fn compute_reflections(&mut self) {
@@ -204,14 +327,34 @@ pub struct Voice {
     glottis: Glottis,
     vocal: Waveguide,
     nasal: Waveguide,
+    subglottal: Waveguide,
+    subglottal_coupling: f32,
+    vocal_damping: f32,
+    nasal_damping: f32,
     volume: f32,
     nasal_coupling: f32,
     coupling_position: usize,
-    nasal_off_after_step: i64
+    nasal_off_after_step: i64,
+    shape_length: usize,
+    scaled_shape: Vec<f32>,
+    formant_frequency: f32
 }
 
 impl Voice {
     pub fn new(voice_part: VoicePart, index: usize) -> Self {
+        Voice::with_random(voice_part, index, Random::new())
+    }
+
+    /// Create a Voice that uses a specific random number generator for its fluctuations.
+    pub fn with_random(voice_part: VoicePart, index: usize, random: Random) -> Self {
+        Voice::with_length_scale(voice_part, index, random, 1.0)
+    }
+
+    /// Create a Voice whose vocal tract is longer or shorter than normal by a factor of
+    /// length_scale.  Shapes are still specified with the standard number of segments for the
+    /// voice part, and are resampled to the actual length.  The singer's formant is shifted
+    /// to match.
+    pub fn with_length_scale(voice_part: VoicePart, index: usize, random: Random, length_scale: f32) -> Self {
         let vocal_length;
         let coupling_position;
         let vibrato_frequency;
@@ -247,20 +390,45 @@ impl Voice {
                 nasal_shape = vec![1.52, 1.69, 1.95, 2.54, 3.17, 3.88, 4.39, 4.18, 2.75, 2.48, 2.44, 2.24, 2.07, 2.17, 2.43, 2.78, 2.66, 2.06, 1.44, 1.38, 1.29, 1.08, 1.13, 0.978, 0.658];
             }
         }
+        let actual_length = usize::max(2, (length_scale*vocal_length as f32).round() as usize);
+        let ratio = actual_length as f32 / vocal_length as f32;
+        // The subglottal tract is modeled as a uniform tube whose first resonance is a little
+        // above the first formant of a neutral vowel.
+
+        let subglottal_length = match voice_part {
+            VoicePart::Soprano | VoicePart::Alto => 34,
+            VoicePart::Tenor | VoicePart::Bass => 40
+        };
+        let subglottal_length = usize::max(2, (ratio*subglottal_length as f32).round() as usize);
         let mut voice = Voice {
-            glottis: Glottis::new(index, formant_frequency),
-            vocal: Waveguide::new(vocal_length),
+            glottis: Glottis::with_random(index, formant_frequency/ratio, random),
+            vocal: Waveguide::new(actual_length),
             nasal: Waveguide::new(nasal_shape.len()),
+            subglottal: Waveguide::new(subglottal_length),
+            subglottal_coupling: 0.0,
+            vocal_damping: 0.995,
+            nasal_damping: 0.98,
             volume: 1.0,
             nasal_coupling: 0.0,
-            coupling_position: coupling_position,
-            nasal_off_after_step: 0
+            coupling_position: usize::min(actual_length-1, (ratio*coupling_position as f32).round() as usize),
+            nasal_off_after_step: 0,
+            shape_length: vocal_length,
+            scaled_shape: vec![0.0; actual_length],
+            formant_frequency: formant_frequency/ratio
         };
         voice.nasal.set_shape(&nasal_shape);
+        voice.subglottal.set_shape(&vec![2.0; subglottal_length]);
         voice.glottis.vibrato_frequency = vibrato_frequency;
         voice
     }
 
+    /// Shift the center of the singer's formant by a factor relative to its standard frequency
+    /// for this voice.
+    pub fn set_formant_shift(&mut self, factor: f32) {
+        let frequency = factor*self.formant_frequency;
+        self.glottis.formant = BandpassFilter::new(0.5*frequency, 2.0*frequency);
+    }
+
     /// Set the volume of the glottal excitation (between 0.0 and 1.0).
     pub fn set_volume(&mut self, volume: f32) {
         self.volume = volume;
@@ -271,10 +439,43 @@ impl Voice {
     /// tract and nasal cavity.  This should be 0.5 for nasal sounds like m and n, 0.0
     /// for most others.
     pub fn set_vocal_shape(&mut self, shape: &Vec<f32>, nasal_coupling: f32) {
-        self.vocal.set_shape(shape);
+        let n = self.scaled_shape.len();
+        if shape.len() == n || shape.len() < 2 {
+            self.vocal.set_shape(shape);
+        }
+        else {
+            // The vocal tract has a different number of segments than the shape.  Interpolate it.
+
+            let scale = (shape.len()-1) as f32 / (n-1) as f32;
+            for i in 0..n {
+                let x = i as f32*scale;
+                let j = usize::min(x as usize, shape.len()-2);
+                let fraction = x-j as f32;
+                self.scaled_shape[i] = (1.0-fraction)*shape[j] + fraction*shape[j+1];
+            }
+            self.vocal.set_shape(&self.scaled_shape);
+        }
         self.nasal_coupling = nasal_coupling;
     }
 
+    /// Get the current shape of the vocal tract.  If the Voice was created with a length scale,
+    /// this may have a slightly different number of segments than the shapes passed to
+    /// set_vocal_shape().
+    pub fn get_vocal_shape(&self) -> &Vec<f32> {
+        self.vocal.get_shape()
+    }
+
+    /// Get the current degree of coupling between the vocal tract and nasal cavity.
+    pub fn get_nasal_coupling(&self) -> f32 {
+        self.nasal_coupling
+    }
+
+    /// Get the instantaneous pressure in each segment of the vocal tract.  The values are stored
+    /// in pressure, replacing its contents, so a buffer can be reused without allocating memory.
+    pub fn get_tract_pressure(&self, pressure: &mut Vec<f32>) {
+        self.vocal.get_pressure(pressure);
+    }
+
     /// Set the frequency of the glottal excitation (in Hz).
     pub fn set_frequency(&mut self, frequency: f32) {
         self.glottis.frequency = frequency;
@@ -292,6 +493,42 @@ impl Voice {
         self.glottis.noise = noise;
     }
 
+    /// Scale the amplitudes of all random fluctuations in the glottal excitation.  1.0 gives the
+    /// standard amounts, 0.0 removes them for a perfectly steady tone, and larger values make
+    /// the voice less steady.
+    pub fn set_humanize(&mut self, amount: f32) {
+        self.glottis.frequency_drift_amplitude = amount*FREQUENCY_DRIFT;
+        self.glottis.volume_drift_amplitude = amount*VOLUME_DRIFT;
+        self.glottis.vibrato_frequency_drift_amplitude = amount*VIBRATO_FREQUENCY_DRIFT;
+        self.glottis.vibrato_amplitude_drift_amplitude = amount*VIBRATO_AMPLITUDE_DRIFT;
+    }
+
+    /// Set the fraction of a wave's amplitude that is retained as it passes through each segment
+    /// of the vocal tract and nasal cavity.  Values closer to 1.0 produce a brighter, more
+    /// ringing tone, and smaller values produce a warmer, more damped one.
+    pub fn set_damping(&mut self, vocal: f32, nasal: f32) {
+        self.vocal_damping = vocal;
+        self.nasal_damping = nasal;
+    }
+
+    /// Set the strength of coupling between the glottis and the subglottal tract (between 0.0
+    /// and 1.0).  This adds the subglottal resonances, which are most noticeable in low voices.
+    pub fn set_subglottal_coupling(&mut self, coupling: f32) {
+        self.subglottal_coupling = coupling;
+    }
+
+    /// Set how much to whisper (between 0.0 and 1.0).  At 1.0 the glottal tone is completely
+    /// replaced by turbulent noise.
+    pub fn set_whisper(&mut self, whisper: f32) {
+        self.glottis.whisper = whisper;
+    }
+
+    /// Set the amplitude of aspiration noise generated at the glottis.  This is independent of
+    /// the volume, so it can be heard before the glottal tone begins.
+    pub fn set_aspiration(&mut self, aspiration: f32) {
+        self.glottis.aspiration = aspiration;
+    }
+
     /// Get the frequency of vibrato.
     pub fn get_vibrato_frequency(&self) -> f32 {
         self.glottis.vibrato_frequency
@@ -312,10 +549,25 @@ impl Voice {
         self.glottis.tremolo_amplitude = amplitude;
     }
 
+    /// Set the frequency of tremolo.  If this is 0, the tremolo is synchronized with the vibrato.
+    pub fn set_tremolo_frequency(&mut self, frequency: f32) {
+        self.glottis.tremolo_frequency = frequency;
+    }
+
+    /// Set the factor by which the glottal excitation is oversampled.  The waveguides always run
+    /// at the output rate, since their segment length is tied to it.
+    pub fn set_oversampling(&mut self, factor: usize) {
+        self.glottis.set_oversampling(factor);
+    }
+
     /// Generate the next audio sample.  Arguments are the current sample index, the noise signal
     /// to inject into the vocal tract (to simulate consonants), and the position at which to
     /// inject it.
     pub fn generate(&mut self, step: i64, mut noise: f32, noise_position: usize) -> (f32, f32) {
+        let n = self.scaled_shape.len();
+        let noise_position = if n == self.shape_length {noise_position} else {
+            usize::min(n-1, (noise_position as f32*(n-1) as f32/(self.shape_length-1) as f32).round() as usize)
+        };
         let noise_area = self.vocal.area[noise_position];
         if noise_area > 0.0 {
             if noise_area < 0.2 {
@@ -324,11 +576,31 @@ impl Voice {
             self.vocal.right[noise_position] += noise;
             self.vocal.left[noise_position] += noise;
         }
-        let excitation = self.volume*self.glottis.generate(step);
+        let excitation = self.volume*self.glottis.generate(step) + self.glottis.generate_aspiration();
         let n = self.vocal.right.len();
         let nasal_n = self.nasal.right.len();
-        let damping = 0.995;
+        let damping = self.vocal_damping;
         for _substep in 0..2 {
+            // Propagate waves in the subglottal tract.  Index 0 is at the glottis.  The glottal
+            // flow sends a wave of opposite sign down toward the lungs, where it is mostly absorbed.
+            // The wave that returns to the glottis is added to the excitation of the vocal tract.
+
+            let mut excitation = excitation;
+            if self.subglottal_coupling > 0.0 {
+                let sub_n = self.subglottal.right.len();
+                let sub_right = self.subglottal.right.clone();
+                let sub_left = self.subglottal.left.clone();
+                let sub_right_output = &mut self.subglottal.right;
+                let sub_left_output = &mut self.subglottal.left;
+                sub_right_output[0] = 0.7*sub_left[0] - excitation;
+                for i in 1..sub_n {
+                    sub_right_output[i] = 0.99*sub_right[i-1];
+                    sub_left_output[i-1] = 0.99*sub_left[i];
+                }
+                sub_left_output[sub_n-1] = -0.5*sub_right[sub_n-1];
+                excitation += self.subglottal_coupling*sub_left[0];
+            }
+
             // Propagate waves in the vocal tract.
 
             let right = self.vocal.right.clone();
@@ -342,13 +614,13 @@ impl Voice {
                 right_output[i] = damping*(right[i-1] - w);
                 left_output[i-1] = damping*(left[i] + w);
             }
-            if self.nasal_coupling > 0.0 {
+            if self.nasal_coupling > 0.0 || self.nasal.right[nasal_n-1].abs() > 1e-5 {
                 self.nasal_off_after_step = step+500;
             }
             if step < self.nasal_off_after_step {
                 // Propagate waves in the nasal cavity.
 
-                let damping = 0.98;
+                let damping = self.nasal_damping;
                 let nasal_right = self.nasal.right.clone();
                 let nasal_left = self.nasal.left.clone();
                 let nasal_right_output = &mut self.nasal.right;
@@ -0,0 +1,1053 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use chorus::director::{BrightnessMode, Director, DirectorBuilder, LegatoMode, Message};
+use chorus::filter::{BiquadFilter, Filter};
+use chorus::phonemes::Phonemes;
+use chorus::random::Random;
+use chorus::syllable;
+use chorus::VoicePart;
+use std::f32::consts::PI;
+use std::sync::mpsc;
+
+#[test]
+fn test_seed() {
+    // Two Directors with the same seed should produce identical output.  A different seed
+    // should produce different output.
+
+    let mut outputs = vec![];
+    for seed in [1, 1, 2] {
+        let (sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 4).seed(seed).vibrato(0.8).build(receiver);
+        let _ = sender.send(Message::NoteOn {syllable: "sA".to_string(), note_index: 55, velocity: 1.0, continue_syllable: false, vowel_start: None});
+        let mut output = vec![];
+        for _ in 0..10000 {
+            output.push(director.generate());
+        }
+        outputs.push(output);
+    }
+    assert!(outputs[0] == outputs[1]);
+    assert!(outputs[0] != outputs[2]);
+}
+
+#[test]
+fn test_direct_control() {
+    // Controlling the Director with direct method calls should give the same result as sending
+    // messages, except that there is no delay before they take effect.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director1 = DirectorBuilder::new(VoicePart::Alto, 2).seed(5).build(receiver);
+    let (sender, receiver) = mpsc::channel();
+    let mut director2 = DirectorBuilder::new(VoicePart::Alto, 2).seed(5).build(receiver);
+    assert!(director1.note_on("x", 60, 1.0, false).is_err());
+    director1.set_volume(0.7);
+    assert!(director1.note_on("mA", 60, 1.0, false).is_ok());
+    let _ = sender.send(Message::SetVolume {volume: 0.7});
+    let _ = sender.send(Message::NoteOn {syllable: "mA".to_string(), note_index: 60, velocity: 1.0, continue_syllable: false, vowel_start: None});
+    for _ in 0..10000 {
+        assert_eq!(director1.generate(), director2.generate());
+    }
+}
+
+#[test]
+fn test_config() {
+    // Applying the configuration of one Director to another should make their settings identical.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director1 = DirectorBuilder::new(VoicePart::Soprano, 2).build(receiver);
+    director1.set_brightness(0.3);
+    director1.set_pitch_bend(-1.5);
+    director1.handle_message(Message::SetAccent {accent: true});
+    director1.handle_message(Message::SetGlideTime {samples: 5000});
+    director1.handle_message(Message::SetConsonantGain {consonant: 's', gain: 0.5});
+    let config = director1.get_config();
+    assert_eq!(config.brightness, 0.3);
+    assert!((config.pitch_bend+1.5).abs() < 1e-5);
+    let (_sender, receiver) = mpsc::channel();
+    let mut director2 = DirectorBuilder::new(VoicePart::Soprano, 2).build(receiver);
+    assert!(director2.get_config() != config);
+    director2.apply_config(&config);
+    assert_eq!(director2.get_config(), config);
+
+    // A DirectorBuilder should use the same defaults as Director::new().
+
+    let (_sender, receiver) = mpsc::channel();
+    let director3 = Director::new(VoicePart::Soprano, 2, receiver);
+    let (_sender, receiver) = mpsc::channel();
+    assert_eq!(DirectorBuilder::new(VoicePart::Soprano, 2).build(receiver).get_config(), director3.get_config());
+}
+
+#[test]
+fn test_voice_part_names() {
+    for part in VoicePart::all() {
+        assert_eq!(part.to_string().parse::<VoicePart>(), Ok(part));
+    }
+    assert_eq!("bass".parse::<VoicePart>(), Ok(VoicePart::Bass));
+    assert!("baritone".parse::<VoicePart>().is_err());
+}
+
+#[test]
+fn test_ensemble_limits() {
+    // Out of range ensemble settings are clamped, so they cannot read past the end of the delay
+    // buffer.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Alto, 2).build(receiver);
+    director.handle_message(Message::SetEnsemble {depth: 20.0, voices: 1000});
+    assert_eq!(director.get_config().ensemble_depth, 1.0);
+    assert_eq!(director.get_config().ensemble_voices, chorus::ensemble::MAX_VOICES);
+    director.handle_message(Message::SetEnsemble {depth: -1.0, voices: 3});
+    assert_eq!(director.get_config().ensemble_depth, 0.0);
+    director.handle_message(Message::SetEnsemble {depth: 20.0, voices: 3});
+    assert!(director.note_on("A", 60, 1.0, false).is_ok());
+    for _ in 0..10000 {
+        let (left, right) = director.generate();
+        assert!(left.is_finite() && right.is_finite());
+    }
+}
+
+#[test]
+fn test_current_state() {
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Tenor, 3).build(receiver);
+    assert_eq!(director.current_state().note, None);
+    assert!(director.note_on("sa", 60, 1.0, false).is_ok());
+    let state = director.current_state();
+    assert_eq!(state.note, Some(60));
+    assert_eq!(state.vowel, Some('a'));
+    assert_eq!(state.syllable_index, -1);
+    director.handle_message(Message::SetSyllableIndex {index: 3});
+    assert_eq!(director.current_state().syllable_index, 3);
+    for _ in 0..20000 {
+        director.generate();
+    }
+    assert!(director.current_state().envelope_level > 0.0);
+    director.note_off();
+    assert_eq!(director.current_state().note, None);
+}
+
+#[test]
+fn test_breath() {
+    // A breath should produce sound without any note being played, then fall silent.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Bass, 2).build(receiver);
+    director.breath(1.0);
+    let mut energy = 0.0;
+    for _ in 0..12000 {
+        let (left, right) = director.generate();
+        energy += left*left + right*right;
+    }
+    assert!(energy > 0.0);
+    for _ in 0..20000 {
+        director.generate();
+    }
+    assert_eq!(director.generate(), (0.0, 0.0));
+}
+
+#[test]
+fn test_standalone() {
+    // A standalone Director should produce the same output whether samples are generated one at
+    // a time or in blocks.
+
+    let mut director1 = DirectorBuilder::new(VoicePart::Tenor, 2).seed(3).build_standalone();
+    let mut director2 = DirectorBuilder::new(VoicePart::Tenor, 2).seed(3).build_standalone();
+    assert!(director1.note_on("lA", 50, 1.0, false).is_ok());
+    assert!(director2.note_on("lA", 50, 1.0, false).is_ok());
+    let mut buffer = vec![0.0; 2000];
+    for _ in 0..10 {
+        director2.generate_interleaved(&mut buffer);
+        for frame in buffer.chunks(2) {
+            assert_eq!(director1.generate(), (frame[0], frame[1]));
+        }
+    }
+}
+
+#[test]
+fn test_cold_start_fade_in() {
+    // When starting from silence, the envelope should rise smoothly even with a very fast attack,
+    // such as after a glottal stop.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Alto, 2).max_voice_delay(0).build(receiver);
+    assert!(director.note_on("?a", 60, 1.0, false).is_ok());
+    let mut last_level = 0.0;
+    for _ in 0..10000 {
+        director.generate();
+        let level = director.current_state().envelope_level;
+        assert!((level-last_level).abs() < 0.05);
+        last_level = level;
+    }
+    assert!(last_level > 0.5);
+}
+
+#[test]
+fn test_vowel_start_per_note() {
+    // Overriding the vowel start time for a single note should be the same as setting it
+    // globally for that note.
+
+    let (_sender1, receiver1) = mpsc::channel();
+    let (_sender2, receiver2) = mpsc::channel();
+    let mut director1 = DirectorBuilder::new(VoicePart::Tenor, 1).seed(5).build(receiver1);
+    let mut director2 = DirectorBuilder::new(VoicePart::Tenor, 1).seed(5).build(receiver2);
+    director1.handle_message(Message::SetMinVowelStartTime {samples: 5000});
+    assert!(director1.note_on("sa", 60, 1.0, false).is_ok());
+    assert!(director2.note_on_with_vowel_start("sa", 60, 1.0, false, Some(5000)).is_ok());
+    for _ in 0..10000 {
+        assert_eq!(director1.generate(), director2.generate());
+    }
+
+    // The vowel should start later than with no delay.
+
+    let (_sender3, receiver3) = mpsc::channel();
+    let mut director3 = DirectorBuilder::new(VoicePart::Tenor, 1).seed(5).build(receiver3);
+    director3.handle_message(Message::SetMinVowelStartTime {samples: 5000});
+    assert!(director3.note_on_with_vowel_start("sa", 60, 1.0, false, Some(0)).is_ok());
+    let mut differ = false;
+    for _ in 0..10000 {
+        differ |= director2.generate() != director3.generate();
+    }
+    assert!(differ);
+}
+
+#[test]
+fn test_release_tail() {
+    // With the slowest release rate, the sound should decay to silence, after which the Director
+    // stops computing the voices.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Bass, 2).build(receiver);
+    director.handle_message(Message::SetReleaseRate {release: 0.0});
+    assert!(director.note_on("mA", 48, 1.0, false).is_ok());
+    for _ in 0..20000 {
+        director.generate();
+    }
+    director.note_off();
+    let mut peak = 0.0_f32;
+    for i in 0..48000 {
+        let (left, right) = director.generate();
+        if i > 40000 {
+            peak = peak.max(left.abs()).max(right.abs());
+        }
+    }
+    assert!(peak < 1e-5);
+}
+
+#[test]
+fn test_output_gain() {
+    // Raising the output gain by 6 dB should double the output.
+
+    let (_sender1, receiver1) = mpsc::channel();
+    let (_sender2, receiver2) = mpsc::channel();
+    let mut director1 = DirectorBuilder::new(VoicePart::Soprano, 2).seed(3).build(receiver1);
+    let mut director2 = DirectorBuilder::new(VoicePart::Soprano, 2).seed(3).build(receiver2);
+    director2.handle_message(Message::SetOutputGain {gain_db: 20.0*2.0_f32.log10()});
+    assert_eq!(director2.get_config().output_gain_db, 20.0*2.0_f32.log10());
+    assert!(director1.note_on("A", 65, 1.0, false).is_ok());
+    assert!(director2.note_on("A", 65, 1.0, false).is_ok());
+    for _ in 0..10000 {
+        let (left1, right1) = director1.generate();
+        let (left2, right2) = director2.generate();
+        assert!((2.0*left1-left2).abs() < 1e-5);
+        assert!((2.0*right1-right2).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn test_mono() {
+    // In mono mode, both channels should be identical.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Tenor, 4).build(receiver);
+    director.handle_message(Message::SetMono {enabled: true});
+    assert!(director.note_on("sa", 55, 1.0, false).is_ok());
+    let mut peak = 0.0_f32;
+    for _ in 0..10000 {
+        let (left, right) = director.generate();
+        assert_eq!(left, right);
+        peak = peak.max(left.abs());
+    }
+    assert!(peak > 0.0);
+}
+
+#[test]
+fn test_voice_getters() {
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Alto, 3).build(receiver);
+    assert_eq!(director.voice_part(), VoicePart::Alto);
+    assert_eq!(director.voice_count(), 3);
+    director.handle_message(Message::Reinitialize {voice_part: VoicePart::Bass, voice_count: 5});
+    assert_eq!(director.voice_part(), VoicePart::Bass);
+    assert_eq!(director.voice_count(), 5);
+}
+
+#[test]
+fn test_set_voice_count() {
+    // Changing the number of voices should not interrupt the current note.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Soprano, 2).build(receiver);
+    assert!(director.note_on("lA", 67, 1.0, false).is_ok());
+    for _ in 0..20000 {
+        director.generate();
+    }
+    for count in [5, 3, 6, 1] {
+        director.handle_message(Message::SetVoiceCount {count});
+        assert_eq!(director.voice_count(), count);
+        assert_eq!(director.current_state().note, Some(67));
+        let mut peak = 0.0_f32;
+        for _ in 0..5000 {
+            let (left, right) = director.generate();
+            assert!(left.is_finite() && right.is_finite());
+            peak = peak.max(left.abs());
+        }
+        assert!(peak > 0.01);
+    }
+
+    // It should still be possible to sing new notes afterward.
+
+    assert!(director.note_on("mi", 69, 1.0, false).is_ok());
+    for _ in 0..10000 {
+        director.generate();
+    }
+    assert_eq!(director.current_state().note, Some(69));
+}
+
+#[test]
+fn test_set_voice_count_from_zero() {
+    // Adding voices when there are none has no existing voice to copy, so the voices should be
+    // created from scratch.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Tenor, 2).build(receiver);
+    director.handle_message(Message::Reinitialize {voice_part: VoicePart::Tenor, voice_count: 0});
+    assert_eq!(director.voice_count(), 0);
+    director.handle_message(Message::SetVoiceCount {count: 3});
+    assert_eq!(director.voice_count(), 3);
+    assert_eq!(director.voice_part(), VoicePart::Tenor);
+    assert!(director.note_on("lA", 60, 1.0, false).is_ok());
+    let mut peak = 0.0_f32;
+    for _ in 0..20000 {
+        let (left, right) = director.generate();
+        assert!(left.is_finite() && right.is_finite());
+        peak = peak.max(left.abs());
+    }
+    assert!(peak > 0.01);
+}
+
+#[test]
+fn test_vibrato_intensity_coupling() {
+    // The coupling has no effect at the default intensity, but changes the sound at others.
+
+    let run = |intensity: f32, coupling: f32| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Alto, 1).seed(7).build(receiver);
+        director.set_intensity(intensity);
+        director.handle_message(Message::SetVibratoIntensityCoupling {amount: coupling});
+        assert!(director.note_on("A", 62, 1.0, false).is_ok());
+        (0..30000).map(|_| director.generate().0).collect::<Vec<f32>>()
+    };
+    assert_eq!(run(0.5, 0.0), run(0.5, 1.0));
+    assert!(run(1.0, 0.0) != run(1.0, 1.0));
+}
+
+#[test]
+fn test_release_shape() {
+    // Early in the release, an exponential curve should have decayed the most and a raised
+    // cosine the least.
+
+    let level_during_release = |curve: f32| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 2).max_voice_delay(0).build(receiver);
+        director.handle_message(Message::SetReleaseShape {curve});
+        assert!(director.note_on("A", 55, 1.0, false).is_ok());
+        for _ in 0..30000 {
+            director.generate();
+        }
+        director.note_off();
+        for _ in 0..1001 {
+            director.generate();
+        }
+        director.current_state().envelope_level
+    };
+    let cosine = level_during_release(0.0);
+    let linear = level_during_release(-1.0);
+    let exponential = level_during_release(1.0);
+    assert!(exponential < linear);
+    assert!(linear < cosine);
+}
+
+#[test]
+fn test_consonant_gain() {
+    // Setting the gain of a consonant to zero should silence it without affecting others.
+
+    let render = |syllable: &str, gain: f32| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 2).seed(11).build(receiver);
+        director.handle_message(Message::SetConsonantGain {consonant: 's', gain});
+        assert!(director.note_on(syllable, 55, 1.0, false).is_ok());
+        (0..4000).map(|_| director.generate().0).collect::<Vec<f32>>()
+    };
+    assert!(render("sa", 0.0).iter().all(|&x| x.abs() < 1e-3));
+    assert!(render("sa", 1.0).iter().any(|&x| x.abs() > 1e-3));
+    assert_eq!(render("fa", 0.0), render("fa", 1.0));
+}
+
+#[test]
+fn test_consonant_clusters() {
+    // Every consonant in a long cluster should be heard, in the right order.
+
+    for (word, expected) in [("strENT", "str"), ("Spri", "Spr"), ("pfErt", "pf")] {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 1).max_voice_delay(0).build(receiver);
+        assert!(director.note_on(word, 55, 1.0, false).is_ok());
+        let mut heard = String::new();
+        for _ in 0..30000 {
+            let (left, right) = director.generate();
+            assert!(left.is_finite() && right.is_finite());
+            if let Some(c) = director.current_state().active_consonant {
+                // A rolled r is played as a series of taps.
+
+                if c != '4' && !heard.ends_with(c) {
+                    heard.push(c);
+                }
+            }
+        }
+        assert_eq!(heard, expected, "{word}");
+    }
+}
+
+#[test]
+fn test_rest() {
+    // A rest should end the current note and produce silence.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Alto, 2).build(receiver);
+    assert!(director.note_on("la", 62, 1.0, false).is_ok());
+    for _ in 0..20000 {
+        director.generate();
+    }
+    assert_eq!(director.current_state().note, Some(62));
+    assert!(director.note_on(syllable::REST, 64, 1.0, false).is_ok());
+    assert_eq!(director.current_state().note, None);
+    let mut peak = 0.0_f32;
+    for i in 0..48000 {
+        let (left, right) = director.generate();
+        if i > 40000 {
+            peak = peak.max(left.abs()).max(right.abs());
+        }
+    }
+    assert!(peak < 1e-5);
+}
+
+#[test]
+fn test_note_transitions() {
+    // Drive a Director through a sequence of notes: legato on the same vowel, legato with a vowel
+    // change, and notes separated by consonants.  The output should always be finite, there
+    // should be sound during each note, and it should go silent after the last one ends.
+
+    let (sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Soprano, 4).seed(3).build(receiver);
+    let run = |director: &mut Director, samples: usize| {
+        let mut peak = 0.0_f32;
+        for _ in 0..samples {
+            let (left, right) = director.generate();
+            assert!(left.is_finite() && right.is_finite());
+            peak = peak.max(left.abs()).max(right.abs());
+        }
+        peak
+    };
+    let note_on = |syllable: &str, note_index: i32| Message::NoteOn {syllable: syllable.to_string(), note_index: note_index, velocity: 1.0, continue_syllable: false, vowel_start: None};
+    let _ = sender.send(note_on("lA", 67));
+    assert!(run(&mut director, 20000) > 0.01);
+    let _ = sender.send(note_on("A", 69));
+    assert!(run(&mut director, 20000) > 0.01);
+    assert_eq!(director.current_state().note, Some(69));
+    let _ = sender.send(note_on("i", 71));
+    assert!(run(&mut director, 20000) > 0.01);
+    let _ = sender.send(note_on("tu", 67));
+    assert!(run(&mut director, 20000) > 0.01);
+    let _ = sender.send(Message::NoteOff);
+    run(&mut director, 10000);
+    let _ = sender.send(note_on("sEn", 64));
+    assert!(run(&mut director, 20000) > 0.01);
+    let _ = sender.send(Message::NoteOff);
+    run(&mut director, 40000);
+    assert_eq!(director.current_state().note, None);
+    assert!(run(&mut director, 1000) < 1e-5);
+}
+
+#[test]
+fn test_bounded_output() {
+    // Send random but valid sequences of messages, and check that the output always stays
+    // finite and within a safe range.
+
+    let syllables = ["A", "lA", "mi", "pOl", "strENT", "ku", "Dis", "bEd", "zu", "hi", "_"];
+    for seed in [1, 2, 3] {
+        let mut random = Random::with_seed(seed);
+        let (sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::all()[seed as usize], 4).seed(seed).build(receiver);
+        let mut peak = 0.0_f32;
+        for _ in 0..50 {
+            let value = random.get_uniform();
+            let message = match random.get_int() % 12 {
+                0 | 1 | 2 => {
+                    let syllable = syllables[random.get_int() as usize % syllables.len()].to_string();
+                    let note_index = 40 + (random.get_int() % 45) as i32;
+                    Message::NoteOn {syllable: syllable, note_index: note_index, velocity: value, continue_syllable: false, vowel_start: None}
+                }
+                3 => Message::NoteOff,
+                4 => Message::SetVolume {volume: value},
+                5 => Message::SetIntensity {intensity: value},
+                6 => Message::SetBrightness {brightness: value},
+                7 => Message::SetConsonantVolume {volume: value},
+                8 => Message::SetAccent {accent: value > 0.5},
+                9 => Message::SetVibrato {vibrato: value},
+                10 => Message::SetExciterStrength {strength: value},
+                _ => Message::SetAttackRate {attack: value}
+            };
+            let _ = sender.send(message);
+            for _ in 0..2000+random.get_int()%4000 {
+                let (left, right) = director.generate();
+                assert!(left.is_finite() && right.is_finite());
+                peak = peak.max(left.abs()).max(right.abs());
+            }
+        }
+        assert!(peak < 1.0);
+    }
+}
+
+#[test]
+fn test_attack_shape() {
+    // Early in the attack, an exponential curve should have risen the most and a raised cosine
+    // the least.
+
+    let level_during_attack = |curve: f32| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 2).max_voice_delay(0).build(receiver);
+        director.handle_message(Message::SetAttackRate {attack: 0.0});
+        director.handle_message(Message::SetAttackShape {curve});
+        assert!(director.note_on("A", 55, 1.0, false).is_ok());
+        for _ in 0..1001 {
+            director.generate();
+        }
+        director.current_state().envelope_level
+    };
+    let cosine = level_during_attack(0.0);
+    let linear = level_during_attack(-1.0);
+    let exponential = level_during_attack(1.0);
+    assert!(exponential > linear);
+    assert!(linear > cosine);
+}
+
+#[test]
+fn test_air() {
+    // Boosting the air band should increase the high frequency content of the output.  Setting
+    // it back to 0 dB should leave the output unfiltered.
+
+    let high_frequency_energy = |gain_db: f32| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Soprano, 2).seed(3).build(receiver);
+        director.handle_message(Message::SetAir {gain_db});
+        assert_eq!(director.get_config().air_gain_db, gain_db);
+        assert!(director.note_on("sA", 65, 1.0, false).is_ok());
+        let mut energy = 0.0;
+        let mut previous = 0.0;
+        for _ in 0..20000 {
+            let (left, _right) = director.generate();
+            energy += (left-previous)*(left-previous);
+            previous = left;
+        }
+        energy
+    };
+    assert!(high_frequency_energy(12.0) > 1.2*high_frequency_energy(0.0));
+    assert!(high_frequency_energy(-12.0) < high_frequency_energy(0.0));
+}
+
+#[test]
+fn test_brightness_mode() {
+    // In SpectralTilt mode, reducing the brightness should remove high frequencies without
+    // changing the vocal tract shape.  At full brightness, the two modes should sound the same.
+
+    let high_frequency_energy = |mode: BrightnessMode, brightness: f32| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Alto, 2).seed(3).brightness(brightness).build(receiver);
+        director.handle_message(Message::SetBrightnessMode {mode});
+        assert_eq!(director.get_config().brightness_mode, mode);
+        assert!(director.note_on("i", 65, 1.0, false).is_ok());
+        let mut energy = 0.0;
+        let mut previous = 0.0;
+        for _ in 0..20000 {
+            let (left, _right) = director.generate();
+            energy += (left-previous)*(left-previous);
+            previous = left;
+        }
+        energy
+    };
+    let bright = high_frequency_energy(BrightnessMode::ShapeBlend, 1.0);
+    assert!((high_frequency_energy(BrightnessMode::SpectralTilt, 1.0)-bright).abs() < 1e-3*bright);
+    assert!(high_frequency_energy(BrightnessMode::SpectralTilt, 0.0) < 0.5*bright);
+}
+
+#[test]
+fn test_timbre_targets() {
+    // At zero brightness, the vocal tract shape is blended 20% of the way toward the dark vowel.
+    // Changing the dark vowel from o to u should therefore move the shape by about 0.2*(u-o).
+    // The voice's tract may have a slightly different length, so the vowel shapes are
+    // interpolated to match it.  Invalid targets should be ignored.
+
+    let run = |dark: char| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Bass, 2).seed(3).brightness(0.0).build(receiver);
+        director.handle_message(Message::SetTimbreTargets {dark: dark, high: 'A'});
+        assert!(director.note_on("E", 48, 1.0, false).is_ok());
+        for _ in 0..10000 {
+            director.generate();
+        }
+        (director.get_config().dark_vowel, director.get_vocal_shape().unwrap().0.clone())
+    };
+    let (default_vowel, default_shape) = run('o');
+    let (changed_vowel, changed_shape) = run('u');
+    let (invalid_vowel, invalid_shape) = run('#');
+    assert_eq!(default_vowel, 'o');
+    assert_eq!(changed_vowel, 'u');
+    assert_eq!(invalid_vowel, 'o');
+    assert_eq!(default_shape, invalid_shape);
+    let phonemes = Phonemes::new(VoicePart::Bass);
+    let n = default_shape.len();
+    let interpolate = |shape: &Vec<f32>, i: usize| {
+        let x = i as f32*(shape.len()-1) as f32/(n-1) as f32;
+        let j = (x as usize).min(shape.len()-2);
+        let f = x-j as f32;
+        (1.0-f)*shape[j] + f*shape[j+1]
+    };
+    let o = phonemes.get_vowel_shape('o').unwrap();
+    let u = phonemes.get_vowel_shape('u').unwrap();
+    let mut dot = 0.0;
+    let mut norm = 0.0;
+    for i in 0..n {
+        let difference = interpolate(u, i)-interpolate(o, i);
+        dot += (changed_shape[i]-default_shape[i])*difference;
+        norm += difference*difference;
+    }
+    let blend = dot/norm;
+    assert!((blend-0.2).abs() < 0.01);
+}
+
+#[test]
+fn test_tremolo() {
+    // Tremolo should modulate the amplitude at its own rate, by an amount proportional to
+    // tremolo_amount.  With a rate of 0 it follows the vibrato instead.  Measure the RMS amplitude
+    // in 10 ms windows over two seconds, and compare the strength of the modulation at different
+    // frequencies.
+
+    let envelope = |rate_hz: f32, amount: f32| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Alto, 1).seed(3).build(receiver);
+        director.handle_message(Message::SetTremolo {rate_hz, amount});
+        assert_eq!(director.get_config().tremolo_rate, rate_hz);
+        assert_eq!(director.get_config().tremolo_amount, amount);
+        assert!(director.note_on("A", 60, 1.0, false).is_ok());
+        for _ in 0..20000 {
+            director.generate();
+        }
+        let mut envelope = vec![];
+        for _ in 0..200 {
+            let mut sum = 0.0;
+            for _ in 0..480 {
+                let (left, _right) = director.generate();
+                sum += left*left;
+            }
+            envelope.push((sum/480.0).sqrt());
+        }
+        envelope
+    };
+    let modulation = |envelope: &[f32], frequency: f32| {
+        let mean = envelope.iter().sum::<f32>()/envelope.len() as f32;
+        let mut re = 0.0;
+        let mut im = 0.0;
+        for (i, x) in envelope.iter().enumerate() {
+            let phase = 2.0*PI*frequency*i as f32/100.0;
+            re += (x-mean)*phase.cos();
+            im += (x-mean)*phase.sin();
+        }
+        (re*re + im*im).sqrt()/(mean*envelope.len() as f32)
+    };
+    let none = envelope(2.0, 0.0);
+    let slow = envelope(2.0, 1.0);
+    let deep = envelope(2.0, 2.0);
+    let fast = envelope(3.5, 1.0);
+    let synchronized = envelope(0.0, 1.0);
+    assert!(modulation(&slow, 2.0) > 5.0*modulation(&none, 2.0));
+    assert!((modulation(&deep, 2.0)/modulation(&slow, 2.0)-2.0).abs() < 0.2);
+    assert!(modulation(&fast, 3.5) > 5.0*modulation(&none, 3.5));
+    assert!(modulation(&fast, 2.0) < 0.2*modulation(&slow, 2.0));
+    assert!(modulation(&synchronized, 2.0) < 0.2*modulation(&slow, 2.0));
+}
+
+#[test]
+fn test_tract_pressure() {
+    // The pressure should have one value for each segment of the vocal tract.  It should be zero
+    // before anything is sung, and nonzero while singing.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Tenor, 2).build(receiver);
+    let mut pressure = vec![];
+    director.get_tract_pressure(&mut pressure);
+    assert_eq!(pressure.len(), director.get_vocal_shape().unwrap().0.len());
+    assert!(pressure.iter().all(|&p| p == 0.0));
+    assert!(director.note_on("A", 55, 1.0, false).is_ok());
+    for _ in 0..10000 {
+        director.generate();
+    }
+    director.get_tract_pressure(&mut pressure);
+    assert_eq!(pressure.len(), director.get_vocal_shape().unwrap().0.len());
+    assert!(pressure.iter().all(|p| p.is_finite()));
+    assert!(pressure.iter().any(|&p| p != 0.0));
+}
+
+#[test]
+fn test_oversampling() {
+    // Oversampling should remove aliasing, which shows up as energy above the cutoff of the
+    // decimation filters.  It should leave the overall level almost unchanged.
+
+    let run = |factor: usize| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Soprano, 2).seed(3).build(receiver);
+        director.handle_message(Message::SetOversampling {factor});
+        assert!(director.note_on("A", 81, 1.0, false).is_ok());
+        let mut highpass1 = BiquadFilter::highpass(21000.0, 0.707);
+        let mut highpass2 = BiquadFilter::highpass(21000.0, 0.707);
+        let mut total_energy = 0.0;
+        let mut high_energy = 0.0;
+        for _ in 0..20000 {
+            let x = director.generate().0;
+            assert!(x.is_finite());
+            let high = highpass2.process(highpass1.process(x));
+            total_energy += x*x;
+            high_energy += high*high;
+        }
+        (director.get_config().oversampling, total_energy, high_energy)
+    };
+    let (factor1, total1, high1) = run(1);
+    let (factor2, total2, high2) = run(2);
+    let (factor4, total4, high4) = run(4);
+    assert_eq!(factor1, 1);
+    assert_eq!(factor2, 2);
+    assert_eq!(factor4, 4);
+    assert_eq!(run(0).0, 1);
+    assert_eq!(run(10).0, 4);
+    assert!((total2-total1).abs() < 0.01*total1);
+    assert!((total4-total1).abs() < 0.01*total1);
+    assert!(high2 < 0.5*high1);
+    assert!(high4 < 0.5*high1);
+}
+
+#[test]
+fn test_legato_mode() {
+    // Measure the minimum envelope level and whether a consonant is heard while moving between
+    // two notes.
+
+    let measure = |syllable: &str, mode: LegatoMode, continue_syllable: bool| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Alto, 2).build(receiver);
+        director.handle_message(Message::SetLegatoMode {mode: mode});
+        assert!(director.note_on(syllable, 62, 1.0, false).is_ok());
+        for _ in 0..20000 {
+            director.generate();
+        }
+        assert!(director.note_on(syllable, 64, 1.0, continue_syllable).is_ok());
+        let mut min_envelope = 1.0_f32;
+        let mut consonant = false;
+        for _ in 0..20000 {
+            director.generate();
+            let state = director.current_state();
+            min_envelope = min_envelope.min(state.envelope_level);
+            consonant |= state.active_consonant.is_some();
+        }
+        assert_eq!(director.current_state().note, Some(64));
+        (min_envelope, consonant)
+    };
+
+    // In Auto mode, the consonant is sung unless the syllable is continued.
+
+    let (_, auto_consonant) = measure("tA", LegatoMode::Auto, false);
+    assert!(auto_consonant);
+    let (_, continued_consonant) = measure("tA", LegatoMode::Auto, true);
+    assert!(!continued_consonant);
+
+    // AlwaysLegato continues the syllable even when not asked to.
+
+    let (legato_envelope, legato_consonant) = measure("tA", LegatoMode::AlwaysLegato, false);
+    assert!(!legato_consonant);
+    assert!(legato_envelope > 0.5);
+
+    // With no consonants, Auto blends the notes, while AlwaysRetrigger releases the first note
+    // before attacking the second, even when asked to continue the syllable.
+
+    let (auto_envelope, _) = measure("A", LegatoMode::Auto, false);
+    assert!(auto_envelope > 0.5);
+    let (retrigger_envelope, _) = measure("A", LegatoMode::AlwaysRetrigger, true);
+    assert!(retrigger_envelope < 0.5*auto_envelope);
+    let (_, retrigger_consonant) = measure("tA", LegatoMode::AlwaysRetrigger, true);
+    assert!(retrigger_consonant);
+}
+
+#[test]
+fn test_voicing() {
+    // Setting the default voicing should not change the output.  Increasing Rd makes the voice
+    // breathier, which reduces the high frequency content relative to the total energy.  Extreme
+    // values should still produce finite output.
+
+    let tilt = |output: &[(f32, f32)]| {
+        let mut energy = 0.0;
+        let mut high_frequency_energy = 0.0;
+        let mut previous = 0.0;
+        for &(left, _right) in output {
+            energy += left*left;
+            high_frequency_energy += (left-previous)*(left-previous);
+            previous = left;
+        }
+        high_frequency_energy/energy
+    };
+    let run = |voicing: Option<(f32, f32, f32, f32)>| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Alto, 2).seed(3).build(receiver);
+        if let Some((rd_base, rd_pitch_scale, rd_volume_scale, rd_intensity_scale)) = voicing {
+            director.handle_message(Message::SetVoicing {rd_base, rd_pitch_scale, rd_volume_scale, rd_intensity_scale});
+            let config = director.get_config();
+            assert_eq!((config.rd_base, config.rd_pitch_scale, config.rd_volume_scale, config.rd_intensity_scale), voicing.unwrap());
+        }
+        assert!(director.note_on("A", 60, 1.0, false).is_ok());
+        let mut output = vec![];
+        for _ in 0..20000 {
+            output.push(director.generate());
+        }
+        assert!(output.iter().all(|(left, right)| left.is_finite() && right.is_finite()));
+        output
+    };
+    let default_output = run(None);
+    assert!(default_output == run(Some((1.5, 0.5, 0.2, 1.0))));
+    let default_tilt = tilt(&default_output);
+
+    // Raising rd_base, or reducing how much the volume lowers Rd, should make the sound darker.
+
+    assert!(tilt(&run(Some((0.8, 0.5, 0.2, 1.0)))) > 1.5*default_tilt);
+    assert!(tilt(&run(Some((2.5, 0.5, 0.2, 1.0)))) < 0.6*default_tilt);
+    assert!(tilt(&run(Some((1.5, 0.5, 0.0, 1.0)))) < 0.9*default_tilt);
+    assert!(tilt(&run(Some((1.5, 0.5, 0.6, 1.0)))) > 1.2*default_tilt);
+
+    // The note is below the top of the range, so raising rd_pitch_scale should also raise Rd.
+
+    assert!(tilt(&run(Some((1.5, 2.0, 0.2, 1.0)))) < 0.6*default_tilt);
+    run(Some((100.0, 0.0, 0.0, 0.0)));
+    run(Some((-100.0, 0.0, 0.0, 0.0)));
+}
+
+#[test]
+fn test_vowel_amplification() {
+    // Doubling the amplification of a vowel should double its envelope.  The change should
+    // be reported in the configuration and survive reinitializing the voices.
+
+    let run = |amplification: Option<f32>| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Alto, 2).seed(3).build(receiver);
+        assert!(director.get_config().vowel_amplification.is_empty());
+        if let Some(amplification) = amplification {
+            director.handle_message(Message::SetVowelAmplification {vowel: 'i', amplification});
+            director.handle_message(Message::Reinitialize {voice_part: VoicePart::Alto, voice_count: 2});
+            assert_eq!(director.get_config().vowel_amplification[&'i'], amplification);
+        }
+        assert!(director.note_on("i", 55, 1.0, false).is_ok());
+        for _ in 0..30000 {
+            director.generate();
+        }
+        director.current_state().envelope_level
+    };
+    let default_level = run(None);
+    let doubled_level = run(Some(3.0));
+    assert!((doubled_level-2.0*default_level).abs() < 1e-4);
+
+    // Characters that are not vowels are ignored.
+
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Alto, 2).build(receiver);
+    director.handle_message(Message::SetVowelAmplification {vowel: 's', amplification: 2.0});
+    assert!(!director.get_config().vowel_amplification.contains_key(&'s'));
+
+    // Only amplifications that differ from the defaults are stored, and applying a configuration
+    // restores the defaults for the others.
+
+    let mut config = director.get_config();
+    director.handle_message(Message::SetVowelAmplification {vowel: 'i', amplification: 2.0});
+    director.handle_message(Message::SetVowelAmplification {vowel: 'a', amplification: 2.0});
+    director.handle_message(Message::SetVowelAmplification {vowel: 'a', amplification: 1.0});
+    assert_eq!(director.get_config().vowel_amplification.len(), 1);
+    director.apply_config(&config);
+    assert!(director.get_config().vowel_amplification.is_empty());
+    config.vowel_amplification.insert('u', 3.0);
+    director.apply_config(&config);
+    assert_eq!(director.get_config(), config);
+}
+
+#[test]
+fn test_custom_vowel() {
+    // A custom shape for a vowel should change how it sounds.  Shapes with the wrong length, and
+    // symbols that are not vowels, should be rejected.
+
+    let length = Phonemes::new(VoicePart::Tenor).get_vocal_length();
+    let run = |shape: Option<Vec<f32>>| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 1).seed(3).build(receiver);
+        if let Some(shape) = shape {
+            assert!(director.set_custom_vowel('a', shape, 0.0).is_ok());
+        }
+        assert!(director.note_on("a", 55, 1.0, false).is_ok());
+        (0..20000).map(|_| director.generate().0).collect::<Vec<f32>>()
+    };
+    assert!(run(None) != run(Some(vec![2.0; length])));
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Tenor, 1).build(receiver);
+    assert!(director.set_custom_vowel('a', vec![2.0; length+1], 0.0).is_err());
+    assert!(director.set_custom_vowel('q', vec![2.0; length], 0.0).is_err());
+    assert!(director.set_custom_vowel('u', vec![2.0; length], 0.1).is_ok());
+}
+
+#[test]
+fn test_nasality() {
+    // The baseline nasality should be added to the coupling of every vowel, but never push it
+    // above the coupling used for nasal consonants.
+
+    let coupling = |nasality: f32, syllable: &str| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 2).build(receiver);
+        director.handle_message(Message::SetNasality {coupling: nasality});
+        assert!(director.note_on(syllable, 55, 1.0, false).is_ok());
+        for _ in 0..20000 {
+            director.generate();
+        }
+        director.get_vocal_shape().unwrap().1
+    };
+    assert_eq!(coupling(0.0, "A"), 0.0);
+    assert!((coupling(0.2, "A")-0.2).abs() < 1e-6);
+    assert!((coupling(1.0, "A")-0.5).abs() < 1e-6);
+    assert!((coupling(0.2, "m")-0.5).abs() < 1e-6);
+    let (_sender, receiver) = mpsc::channel();
+    let mut director = DirectorBuilder::new(VoicePart::Tenor, 2).build(receiver);
+    director.handle_message(Message::SetNasality {coupling: 0.3});
+    assert_eq!(director.get_config().nasality, 0.3);
+}
+
+#[test]
+fn test_geminate() {
+    // Count how many times a consonant is started while singing two syllables legato, and how
+    // long it is active.
+
+    let measure = |first: &str, second: &str, consonant: char| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 1).build(receiver);
+        assert!(director.note_on(first, 55, 1.0, false).is_ok());
+        for _ in 0..20000 {
+            director.generate();
+        }
+        assert!(director.note_on(second, 57, 1.0, false).is_ok());
+        let mut count = 0;
+        let mut duration = 0;
+        let mut active = false;
+        for _ in 0..20000 {
+            director.generate();
+            let is_active = director.current_state().active_consonant == Some(consonant);
+            if is_active && !active {
+                count += 1;
+            }
+            if is_active {
+                duration += 1;
+            }
+            active = is_active;
+        }
+        (count, duration)
+    };
+
+    // The t in fAt tA is sung once, and held longer than a single t.
+
+    let (single_count, single_duration) = measure("fA", "tA", 't');
+    let (geminate_count, geminate_duration) = measure("fAt", "tA", 't');
+    assert_eq!(single_count, 1);
+    assert_eq!(geminate_count, 1);
+    assert!(geminate_duration > single_duration);
+
+    // A double r is a single trill, with the same number of taps as a single r.
+
+    let (single_count, _) = measure("te", "rA", 'r');
+    let (geminate_count, _) = measure("ter", "rA", 'r');
+    assert!(single_count > 0);
+    assert_eq!(geminate_count, single_count);
+
+    // The sonorants l, m, and n are treated as vowels.  Measure how long the vocal tract shape
+    // takes to settle on the vowel of the second note.
+
+    let settle_time = |first: &str, second: &str| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 1).build(receiver);
+        assert!(director.note_on(first, 55, 1.0, false).is_ok());
+        for _ in 0..20000 {
+            director.generate();
+        }
+        assert!(director.note_on(second, 57, 1.0, false).is_ok());
+        let mut shapes = vec![];
+        for _ in 0..20000 {
+            director.generate();
+            shapes.push(director.get_vocal_shape().unwrap().0.clone());
+        }
+        let last = shapes.last().unwrap().clone();
+        shapes.iter().rposition(|shape| shape.iter().zip(&last).any(|(a, b)| (a-b).abs() > 0.01)).unwrap()
+    };
+
+    // A geminate sonorant is held longer than a single one, but is only sung once.  If it were
+    // sung at the end of the first note and again at the start of the second, it would add the
+    // full length of a final sonorant.
+
+    for (single, geminate) in [(("be", "lo"), ("bel", "lo")), (("mA", "mA"), ("mAm", "mA")), (("A", "no"), ("An", "no"))] {
+        let single_time = settle_time(single.0, single.1);
+        let geminate_time = settle_time(geminate.0, geminate.1);
+        assert!(geminate_time > single_time);
+        assert!(geminate_time < single_time+3000);
+    }
+}
+
+#[test]
+#[ignore]
+fn test_consonant_queue_timing() {
+    // Consonants that are queued to start far in the future should not make generate() much
+    // slower.  This compares the time to generate samples while one note is waiting against the
+    // time while thousands are waiting.  Run it with "cargo test --release -- --ignored".
+
+    let time = |notes: i64| {
+        let (_sender, receiver) = mpsc::channel();
+        let mut director = DirectorBuilder::new(VoicePart::Tenor, 4).seed(5).build(receiver);
+        for i in 0..notes {
+            assert!(director.note_on_with_vowel_start("strA", 55, 1.0, false, Some(1000000+100*i)).is_ok());
+        }
+        let start = std::time::Instant::now();
+        for _ in 0..20000 {
+            director.generate();
+        }
+        start.elapsed().as_secs_f64()
+    };
+    let few = time(1);
+    let many = time(2000);
+    println!("1 note: {few:.3} s, 2000 notes: {many:.3} s");
+    assert!(many < 3.0*few);
+}
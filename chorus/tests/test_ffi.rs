@@ -0,0 +1,36 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(feature = "ffi")]
+
+use chorus::ffi::*;
+
+#[test]
+fn test_ffi() {
+    assert!(director_new(4, 2).is_null());
+    let director = director_new(1, 2);
+    assert!(!director.is_null());
+    unsafe {
+        assert_eq!(director_send_message(director, MESSAGE_SET_VOLUME, 0.8), 0);
+        assert_eq!(director_send_message(director, 1000, 0.0), -1);
+        assert_eq!(director_note_on(director, c"x".as_ptr(), 60, 1.0, false), -1);
+        assert_eq!(director_note_on(director, c"mA".as_ptr(), 60, 1.0, false), 0);
+        let mut buffer = vec![0.0; 2*10000];
+        director_generate(director, buffer.as_mut_ptr(), 10000);
+        assert!(buffer.iter().any(|&x| x != 0.0));
+        director_note_off(director);
+        director_free(director);
+    }
+}
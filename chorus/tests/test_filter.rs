@@ -13,54 +13,148 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
-use chorus::filter::{Filter, LowpassFilter, HighpassFilter, BandpassFilter, ResonantFilter};
+use chorus::filter::{Filter, LowpassFilter, HighpassFilter, BandpassFilter, ResonantFilter, BiquadFilter};
 use chorus::SAMPLE_RATE;
 use std::f32::consts::PI;
 
-fn compute_response_amplitude(filter: &mut impl Filter, frequency: f32) -> f32 {
+fn response(filter: &impl Filter, frequency: f32) -> f32 {
+    filter.magnitude_response(frequency, SAMPLE_RATE as f32)
+}
+
+/// Measure the response by filtering a sine wave and computing the RMS amplitude of the output.
+/// This is much slower than computing it analytically, so it is only used to check the
+/// analytical response.
+fn compute_response_amplitude(filter: &mut dyn Filter, frequency: f32) -> f32 {
     let f = 2.0*PI*frequency/SAMPLE_RATE as f32;
-    let mut max_amplitude = 0.0;
-    for i in 0..(2*SAMPLE_RATE) {
+    let mut sum_squares = 0.0;
+    let mut count = 0;
+    for i in 0..(SAMPLE_RATE/4) {
         let x = (f*i as f32).sin();
         let y = filter.process(x);
-        if i > 1000 {
-            max_amplitude = f32::max(max_amplitude, y.abs());
+        if i >= 2000 {
+            sum_squares += y*y;
+            count += 1;
         }
     }
-    max_amplitude
+    (2.0*sum_squares/count as f32).sqrt()
 }
 
 #[test]
 fn test_lowpass() {
-    let mut filter = LowpassFilter::new(2000.0);
-    assert!(compute_response_amplitude(&mut filter, 1000.0) > 0.5);
-    assert!(compute_response_amplitude(&mut filter, 4000.0) < 0.5);
+    let filter = LowpassFilter::new(2000.0);
+    assert!(response(&filter, 1000.0) > 0.5);
+    assert!(response(&filter, 4000.0) < 0.5);
 }
 
 #[test]
 fn test_highpass() {
-    let mut filter = HighpassFilter::new(2000.0);
-    assert!(compute_response_amplitude(&mut filter, 1000.0) < 0.5);
-    assert!(compute_response_amplitude(&mut filter, 4000.0) > 0.5);
+    let filter = HighpassFilter::new(2000.0);
+    assert!(response(&filter, 1000.0) < 0.5);
+    assert!(response(&filter, 4000.0) > 0.5);
 }
 
 #[test]
 fn test_bandpass() {
-    let mut filter = BandpassFilter::new(2000.0, 3000.0);
-    let y1 = compute_response_amplitude(&mut filter, 500.0);
-    let y2 = compute_response_amplitude(&mut filter, 2500.0);
-    let y3 = compute_response_amplitude(&mut filter, 4000.0);
+    let filter = BandpassFilter::new(2000.0, 3000.0);
+    let y1 = response(&filter, 500.0);
+    let y2 = response(&filter, 2500.0);
+    let y3 = response(&filter, 4000.0);
     assert!(y2 > y1);
     assert!(y2 > y3);
 }
 
 #[test]
 fn test_resonant() {
-    let mut filter = ResonantFilter::new(2000.0, 1000.0);
-    let y1 = compute_response_amplitude(&mut filter, 500.0);
-    let y2 = compute_response_amplitude(&mut filter, 2000.0);
-    let y3 = compute_response_amplitude(&mut filter, 4000.0);
+    let filter = ResonantFilter::new(2000.0, 1000.0);
+    let y1 = response(&filter, 500.0);
+    let y2 = response(&filter, 2000.0);
+    let y3 = response(&filter, 4000.0);
     assert!(y2 > y1);
     assert!(y2 > y3);
     assert!(y2 > 1.0);
 }
+
+#[test]
+fn test_biquad_lowpass() {
+    let filter = BiquadFilter::lowpass(2000.0, 0.707);
+    assert!(response(&filter, 500.0) > 0.9);
+    assert!(response(&filter, 8000.0) < 0.1);
+}
+
+#[test]
+fn test_biquad_highpass() {
+    let filter = BiquadFilter::highpass(2000.0, 0.707);
+    assert!(response(&filter, 250.0) < 0.1);
+    assert!(response(&filter, 8000.0) > 0.9);
+}
+
+#[test]
+fn test_biquad_peaking() {
+    let filter = BiquadFilter::peaking(2000.0, 2.0, 6.0);
+    let y1 = response(&filter, 200.0);
+    let y2 = response(&filter, 2000.0);
+    let y3 = response(&filter, 15000.0);
+    assert!((y1-1.0).abs() < 0.1);
+    assert!((y2-2.0).abs() < 0.1);
+    assert!((y3-1.0).abs() < 0.1);
+}
+
+#[test]
+fn test_biquad_shelf() {
+    let low = BiquadFilter::low_shelf(1000.0, 0.707, -6.0);
+    assert!((response(&low, 100.0)-0.5).abs() < 0.05);
+    assert!((response(&low, 10000.0)-1.0).abs() < 0.05);
+    let high = BiquadFilter::high_shelf(1000.0, 0.707, 6.0);
+    assert!((response(&high, 100.0)-1.0).abs() < 0.05);
+    assert!((response(&high, 10000.0)-2.0).abs() < 0.1);
+}
+
+#[test]
+fn test_copy_coefficients() {
+    // After copying the coefficients, a filter should have the same response as the one they
+    // were copied from.
+
+    let mut filter = BiquadFilter::high_shelf(1000.0, 0.707, 0.0);
+    let other = BiquadFilter::high_shelf(1000.0, 0.707, 6.0);
+    filter.process(1.0);
+    filter.copy_coefficients(&other);
+    for frequency in [100.0, 1000.0, 10000.0] {
+        assert_eq!(filter.magnitude_response(frequency, SAMPLE_RATE as f32), other.magnitude_response(frequency, SAMPLE_RATE as f32));
+    }
+}
+
+#[test]
+fn test_reset() {
+    // After a reset, a filter should produce exactly the same output as a newly created one.
+
+    let mut filters: Vec<Box<dyn Filter>> = vec![Box::new(LowpassFilter::new(2000.0)), Box::new(HighpassFilter::new(2000.0)),
+        Box::new(BandpassFilter::new(2000.0, 3000.0)), Box::new(ResonantFilter::new(2000.0, 1000.0)), Box::new(BiquadFilter::peaking(2000.0, 2.0, 6.0))];
+    let mut fresh: Vec<Box<dyn Filter>> = vec![Box::new(LowpassFilter::new(2000.0)), Box::new(HighpassFilter::new(2000.0)),
+        Box::new(BandpassFilter::new(2000.0, 3000.0)), Box::new(ResonantFilter::new(2000.0, 1000.0)), Box::new(BiquadFilter::peaking(2000.0, 2.0, 6.0))];
+    for (filter, fresh) in filters.iter_mut().zip(fresh.iter_mut()) {
+        for i in 0..1000 {
+            filter.process((0.1*i as f32).sin());
+        }
+        filter.reset();
+        for i in 0..100 {
+            let x = (0.3*i as f32).sin();
+            assert_eq!(fresh.process(x), filter.process(x));
+        }
+    }
+}
+
+#[test]
+fn test_magnitude_response() {
+    // The analytical response should match what we measure by filtering a sine wave.
+
+    let mut filters: Vec<Box<dyn Filter>> = vec![Box::new(LowpassFilter::new(2000.0)), Box::new(HighpassFilter::new(2000.0)),
+        Box::new(BandpassFilter::new(2000.0, 3000.0)), Box::new(ResonantFilter::new(2000.0, 1000.0)),
+        Box::new(BiquadFilter::lowpass(2000.0, 0.707)), Box::new(BiquadFilter::peaking(2000.0, 2.0, 6.0))];
+    for filter in filters.iter_mut() {
+        for frequency in [500.0, 2000.0, 4000.0] {
+            let expected = filter.magnitude_response(frequency, SAMPLE_RATE as f32);
+            let measured = compute_response_amplitude(filter.as_mut(), frequency);
+            assert!((expected-measured).abs() < 0.02*expected.max(1.0));
+        }
+    }
+}
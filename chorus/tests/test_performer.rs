@@ -0,0 +1,215 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use chorus::director::{DirectorBuilder, Message};
+use chorus::performer::{phrase_syllables, Event, NotePriority, Performer, SyllableGrouping, MAX_PHRASE_LENGTH};
+use chorus::VoicePart;
+use std::sync::mpsc;
+
+/// Collect the syllables and notes of all NoteOn messages that have been sent, and count the
+/// NoteOffs.
+fn received(receiver: &mpsc::Receiver<Message>) -> (Vec<(String, i32, bool)>, usize) {
+    let mut notes = vec![];
+    let mut note_offs = 0;
+    while let Ok(message) = receiver.try_recv() {
+        match message {
+            Message::NoteOn {syllable, note_index, continue_syllable, ..} => notes.push((syllable, note_index, continue_syllable)),
+            Message::NoteOff => note_offs += 1,
+            _ => {}
+        }
+    }
+    (notes, note_offs)
+}
+
+#[test]
+fn test_phrase_syllables() {
+    let syllables: Vec<&str> = phrase_syllables(" glo.4i  A\tde.o ").collect();
+    assert_eq!(syllables, vec!["glo", "4i", "A", "de", "o"]);
+}
+
+#[test]
+fn test_advance_syllable() {
+    let (sender, receiver) = mpsc::channel();
+    let mut performer = Performer::new(sender);
+    performer.set_phrase("lA dA");
+    performer.transpose = 2;
+    for note in [60, 62, 64] {
+        performer.handle_event(Event::NoteOn {note, velocity: 1.0});
+        performer.flush();
+        performer.handle_event(Event::NoteOff {note, velocity: 0.5});
+        performer.flush();
+    }
+    let (notes, note_offs) = received(&receiver);
+    assert_eq!(notes, vec![("lA".to_string(), 62, false), ("dA".to_string(), 64, false), ("lA".to_string(), 66, false)]);
+    assert_eq!(note_offs, 3);
+    assert_eq!(performer.syllable_index(), 0);
+
+    // Restarting the phrase goes back to the first syllable.  Without advancing, every note
+    // continues it.
+
+    performer.restart_phrase();
+    performer.advance_syllable = false;
+    performer.handle_event(Event::NoteOn {note: 60, velocity: 1.0});
+    performer.handle_event(Event::NoteOn {note: 62, velocity: 1.0});
+    let (notes, _) = received(&receiver);
+    assert_eq!(notes, vec![("lA".to_string(), 62, true), ("lA".to_string(), 64, true)]);
+}
+
+#[test]
+fn test_held_notes() {
+    let (sender, receiver) = mpsc::channel();
+    let mut performer = Performer::new(sender);
+    performer.set_phrase("lA dA");
+    performer.note_priority = NotePriority::Highest;
+
+    // A lower note does not interrupt the highest one.
+
+    performer.handle_event(Event::NoteOn {note: 64, velocity: 1.0});
+    performer.handle_event(Event::NoteOn {note: 60, velocity: 1.0});
+    let (notes, _) = received(&receiver);
+    assert_eq!(notes, vec![("lA".to_string(), 64, false)]);
+
+    // Releasing it returns to the held note, continuing the same syllable.
+
+    performer.handle_event(Event::NoteOff {note: 64, velocity: 0.5});
+    performer.flush();
+    let (notes, note_offs) = received(&receiver);
+    assert_eq!(notes, vec![("lA".to_string(), 60, true)]);
+    assert_eq!(note_offs, 0);
+}
+
+#[test]
+fn test_note_off() {
+    let (sender, receiver) = mpsc::channel();
+    let mut performer = Performer::new(sender);
+    performer.set_phrase("A");
+
+    // A NoteOff followed by a NoteOn at the same time is played legato.
+
+    performer.handle_event(Event::NoteOn {note: 60, velocity: 1.0});
+    performer.handle_event(Event::NoteOff {note: 60, velocity: 0.5});
+    performer.handle_event(Event::NoteOn {note: 62, velocity: 1.0});
+    performer.flush();
+    assert_eq!(received(&receiver).1, 0);
+
+    // The sustain pedal defers the NoteOff until it is released.
+
+    performer.handle_event(Event::Sustain {pressed: true});
+    performer.handle_event(Event::NoteOff {note: 62, velocity: 0.5});
+    performer.flush();
+    assert_eq!(received(&receiver).1, 0);
+    performer.handle_event(Event::Sustain {pressed: false});
+    performer.flush();
+    assert_eq!(received(&receiver).1, 1);
+}
+
+#[test]
+fn test_release_velocity() {
+    // The release velocity adjusts the release rate for one note.  A velocity of 0, which is
+    // sent by hosts that don't measure it, leaves the release rate unchanged.
+
+    let release_rate = |velocity: f32| {
+        let (sender, receiver) = mpsc::channel();
+        let mut performer = Performer::new(sender);
+        performer.set_phrase("A");
+        performer.set_release_rate(0.3);
+        performer.handle_event(Event::NoteOn {note: 60, velocity: 1.0});
+        performer.handle_event(Event::NoteOff {note: 60, velocity});
+        performer.flush();
+        receiver.try_iter().filter_map(|message| match message {
+            Message::SetReleaseRate {release} => Some(release),
+            _ => None
+        }).last().unwrap()
+    };
+    assert_eq!(release_rate(0.5), 0.3);
+    assert_eq!(release_rate(0.0), 0.3);
+    assert!(release_rate(0.9) > 0.3);
+    assert!(release_rate(0.1) < 0.3);
+}
+
+#[test]
+fn test_from_midi() {
+    assert_eq!(Event::from_midi(&[0x91, 60, 127]), Some(Event::NoteOn {note: 60, velocity: 1.0}));
+    assert_eq!(Event::from_midi(&[0x90, 60, 0]), Some(Event::NoteOff {note: 60, velocity: 0.5}));
+    assert_eq!(Event::from_midi(&[0x80, 60, 0]), Some(Event::NoteOff {note: 60, velocity: 0.0}));
+    assert_eq!(Event::from_midi(&[0xB0, 64, 127]), Some(Event::Sustain {pressed: true}));
+    assert_eq!(Event::from_midi(&[0xE0, 0, 64]), Some(Event::PitchBend {value: 0.5}));
+    assert_eq!(Event::from_midi(&[0xB0, 7, 127]), None);
+    assert_eq!(Event::from_midi(&[]), None);
+}
+
+#[test]
+fn test_word_grouping() {
+    let (sender, receiver) = mpsc::channel();
+    let mut performer = Performer::new(sender);
+    performer.set_phrase("glo.4i.A in ek.sEl.sis");
+    performer.grouping = SyllableGrouping::Word;
+
+    // Each note sings all the syllables of the next word.
+
+    performer.handle_event(Event::NoteOn {note: 60, velocity: 1.0});
+    let (notes, _) = received(&receiver);
+    assert_eq!(notes, vec![("glo".to_string(), 60, false), ("4i".to_string(), 60, false), ("A".to_string(), 60, false)]);
+    assert_eq!(performer.syllable_index(), 2);
+    performer.handle_event(Event::NoteOn {note: 62, velocity: 1.0});
+    let (notes, _) = received(&receiver);
+    assert_eq!(notes, vec![("in".to_string(), 62, false)]);
+
+    // Returning to a held note continues the last syllable of the word.
+
+    performer.handle_event(Event::NoteOff {note: 62, velocity: 0.5});
+    let (notes, _) = received(&receiver);
+    assert_eq!(notes, vec![("in".to_string(), 60, true)]);
+
+    // Switching back to one syllable per note continues from the next word.
+
+    performer.grouping = SyllableGrouping::Syllable;
+    performer.handle_event(Event::NoteOn {note: 64, velocity: 1.0});
+    let (notes, _) = received(&receiver);
+    assert_eq!(notes, vec![("ek".to_string(), 64, false)]);
+}
+
+#[test]
+fn test_long_phrase() {
+    // A phrase longer than MAX_PHRASE_LENGTH should be truncated at a character boundary.  Here
+    // the last syllable straddles the limit, so only the one before it remains.
+
+    let (sender, receiver) = mpsc::channel();
+    let mut performer = Performer::new(sender);
+    performer.set_phrase(&format!("{}lA ɛ", " ".repeat(MAX_PHRASE_LENGTH-4)));
+    performer.handle_event(Event::NoteOn {note: 60, velocity: 1.0});
+    performer.handle_event(Event::NoteOn {note: 62, velocity: 1.0});
+    let (notes, _) = received(&receiver);
+    assert_eq!(notes, vec![("lA".to_string(), 60, false), ("lA".to_string(), 62, false)]);
+}
+
+#[test]
+fn test_director_syllable_index() {
+    // A Director driven by a Performer should report which syllable of the phrase it is singing.
+
+    let (sender, receiver) = mpsc::channel();
+    let mut performer = Performer::new(sender);
+    let mut director = DirectorBuilder::new(VoicePart::Tenor, 1).build(receiver);
+    assert_eq!(director.current_state().syllable_index, -1);
+    performer.set_phrase("lA dA mi");
+    for (note, index) in [(60, 0), (62, 1), (64, 2), (65, 0)] {
+        performer.handle_event(Event::NoteOn {note, velocity: 1.0});
+        performer.flush();
+        for _ in 0..1000 {
+            director.generate();
+        }
+        assert_eq!(director.current_state().syllable_index, index);
+    }
+}
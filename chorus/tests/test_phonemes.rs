@@ -0,0 +1,122 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use chorus::phonemes::Phonemes;
+use chorus::VoicePart;
+
+#[test]
+fn test_load_consonant_samples() {
+    let mut phonemes = Phonemes::new(VoicePart::Alto);
+    assert_eq!(phonemes.get_consonant('s', None, false, 1.0).unwrap().samples.len(), 0);
+    assert!(phonemes.load_consonant_samples('s', vec![vec![0, 16384, -16384], vec![100; 10]]).is_ok());
+    for is_final in [false, true] {
+        let consonant = phonemes.get_consonant('s', None, is_final, 1.0).unwrap();
+        assert_eq!(consonant.samples.len(), 2);
+        assert_eq!(consonant.samples[0], vec![0.0, 0.5, -0.5]);
+    }
+    assert!(phonemes.load_consonant_samples('Q', vec![vec![0; 10]]).is_err());
+    assert!(phonemes.load_consonant_samples('t', vec![]).is_err());
+}
+
+#[test]
+fn test_consonant_info() {
+    let phonemes = Phonemes::new(VoicePart::Tenor);
+    for &c in Phonemes::consonants() {
+        let info = phonemes.get_consonant_info(c, false).unwrap();
+        assert_eq!(info.voiced, phonemes.is_voiced_consonant(c));
+        assert!(info.position < phonemes.get_vocal_length());
+    }
+    assert!(phonemes.get_consonant_info('z', false).unwrap().voiced);
+    assert!(!phonemes.get_consonant_info('s', true).unwrap().voiced);
+    assert!(phonemes.get_consonant_info('t', false).unwrap().sampled);
+    assert!(phonemes.get_consonant_info('Q', false).is_none());
+}
+
+#[test]
+fn test_intermediate_shape() {
+    let phonemes = Phonemes::new(VoicePart::Soprano);
+    for &v1 in Phonemes::vowels() {
+        for &v2 in Phonemes::vowels() {
+            if let Some(shape) = phonemes.get_intermediate_shape(v1, v2) {
+                assert_eq!(shape.len(), phonemes.get_vocal_length());
+                assert!(shape.iter().all(|&x| x >= 0.0));
+            }
+        }
+    }
+    let a = phonemes.get_vowel_shape('a').unwrap();
+    let i = phonemes.get_vowel_shape('i').unwrap();
+    let shape = phonemes.get_intermediate_shape('a', 'i').unwrap();
+    assert!(shape.iter().zip(a.iter().zip(i)).any(|(&x, (&y, &z))| (x-0.5*(y+z)).abs() > 0.01));
+    assert!(phonemes.get_intermediate_shape('a', 'a').is_none());
+    assert!(phonemes.get_intermediate_shape('a', 'l').is_none());
+    assert!(phonemes.get_intermediate_shape('a', 'm').is_some());
+}
+
+#[test]
+fn test_set_intermediate_shape() {
+    let mut phonemes = Phonemes::new(VoicePart::Bass);
+    let default = phonemes.get_intermediate_shape('a', 'i').unwrap();
+    let shape = vec![1.0; phonemes.get_vocal_length()];
+    assert!(phonemes.set_intermediate_shape('a', 'i', shape.clone()).is_ok());
+    assert_eq!(phonemes.get_intermediate_shape('a', 'i').unwrap(), shape);
+    assert!(phonemes.get_intermediate_shape('i', 'a').unwrap() != shape);
+    assert!(phonemes.set_intermediate_shape('a', 'i', vec![1.0; 3]).is_err());
+    phonemes.clear_intermediate_shape('a', 'i');
+    assert_eq!(phonemes.get_intermediate_shape('a', 'i').unwrap(), default);
+}
+
+#[test]
+fn test_set_vowel_shape() {
+    let mut phonemes = Phonemes::new(VoicePart::Alto);
+    let shape = vec![1.0; phonemes.get_vocal_length()];
+    assert!(phonemes.set_vowel_shape('a', shape.clone()).is_ok());
+    assert_eq!(phonemes.get_vowel_shape('a').unwrap(), &shape);
+
+    // The shape must have the right length, and only vowels that can be sung can be replaced.
+
+    assert!(phonemes.set_vowel_shape('i', vec![1.0; 3]).is_err());
+    assert!(phonemes.get_vowel_shape('i').unwrap() != &vec![1.0; 3]);
+    assert!(phonemes.set_vowel_shape('q', shape.clone()).is_err());
+    assert!(phonemes.get_vowel_shape('q').is_none());
+}
+
+#[test]
+fn test_set_amplification() {
+    let mut phonemes = Phonemes::new(VoicePart::Soprano);
+    assert_eq!(phonemes.get_amplification('a'), 1.0);
+    assert_eq!(phonemes.get_amplification('i'), 1.5);
+    phonemes.set_amplification('i', 2.0);
+    assert_eq!(phonemes.get_amplification('i'), 2.0);
+    assert_eq!(phonemes.get_amplification('a'), 1.0);
+    phonemes.set_amplification('i', -1.0);
+    assert_eq!(phonemes.get_amplification('i'), 0.0);
+}
+
+#[test]
+fn test_nasal_vowels() {
+    // The French nasal vowels should have partial nasal coupling, while their oral counterparts
+    // have none.  Their shapes should also differ from the oral vowels.
+
+    for part in VoicePart::all() {
+        let phonemes = Phonemes::new(part);
+        for (nasal, oral) in [('ɑ', 'A'), ('ɛ', 'E'), ('ɔ', 'O'), ('œ', '9')] {
+            assert_eq!(phonemes.get_nasal_coupling(oral), 0.0);
+            let coupling = phonemes.get_nasal_coupling(nasal);
+            assert!(coupling > 0.0 && coupling < phonemes.get_nasal_coupling('m'));
+            assert_eq!(phonemes.get_vowel_shape(nasal).unwrap().len(), phonemes.get_vocal_length());
+            assert_ne!(phonemes.get_vowel_shape(nasal), phonemes.get_vowel_shape(oral));
+        }
+    }
+}
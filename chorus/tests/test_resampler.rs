@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
-use chorus::resampler::Resampler;
+use chorus::resampler::{Resampler, MAX_FILTER_LENGTH};
 use chorus::SAMPLE_RATE;
 use std::f32::consts::PI;
 
@@ -52,3 +52,33 @@ fn test_downsample() {
 fn test_upsample() {
     test_for_output_rate(96000);
 }
+
+/// Resample a sine wave and return the RMS amplitude of the output, skipping the start while the
+/// filter fills up.
+fn resample_sine(output_rate: f32, filter_length: usize, frequency: f32) -> f32 {
+    let mut resampler = Resampler::with_filter_length(output_rate, filter_length);
+    let mut output = Vec::new();
+    for i in 0..SAMPLE_RATE {
+        while resampler.has_output() {
+            output.push(resampler.get_output());
+        }
+        resampler.add_input((2.0*PI*frequency*i as f32/SAMPLE_RATE as f32).sin());
+    }
+    let output = &output[1000..];
+    (output.iter().map(|x| x*x).sum::<f32>()/output.len() as f32).sqrt()
+}
+
+#[test]
+fn test_filter_length() {
+    // A longer filter should add latency, pass low frequencies unchanged, and remove frequencies
+    // above the output's Nyquist frequency much better than linear interpolation.
+
+    assert_eq!(Resampler::new(44100.0).latency(), 0);
+    assert_eq!(Resampler::with_filter_length(44100.0, 32).latency(), 15);
+    assert_eq!(Resampler::with_filter_length(44100.0, 1000).latency(), MAX_FILTER_LENGTH/2-1);
+    let amplitude = resample_sine(44100.0, 32, 1000.0);
+    assert!((amplitude-0.5_f32.sqrt()).abs() < 0.01);
+    let linear = resample_sine(22050.0, 2, 15000.0);
+    let sinc = resample_sine(22050.0, 32, 15000.0);
+    assert!(sinc < 0.1*linear);
+}
@@ -14,6 +14,8 @@
 // If not, see <https://www.gnu.org/licenses/>.
 
 use chorus::syllable::Syllable;
+use chorus::phonemes::Phonemes;
+use chorus::VoicePart;
 
 #[test]
 fn should_fail() {
@@ -42,3 +44,82 @@ fn should_pass() {
         assert_eq!(expected_main_vowel[i], syllable.main_vowel);
     }
 }
+
+#[test]
+fn trill() {
+    assert_eq!(vec!['r', 'r', '4'], Syllable::build("rA").unwrap().initial_consonants);
+    assert_eq!(vec!['4'], Syllable::build_with_trill("rA", 0).unwrap().initial_consonants);
+    assert_eq!(vec!['r', 'r', 'r', 'r', '4'], Syllable::build_with_trill("Ar", 4).unwrap().final_consonants);
+    assert_eq!(vec!['4'], Syllable::build_with_trill("A4", 4).unwrap().final_consonants);
+}
+
+#[test]
+fn glottal_stop() {
+    let syllable = Syllable::build("?Ai").unwrap();
+    assert_eq!(vec!['?'], syllable.initial_consonants);
+    assert_eq!('A', syllable.main_vowel);
+    let syllable = Syllable::build("A?").unwrap();
+    assert_eq!(vec!['?'], syllable.final_consonants);
+}
+
+#[test]
+fn glide() {
+    let syllable = Syllable::build("de>Im").unwrap();
+    assert_eq!('e', syllable.main_vowel);
+    assert_eq!(Some('I'), syllable.glide);
+    assert_eq!('I', syllable.sustained_vowel());
+    assert_eq!(vec!['m'], syllable.final_vowels);
+    let syllable = Syllable::build("Ai>u").unwrap();
+    assert_eq!(vec!['A'], syllable.initial_vowels);
+    assert_eq!('i', syllable.main_vowel);
+    assert_eq!(None, Syllable::build("Ai").unwrap().glide);
+    for spec in [">a", "a>", "a>d", "a->I", "ad>I", "a>I>u"] {
+        assert!(Syllable::build(spec).is_err(), "{spec} should have failed to parse");
+    }
+}
+
+#[test]
+fn length_markers() {
+    let syllable = Syllable::build("ta:i:d").unwrap();
+    assert_eq!('a', syllable.main_vowel);
+    assert!(syllable.long_main_vowel);
+    assert_eq!(vec![true], syllable.long_final_vowels);
+    let syllable = Syllable::build("bAl=").unwrap();
+    assert_eq!(vec!['A'], syllable.initial_vowels);
+    assert_eq!(vec![false], syllable.long_initial_vowels);
+    assert_eq!('l', syllable.main_vowel);
+    assert!(!syllable.long_main_vowel);
+    let syllable = Syllable::build("sn=:").unwrap();
+    assert_eq!('n', syllable.main_vowel);
+    assert!(syllable.long_main_vowel);
+    for spec in [":a", "a::", "ka-:", "a=", "ad:", "m=A-", "a>I:"] {
+        assert!(Syllable::build(spec).is_err(), "{spec} should have failed to parse");
+    }
+}
+
+#[test]
+fn phoneme_catalog() {
+    let phonemes = Phonemes::new(VoicePart::Alto);
+    for &v in Phonemes::vowels() {
+        let syllable = Syllable::build(&v.to_string()).unwrap();
+        assert_eq!(v, syllable.main_vowel);
+        assert!(phonemes.get_vowel_shape(v).is_some());
+    }
+    for &c in Phonemes::consonants() {
+        assert!(Syllable::build(&format!("{c}a{c}")).is_ok());
+        assert!(phonemes.get_consonant(c, Some('a'), false, 1.0).is_some());
+    }
+}
+
+#[test]
+fn nasal_vowels() {
+    for (spec, vowel) in [("A~", 'ɑ'), ("vE~", 'ɛ'), ("bO~", 'ɔ'), ("br9~", 'œ')] {
+        let syllable = Syllable::build(spec).unwrap();
+        assert_eq!(syllable.main_vowel, vowel);
+    }
+    let syllable = Syllable::build("sA~:s").unwrap();
+    assert_eq!(syllable.main_vowel, 'ɑ');
+    assert!(syllable.long_main_vowel);
+    assert_eq!(syllable.final_consonants, vec!['s']);
+    assert!(Syllable::build("~").is_err());
+}
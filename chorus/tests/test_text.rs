@@ -0,0 +1,56 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use chorus::syllable::Syllable;
+use chorus::text::italian_to_sampa;
+
+#[test]
+fn test_italian_words() {
+    let words = [
+        ("Gloria", "glo 4i A"),
+        ("bello", "bel lo"),
+        ("cielo", "tSe lo"),
+        ("chiesa", "kje sA"),
+        ("figlio", "fi ljo"),
+        ("gnocchi", "njok ki"),
+        ("acqua", "Ak kwA"),
+        ("terra", "ter rA"),
+        ("Roma", "ro mA"),
+        ("cuore", "kwo 4e"),
+        ("Maria", "mA 4i A"),
+        ("altro", "Al t4o"),
+        ("scena", "Se nA"),
+        ("mai", "mA-i"),
+        ("pietà", "pje tA"),
+        ("però", "pe 4O")
+    ];
+    for (word, expected) in words {
+        assert_eq!(italian_to_sampa(word), expected, "{}", word);
+    }
+}
+
+#[test]
+fn test_italian_phrase() {
+    // Punctuation is ignored, and an apostrophe joins two words.
+
+    let phrase = italian_to_sampa("Ave Maria, piena di grazia; l'anima mia.");
+    assert_eq!(phrase, "A ve mA 4i A pje nA di g4A tsi A lA ni mA mi A");
+
+    // Every syllable should be valid X-SAMPA.
+
+    for syllable in phrase.split(' ') {
+        assert!(Syllable::build(syllable).is_ok(), "{}", syllable);
+    }
+}
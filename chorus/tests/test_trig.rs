@@ -0,0 +1,26 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use chorus::trig::{sin_cycles, cos_cycles};
+use std::f32::consts::PI;
+
+#[test]
+fn test_lookup_accuracy() {
+    for i in -1000..1000 {
+        let x = 0.00371*i as f32;
+        assert!((sin_cycles(x)-(2.0*PI*x).sin()).abs() < 1e-5);
+        assert!((cos_cycles(x)-(2.0*PI*x).cos()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use chorus::tuning::{build_tuning_table, parse_keyboard_mapping, parse_scale};
+
+#[test]
+fn test_equal_temperament() {
+    let scl = "! 12tet.scl\n!\n12 tone equal temperament\n 12\n!\n 100.0\n 200.\n 300.0\n 400.0\n 500.0\n 600.0\n 700.0\n 800.0\n 900.0\n 1000.0\n 1100.0\n 2/1\n";
+    let scale = parse_scale(scl).unwrap();
+    assert_eq!(12, scale.len());
+    let table = build_tuning_table(&scale, None).unwrap();
+    assert_eq!(128, table.len());
+    for i in 0..128 {
+        assert!((table[i] - 100.0*(i as f32-69.0)).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_just_intonation() {
+    // A just major scale mapped to the white keys, with C4 at 261.63 Hz.
+
+    let scl = "Just major\n7\n9/8\n5/4\n4/3\n3/2\n5/3\n15/8\n2/1\n";
+    let kbm = "! Map white keys\n12\n0\n127\n60\n60\n261.63\n7\n0\nx\n1\nx\n2\n3\nx\n4\nx\n5\nx\n6\n";
+    let scale = parse_scale(scl).unwrap();
+    let mapping = parse_keyboard_mapping(kbm).unwrap();
+    let table = build_tuning_table(&scale, Some(&mapping)).unwrap();
+    let frequency = |note: usize| 440.0*f32::powf(2.0, table[note]/1200.0);
+    assert!((frequency(60) - 261.63).abs() < 0.01);
+    assert!((frequency(64) - 261.63*1.25).abs() < 0.01);
+    assert!((frequency(67) - 261.63*1.5).abs() < 0.01);
+    assert!((frequency(72) - 261.63*2.0).abs() < 0.01);
+    assert!((frequency(55) - 261.63*0.75).abs() < 0.01);
+
+    // Unmapped keys fall back to equal temperament.
+
+    assert_eq!(100.0*(61.0-69.0), table[61]);
+}
+
+#[test]
+fn test_invalid_scale() {
+    assert!(parse_scale("Missing notes\n3\n100.0\n").is_err());
+    assert!(parse_scale("Bad ratio\n1\n3/0\n").is_err());
+    assert!(parse_scale("Bad count\nx\n").is_err());
+    assert!(parse_scale("Negative count\n-3\n100.0\n200.0\n2/1\n").is_err());
+    assert!(parse_scale("No notes\n0\n").is_err());
+    assert!(parse_scale("").is_err());
+    assert!(build_tuning_table(&[], None).is_err());
+}
+
+#[test]
+fn test_invalid_keyboard_mapping() {
+    let kbm = |size: &str, octave: &str, key: &str| format!("{size}\n0\n127\n60\n69\n440.0\n{octave}\n{key}\n");
+    assert!(parse_keyboard_mapping(&kbm("1", "1", "0")).is_ok());
+    assert!(parse_keyboard_mapping(&kbm("-1", "1", "0")).is_err());
+    assert!(parse_keyboard_mapping(&kbm("129", "1", "0")).is_err());
+    assert!(parse_keyboard_mapping(&kbm("1", "-1", "0")).is_err());
+    assert!(parse_keyboard_mapping(&kbm("1", "1", "-2")).is_err());
+    assert!(parse_keyboard_mapping(&kbm("1", "1", "y")).is_err());
+    assert!(parse_keyboard_mapping("12\n0\n127\n").is_err());
+    assert!(parse_keyboard_mapping(&kbm("1", "1", "0").replace("440.0", "A4")).is_err());
+
+    // An empty mapping maps keys linearly to scale degrees.
+
+    let mapping = parse_keyboard_mapping(&kbm("0", "1", "")).unwrap();
+    assert!(mapping.mapping.is_empty());
+    let table = build_tuning_table(&[100.0], Some(&mapping)).unwrap();
+    assert!((table[70] - 100.0).abs() < 1e-3);
+}
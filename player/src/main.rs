@@ -13,19 +13,33 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
+mod midi;
+#[cfg(feature = "osc")]
+mod osc;
+
 use chorus::director::{Director, Message};
+use chorus::performer::{Event, Performer};
 use chorus::VoicePart;
 use chorus::phonemes::Phonemes;
 use chorus::SAMPLE_RATE;
 
 use rodio::{OutputStream, Source};
-use midir::MidiInput;
+use midi::{connect_midi, default_midi_port, midi_port_names, option_value};
+use midir::MidiInputConnection;
 use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
 
 use eframe::egui::{self, CentralPanel};
 use eframe::{App, NativeOptions};
 
+/// The address and UDP port to listen on for OSC messages, unless others are given on the command
+/// line with --osc-address and --osc-port.  The loopback address only accepts messages from
+/// programs on the same computer.
+#[cfg(feature = "osc")]
+const OSC_ADDRESS: &str = "127.0.0.1";
+#[cfg(feature = "osc")]
+const OSC_PORT: u16 = 9000;
+
 struct Player {
     director: Director,
     next_output: f32,
@@ -68,44 +82,34 @@ impl Source for Player {
 struct MidiController {
     pub sender: mpsc::Sender<Message>,
     pub phrase: String,
-    syllables: Vec<String>,
-    next_syllable: usize,
-    last_note: u8,
+    performer: Performer
 }
 
 impl MidiController {
     fn new(sender: mpsc::Sender<Message>, phrase: &str) -> Self {
+        let mut performer = Performer::new(sender.clone());
+        performer.set_phrase(phrase);
         Self {
             sender: sender,
             phrase: phrase.to_string(),
-            syllables : phrase.split_whitespace().map(str::to_string).collect(),
-            next_syllable: 0,
-            last_note: 255
+            performer: performer
         }
     }
 }
 
 fn process_midi_message(_timestamp: u64, message: &[u8], data: &mut Arc<Mutex<MidiController>>) {
-    let mut controller = data.lock().unwrap();
-    if message[0] == 144 && controller.syllables.len() > 0 {
-        let _ = controller.sender.send(Message::NoteOn {syllable: controller.syllables[controller.next_syllable].clone(), note_index: message[1] as i32, velocity: message[2] as f32 / 127.0, continue_syllable: false});
-        controller.next_syllable = (controller.next_syllable+1)%controller.syllables.len();
-        controller.last_note = message[1];
-    }
-    else if message[0] == 128 && controller.last_note == message[1] {
-        let _ = controller.sender.send(Message::NoteOff);
-    }
-    else if message[0] == 176 && message[1] == 1 {
-        let _ = controller.sender.send(Message::SetVolume {volume: message[2] as f32 / 127.0});
-    }
-    else if message[0] == 224 {
-        let value = message[2] as f32 + message[1] as f32 / 128.0;
-        let _ = controller.sender.send(Message::SetPitchBend {semitones: (value-64.0)/32.0});
+    if let Some(event) = Event::from_midi(message) {
+        let mut controller = data.lock().unwrap();
+        controller.performer.handle_event(event);
+        controller.performer.flush();
     }
 }
 
 struct MainGui {
     controller_ref: Arc<Mutex<MidiController>>,
+    midi_ports: Vec<String>,
+    midi_port: usize,
+    _midi_connection: Option<MidiInputConnection<Arc<Mutex<MidiController>>>>,
     voice_part: VoicePart,
     voice_count: usize,
     vowel_delay: i64,
@@ -124,14 +128,24 @@ struct MainGui {
 
 impl App for MainGui {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let old_port = self.midi_port;
         let mut controller = self.controller_ref.lock().unwrap();
         CentralPanel::default().show(ctx, |ui| {
+            let selected = self.midi_ports.get(self.midi_port).cloned().unwrap_or("None".to_string());
+            egui::ComboBox::from_label("MIDI Input")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (i, name) in self.midi_ports.iter().enumerate() {
+                        ui.selectable_value(&mut self.midi_port, i, name);
+                    }
+                });
             ui.horizontal(|ui| {
                 ui.label("Phrase");
                 let response = ui.text_edit_singleline(&mut controller.phrase);
                 if response.changed() {
-                    controller.syllables = controller.phrase.split_whitespace().map(str::to_string).collect();
-                    controller.next_syllable = 0;
+                    let phrase = controller.phrase.clone();
+                    controller.performer.set_phrase(&phrase);
+                    controller.performer.restart_phrase();
                     if controller.phrase.len() > 0 {
                         let phonemes = Phonemes::new(self.voice_part);
                         let cons = controller.phrase.chars().next().unwrap();
@@ -142,7 +156,7 @@ impl App for MainGui {
                             self.consonant_off_time = c.off_time;
                             self.consonant_volume2 = c.volume;
                             self.consonant_position = c.position;
-                            let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, position: self.consonant_position, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
+                            let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
                         }
                     }
                 }
@@ -177,22 +191,20 @@ impl App for MainGui {
                 let _ = controller.sender.send(Message::SetDelays {vowel_delay: self.vowel_delay, vowel_transition_time: self.vowel_transition_time, consonant_delay: self.consonant_delay, consonant_transition_time: self.consonant_transition_time});
             }
             if ui.add(egui::Slider::new(&mut self.consonant_on_time, 0..=4000).text("Consonant On Time")).dragged() {
-                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, position: self.consonant_position, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
+                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
             }
             if ui.add(egui::Slider::new(&mut self.consonant_off_time, 0..=4000).text("Consonant Off Time")).dragged() {
-                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, position: self.consonant_position, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
+                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
             }
             if ui.add(egui::Slider::new(&mut self.consonant_volume2, 0.0..=0.03).text("Consonant Volume")).dragged() {
-                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, position: self.consonant_position, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
-            }
-            if ui.add(egui::Slider::new(&mut self.consonant_position, 0..=46).text("Consonant Position")).dragged() {
-                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, position: self.consonant_position, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
+                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
             }
+            ui.label(format!("Consonant Position: {}", self.consonant_position));
             if ui.add(egui::Slider::new(&mut self.consonant_frequency, 100.0..=5000.0).text("Consonant Frequency")).dragged() {
-                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, position: self.consonant_position, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
+                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
             }
             if ui.add(egui::Slider::new(&mut self.consonant_bandwidth, 100.0..=6000.0).text("Consonant Bandwidth")).dragged() {
-                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, position: self.consonant_position, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
+                let _ = controller.sender.send(Message::SetConsonants {on_time: self.consonant_on_time, off_time: self.consonant_off_time, volume: self.consonant_volume2, frequency: self.consonant_frequency, bandwidth: self.consonant_bandwidth});
             }
             if ui.add(egui::Slider::new(&mut self.brightness, 0.0..=1.0).text("Brightness")).dragged() {
                 let _ = controller.sender.send(Message::SetBrightness {brightness: self.brightness});
@@ -201,6 +213,15 @@ impl App for MainGui {
                 let _ = controller.sender.send(Message::SetRandomize {randomize: self.consonant_volume});
             }
         });
+        drop(controller);
+        if self.midi_port != old_port {
+            // Close the old connection before opening the new one, since some systems only
+            // allow a port to be opened once.  This must not be done while holding the lock
+            // on the controller, since the MIDI callback may be waiting for it.
+
+            self._midi_connection = None;
+            self._midi_connection = connect_midi(self.midi_port, process_midi_message, Arc::clone(&self.controller_ref));
+        }
     }
 }
 
@@ -210,19 +231,29 @@ fn main() -> Result<(), eframe::Error> {
     let (_stream, handle) = OutputStream::try_default().unwrap();
     let _result = handle.play_raw(player.convert_samples());
 
-    let midi_in = MidiInput::new("Chorus").unwrap();
-    let in_ports = midi_in.ports();
-    let in_port = &in_ports[1];
+    let midi_ports = midi_port_names();
+    let args: Vec<String> = std::env::args().collect();
+    let midi_port = default_midi_port(&midi_ports, option_value(&args, "--midi-port"));
     let controller = Arc::new(Mutex::new(MidiController::new(sender.clone(), "A")));
-    let _conn_in = midi_in.connect(
-        in_port,
-        "midir-read-input",
-        process_midi_message,
-        Arc::clone(&controller),
-    ).unwrap();
+    let midi_connection = connect_midi(midi_port, process_midi_message, Arc::clone(&controller));
+    #[cfg(feature = "osc")]
+    let _osc_listener = {
+        let address = option_value(&args, "--osc-address").unwrap_or(OSC_ADDRESS);
+        let port = option_value(&args, "--osc-port").and_then(|port| port.parse().ok()).unwrap_or(OSC_PORT);
+        match osc::start_listener(address, port, sender.clone()) {
+            Ok(listener) => Some(listener),
+            Err(error) => {
+                eprintln!("Cannot listen for OSC messages on {}:{}: {}", address, port, error);
+                None
+            }
+        }
+    };
     let options = NativeOptions::default();
     let gui = MainGui {
         controller_ref: Arc::clone(&controller),
+        midi_ports: midi_ports,
+        midi_port: midi_port,
+        _midi_connection: midi_connection,
         voice_part: VoicePart::Alto,
         voice_count: 4,
         vowel_delay: 0,
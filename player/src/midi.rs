@@ -0,0 +1,54 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+// Helpers for choosing and connecting to a MIDI input port.  This file is shared by the player
+// and the vowel designer, so both select ports the same way.
+
+use midir::{MidiInput, MidiInputConnection};
+
+/// Get the names of all available MIDI input ports.
+pub fn midi_port_names() -> Vec<String> {
+    let midi_in = MidiInput::new("Chorus").unwrap();
+    midi_in.ports().iter().map(|port| midi_in.port_name(port).unwrap_or_default()).collect()
+}
+
+/// Connect to a MIDI input port, identified by its index in the list of available ports.  The
+/// callback is invoked with the data for every message received.
+pub fn connect_midi<T: Send>(port_index: usize, callback: fn(u64, &[u8], &mut T), data: T) -> Option<MidiInputConnection<T>> {
+    let midi_in = MidiInput::new("Chorus").ok()?;
+    let ports = midi_in.ports();
+    let port = ports.get(port_index)?;
+    midi_in.connect(port, "midir-read-input", callback, data).ok()
+}
+
+/// Get the value following an option on the command line, such as --midi-port.
+pub fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == name).and_then(|i| args.get(i+1)).map(String::as_str)
+}
+
+/// Select which MIDI input port to use.  If a name or index was given on the command line with
+/// --midi-port, use the first port that matches it.  Otherwise use the first port that is not
+/// a software loopback port, since those rarely have anything connected to them.
+pub fn default_midi_port(ports: &[String], requested: Option<&str>) -> usize {
+    if let Some(requested) = requested {
+        if let Ok(index) = requested.parse::<usize>() {
+            return index.min(ports.len().saturating_sub(1));
+        }
+        if let Some(index) = ports.iter().position(|name| name.to_lowercase().contains(&requested.to_lowercase())) {
+            return index;
+        }
+    }
+    ports.iter().position(|name| !name.to_lowercase().contains("through")).unwrap_or(0)
+}
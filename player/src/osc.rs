@@ -0,0 +1,265 @@
+// Copyright 2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+// An Open Sound Control interface to the Director.  Packets are received over UDP, and each
+// message is translated to the corresponding Message.  The following addresses are supported.
+//
+//     /note_on syllable note [velocity] [continue_syllable] [vowel_start]
+//     /note_off
+//     /volume, /expression, /pitch_bend, /vibrato, /intensity, /brightness, /consonant_volume,
+//     /attack_rate, /release_rate, /stereo_width, /whisper, /breath, /output_gain,
+//     /air, /nasality   (one float)
+//     /accent, /hum, /mono   (one int or float, nonzero for true)
+//
+// Numeric arguments may be sent as either ints or floats.
+//
+// There is no authentication, so anyone who can send packets to the port can control the
+// synthesizer.  By default the player only listens on the loopback address, so only programs on
+// the same computer can connect.  Use the --osc-address and --osc-port command line options to
+// listen somewhere else.
+
+use chorus::director::Message;
+use std::net::UdpSocket;
+use std::sync::mpsc;
+use std::thread;
+
+/// An argument to an OSC message.
+enum Argument {
+    Int(i32),
+    Float(f32),
+    Str(String)
+}
+
+impl Argument {
+    fn as_f32(&self) -> Option<f32> {
+        match self {
+            Argument::Int(i) => Some(*i as f32),
+            Argument::Float(f) => Some(*f),
+            Argument::Str(_) => None
+        }
+    }
+}
+
+/// Start a thread that listens for OSC packets on a UDP address and port and sends the
+/// corresponding Messages.  Packets that cannot be parsed, and addresses that are not recognized,
+/// are ignored.  Returns an error if the socket cannot be bound, for example because another
+/// program is already using the port.
+pub fn start_listener(address: &str, port: u16, sender: mpsc::Sender<Message>) -> std::io::Result<thread::JoinHandle<()>> {
+    let socket = UdpSocket::bind((address, port))?;
+    Ok(thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        while let Ok(size) = socket.recv(&mut buffer) {
+            let mut messages = vec![];
+            parse_packet(&buffer[..size], &mut messages);
+            for (address, args) in messages {
+                if let Some(message) = to_message(&address, &args) {
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Convert an OSC message to a Message.
+fn to_message(address: &str, args: &[Argument]) -> Option<Message> {
+    let value = args.first().and_then(Argument::as_f32);
+    match address {
+        "/note_on" => {
+            let syllable = match args.first() {
+                Some(Argument::Str(s)) => s.clone(),
+                _ => return None
+            };
+            let note_index = args.get(1)?.as_f32()? as i32;
+            let velocity = args.get(2).and_then(Argument::as_f32).unwrap_or(1.0);
+            let continue_syllable = args.get(3).and_then(Argument::as_f32).unwrap_or(0.0) != 0.0;
+            let vowel_start = args.get(4).and_then(Argument::as_f32).map(|samples| samples as i64);
+            Some(Message::NoteOn {syllable, note_index, velocity, continue_syllable, vowel_start})
+        }
+        "/note_off" => Some(Message::NoteOff),
+        "/volume" => Some(Message::SetVolume {volume: value?}),
+        "/expression" => Some(Message::SetExpression {value: value?}),
+        "/pitch_bend" => Some(Message::SetPitchBend {semitones: value?}),
+        "/vibrato" => Some(Message::SetVibrato {vibrato: value?}),
+        "/intensity" => Some(Message::SetIntensity {intensity: value?}),
+        "/brightness" => Some(Message::SetBrightness {brightness: value?}),
+        "/consonant_volume" => Some(Message::SetConsonantVolume {volume: value?}),
+        "/attack_rate" => Some(Message::SetAttackRate {attack: value?}),
+        "/release_rate" => Some(Message::SetReleaseRate {release: value?}),
+        "/stereo_width" => Some(Message::SetStereoWidth {width: value?}),
+        "/whisper" => Some(Message::SetWhisper {amount: value?}),
+        "/breath" => Some(Message::Breath {intensity: value?}),
+        "/output_gain" => Some(Message::SetOutputGain {gain_db: value?}),
+        "/air" => Some(Message::SetAir {gain_db: value?}),
+        "/nasality" => Some(Message::SetNasality {coupling: value?}),
+        "/accent" => Some(Message::SetAccent {accent: value? != 0.0}),
+        "/hum" => Some(Message::SetHum {enabled: value? != 0.0}),
+        "/mono" => Some(Message::SetMono {enabled: value? != 0.0}),
+        _ => None
+    }
+}
+
+/// Parse an OSC packet, which may be either a single message or a bundle.  Each message found
+/// is added to messages as (address, arguments).  Bundles are processed immediately, ignoring
+/// their time tags.
+fn parse_packet(data: &[u8], messages: &mut Vec<(String, Vec<Argument>)>) {
+    if data.starts_with(b"#bundle\0") {
+        // Skip the time tag, then parse each element, which is preceded by its size.
+
+        let mut pos = 16;
+        while pos+4 <= data.len() {
+            let size = u32::from_be_bytes([data[pos], data[pos+1], data[pos+2], data[pos+3]]) as usize;
+            pos += 4;
+            if pos+size > data.len() {
+                return;
+            }
+            parse_packet(&data[pos..pos+size], messages);
+            pos += size;
+        }
+    }
+    else if let Some(message) = parse_message(data) {
+        messages.push(message);
+    }
+}
+
+/// Parse a single OSC message.
+fn parse_message(data: &[u8]) -> Option<(String, Vec<Argument>)> {
+    let mut pos = 0;
+    let address = read_string(data, &mut pos)?;
+    let mut args = vec![];
+    if pos == data.len() {
+        return Some((address, args));
+    }
+    let tags = read_string(data, &mut pos)?;
+    for tag in tags.chars().skip_while(|&c| c == ',') {
+        match tag {
+            'i' => args.push(Argument::Int(i32::from_be_bytes(read_bytes(data, &mut pos)?))),
+            'f' => args.push(Argument::Float(f32::from_be_bytes(read_bytes(data, &mut pos)?))),
+            's' => args.push(Argument::Str(read_string(data, &mut pos)?)),
+            'T' => args.push(Argument::Int(1)),
+            'F' => args.push(Argument::Int(0)),
+            _ => return None
+        }
+    }
+    Some((address, args))
+}
+
+/// Read a null terminated string, padded to a multiple of four bytes.
+fn read_string(data: &[u8], pos: &mut usize) -> Option<String> {
+    let length = data.get(*pos..)?.iter().position(|&b| b == 0)?;
+    let s = std::str::from_utf8(&data[*pos..*pos+length]).ok()?.to_string();
+    *pos += (length+4) & !3;
+    Some(s)
+}
+
+/// Read four bytes.
+fn read_bytes(data: &[u8], pos: &mut usize) -> Option<[u8; 4]> {
+    let bytes = data.get(*pos..*pos+4)?.try_into().ok()?;
+    *pos += 4;
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Append a string to a packet, null terminated and padded to a multiple of four bytes.
+    fn push_string(data: &mut Vec<u8>, s: &str) {
+        data.extend_from_slice(s.as_bytes());
+        data.push(0);
+        while data.len() & 3 != 0 {
+            data.push(0);
+        }
+    }
+
+    fn note_on_packet() -> Vec<u8> {
+        let mut data = vec![];
+        push_string(&mut data, "/note_on");
+        push_string(&mut data, ",sifT");
+        push_string(&mut data, "lA");
+        data.extend_from_slice(&60_i32.to_be_bytes());
+        data.extend_from_slice(&0.5_f32.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let mut messages = vec![];
+        parse_packet(&note_on_packet(), &mut messages);
+        assert_eq!(messages.len(), 1);
+        let (address, args) = &messages[0];
+        assert_eq!(address, "/note_on");
+        assert_eq!(args.len(), 4);
+        assert!(matches!(&args[0], Argument::Str(s) if s == "lA"));
+        assert!(matches!(args[1], Argument::Int(60)));
+        assert!(matches!(args[2], Argument::Float(f) if f == 0.5));
+        assert!(matches!(args[3], Argument::Int(1)));
+        match to_message(address, args) {
+            Some(Message::NoteOn {syllable, note_index, velocity, continue_syllable, ..}) => {
+                assert_eq!(syllable, "lA");
+                assert_eq!(note_index, 60);
+                assert_eq!(velocity, 0.5);
+                assert!(continue_syllable);
+            }
+            _ => panic!("expected a NoteOn")
+        }
+    }
+
+    #[test]
+    fn test_parse_bundle() {
+        let mut volume = vec![];
+        push_string(&mut volume, "/volume");
+        push_string(&mut volume, ",f");
+        volume.extend_from_slice(&0.25_f32.to_be_bytes());
+        let note_on = note_on_packet();
+        let mut data = vec![];
+        push_string(&mut data, "#bundle");
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in [&volume, &note_on] {
+            data.extend_from_slice(&(element.len() as u32).to_be_bytes());
+            data.extend_from_slice(element);
+        }
+        let mut messages = vec![];
+        parse_packet(&data, &mut messages);
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(to_message(&messages[0].0, &messages[0].1), Some(Message::SetVolume {volume}) if volume == 0.25));
+        assert!(matches!(to_message(&messages[1].0, &messages[1].1), Some(Message::NoteOn {..})));
+    }
+
+    #[test]
+    fn test_invalid_packets() {
+        // Truncated packets and unknown type tags are ignored without panicking.
+
+        let packet = note_on_packet();
+        for length in 0..packet.len() {
+            let mut messages = vec![];
+            parse_packet(&packet[..length], &mut messages);
+            assert!(messages.iter().all(|(address, _)| address == "/note_on"));
+        }
+        let mut data = vec![];
+        push_string(&mut data, "/volume");
+        push_string(&mut data, ",x");
+        let mut messages = vec![];
+        parse_packet(&data, &mut messages);
+        assert!(messages.is_empty());
+
+        // A message with an unknown address or missing arguments produces no Message.
+
+        assert!(to_message("/unknown", &[Argument::Float(1.0)]).is_none());
+        assert!(to_message("/volume", &[]).is_none());
+        assert!(to_message("/note_on", &[Argument::Int(60)]).is_none());
+    }
+}
@@ -13,59 +13,110 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{ChorusExMachinaParams, VoicePart};
+use crate::{ChorusExMachinaParams, LegatoMode, NotePriority, NoteValue, PlaybackState, ResampleQuality, SyllableGrouping};
+use crate::meter::LevelMeter;
+use crate::preset::{Preset, export_phrases, import_phrases};
 use chorus::director::Message;
-use chorus::syllable::Syllable;
+use chorus::performer::phrase_syllables;
+use chorus::random::Random;
+use chorus::syllable::{Syllable, REST};
 use nih_plug::prelude::*;
 use nih_plug_egui::{create_egui_editor, egui};
+use nih_plug_egui::resizable_window::ResizableWindow;
 use egui_extras::{Column, TableBuilder};
 use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
 use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::Ordering;
 
 #[derive(PartialEq)]
 enum Panel {
     Controls,
     Text,
+    Tract,
     Help,
     About
 }
 
 pub struct UIState {
     current_panel: Panel,
-    edit_phrase: usize
+    edit_phrase: usize,
+    preset_path: String,
+    preset_status: String,
+    phrase_path: String,
+    phrase_status: String,
+    random: Random,
+    compare_slot: usize,
+    compare_presets: [Option<Preset>; 2],
+    compare_phrases: bool,
+    preview_note: Option<i32>,
+    last_playback_phrase: i32
 }
 
 impl UIState {
     pub fn new() -> Self {
         Self {
             current_panel: Panel::Controls,
-            edit_phrase: 0
+            edit_phrase: 0,
+            preset_path: "".to_string(),
+            preset_status: "".to_string(),
+            phrase_path: "".to_string(),
+            phrase_status: "".to_string(),
+            random: Random::new(),
+            compare_slot: 0,
+            compare_presets: [None, None],
+            compare_phrases: false,
+            preview_note: None,
+            last_playback_phrase: 0
         }
     }
 }
 
-pub fn draw_editor(params: Arc<ChorusExMachinaParams>, sender: Arc<Mutex<mpsc::Sender<Message>>>, state: Arc<Mutex<UIState>>) -> Option<Box<dyn Editor>> {
+pub fn draw_editor(params: Arc<ChorusExMachinaParams>, sender: Arc<Mutex<mpsc::Sender<Message>>>, state: Arc<Mutex<UIState>>, vocal_shape: Arc<Mutex<(Vec<f32>, f32, Vec<f32>)>>, level_meter: Arc<LevelMeter>, playback: Arc<Mutex<PlaybackState>>) -> Option<Box<dyn Editor>> {
+    let egui_state = params.editor_state.clone();
     create_egui_editor(
-        params.editor_state.clone(),
+        egui_state.clone(),
         (),
         Default::default(),
         |_, _, _| {},
         move |ctx, setter, _queue, _state| {
-            egui::CentralPanel::default().show(ctx, |ui| {
+            // The window can be resized by dragging its corner.  The new size is stored in the
+            // persisted editor state, so it is restored the next time the editor is opened.
+
+            ResizableWindow::new("editor").min_size(egui::vec2(500.0, 300.0)).show(ctx, egui_state.as_ref(), |ui| {
                 egui::SidePanel::left("tabs").max_width(100.0).resizable(false).show_inside(ui, |ui| {
                     let mut state = state.lock().unwrap();
                     ui.vertical_centered_justified(|ui| {
                         ui.selectable_value(&mut state.current_panel, Panel::Controls, "Controls");
                         ui.selectable_value(&mut state.current_panel, Panel::Text, "Text");
+                        ui.selectable_value(&mut state.current_panel, Panel::Tract, "Vocal Tract");
                         ui.selectable_value(&mut state.current_panel, Panel::Help, "Help");
                         ui.selectable_value(&mut state.current_panel, Panel::About, "About");
                     })
                 });
                 egui::CentralPanel::default().show_inside(ui, |ui| {
                     let mut state = state.lock().unwrap();
+
+                    // A program change selects a phrase on the audio thread, which cannot set
+                    // parameters.  Adopt it as the Selected Phrase, so the table highlights the
+                    // phrase being sung and clicking the previous one switches back to it.
+
+                    let phrase = playback.lock().unwrap().phrase;
+                    if phrase != state.last_playback_phrase {
+                        state.last_playback_phrase = phrase;
+                        if phrase != params.selected_phrase.value() {
+                            setter.begin_set_parameter(&params.selected_phrase);
+                            setter.set_parameter(&params.selected_phrase, phrase);
+                            setter.end_set_parameter(&params.selected_phrase);
+                        }
+                    }
                     match state.current_panel {
-                        Panel::Controls => draw_controls_panel(ui, &params, &sender, setter),
-                        Panel::Text => draw_text_panel(ui, &params, setter, &mut state),
+                        Panel::Controls => {
+                            egui::ScrollArea::vertical().show(ui, |ui| {
+                                draw_controls_panel(ui, &params, &sender, setter, &mut state, &level_meter);
+                            });
+                        }
+                        Panel::Text => draw_text_panel(ui, &params, setter, &mut state, &playback),
+                        Panel::Tract => draw_tract_panel(ui, &vocal_shape),
                         Panel::Help => draw_help_panel(ui),
                         Panel::About => draw_about_panel(ui)
                     }
@@ -75,38 +126,53 @@ pub fn draw_editor(params: Arc<ChorusExMachinaParams>, sender: Arc<Mutex<mpsc::S
     )
 }
 
-fn draw_controls_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, sender: &Arc<Mutex<mpsc::Sender<Message>>>, setter: &ParamSetter) {
+fn draw_controls_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, sender: &Arc<Mutex<mpsc::Sender<Message>>>, setter: &ParamSetter, state: &mut UIState, level_meter: &LevelMeter) {
     let mut new_voice_part = params.voice_part.value();
     let mut new_voice_count = params.voice_count.value();
     ui.label(egui::RichText::new("The voices in the chorus").italics());
     ui.add_space(5.0);
     ui.horizontal(|ui| {
         ui.label("Voice Part");
-        egui::ComboBox::from_id_salt("Voice Part").selected_text(format!("{:?}", new_voice_part)).show_ui(ui, |ui| {
-            ui.selectable_value(&mut new_voice_part, VoicePart::Soprano, "Soprano");
-            ui.selectable_value(&mut new_voice_part, VoicePart::Alto, "Alto");
-            ui.selectable_value(&mut new_voice_part, VoicePart::Tenor, "Tenor");
-            ui.selectable_value(&mut new_voice_part, VoicePart::Bass, "Bass");
+        egui::ComboBox::from_id_salt("Voice Part").selected_text(chorus::VoicePart::from(new_voice_part).to_string()).show_ui(ui, |ui| {
+            for part in chorus::VoicePart::all() {
+                ui.selectable_value(&mut new_voice_part, part.into(), part.to_string());
+            }
         });
         ui.add_space(10.0);
         ui.label("Voices");
         ui.add(egui::Slider::new(&mut new_voice_count, 1..=8).handle_shape(egui::style::HandleShape::Circle));
     });
     if params.voice_part.value() != new_voice_part || params.voice_count.value() != new_voice_count {
+        // Changing only the number of voices can be done without interrupting the current note.
+
+        let message = if params.voice_part.value() == new_voice_part {
+            Message::SetVoiceCount {count: new_voice_count as usize}
+        }
+        else {
+            Message::Reinitialize {voice_part: new_voice_part.into(), voice_count: new_voice_count as usize}
+        };
         setter.begin_set_parameter(&params.voice_part);
         setter.set_parameter(&params.voice_part, new_voice_part);
         setter.end_set_parameter(&params.voice_part);
         setter.begin_set_parameter(&params.voice_count);
         setter.set_parameter(&params.voice_count, new_voice_count);
         setter.end_set_parameter(&params.voice_count);
-        let voice_part = match &new_voice_part {
-            VoicePart::Soprano => chorus::VoicePart::Soprano,
-            VoicePart::Alto => chorus::VoicePart::Alto,
-            VoicePart::Tenor => chorus::VoicePart::Tenor,
-            VoicePart::Bass => chorus::VoicePart::Bass,
-        };
-        let _ = sender.lock().unwrap().send(Message::Reinitialize {voice_part: voice_part, voice_count: new_voice_count as usize});
+        let reinitialize = matches!(message, Message::Reinitialize {..});
+        let _ = sender.lock().unwrap().send(message);
+        if reinitialize {
+            params.params_dirty.store(true, Ordering::Relaxed);
+        }
     };
+    ui.add_space(5.0);
+    ui.horizontal(|ui| {
+        let mut transpose = params.transpose.value();
+        ui.label("Transpose");
+        if ui.add(egui::Slider::new(&mut transpose, -24..=24).suffix(" st").handle_shape(egui::style::HandleShape::Circle)).changed() {
+            setter.begin_set_parameter(&params.transpose);
+            setter.set_parameter(&params.transpose, transpose);
+            setter.end_set_parameter(&params.transpose);
+        }
+    });
     ui.add_space(20.0);
     ui.label(egui::RichText::new("These controls can be mapped to MIDI CCs and automated in a DAW").italics());
     ui.add_space(5.0);
@@ -121,24 +187,116 @@ fn draw_controls_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, s
         draw_param_slider(ui, &params.release_rate, setter);
         draw_param_slider(ui, &params.stereo_width, setter);
         draw_param_slider(ui, &params.exciter_strength, setter);
+        draw_param_slider(ui, &params.ensemble, setter);
+        draw_param_slider(ui, &params.accent_strength, setter);
+        draw_param_slider(ui, &params.whisper, setter);
+        draw_param_slider(ui, &params.subglottal, setter);
+        draw_param_slider(ui, &params.resonance, setter);
+        draw_param_slider(ui, &params.humanize, setter);
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+            ui.label("Output Gain (dB)");
+        });
+        let mut gain = params.output_gain.value();
+        if ui.add(egui::Slider::new(&mut gain, -24.0..=12.0).handle_shape(egui::style::HandleShape::Circle).max_decimals(1)).changed() {
+            setter.begin_set_parameter(&params.output_gain);
+            setter.set_parameter(&params.output_gain, gain);
+            setter.end_set_parameter(&params.output_gain);
+        }
+        ui.end_row();
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+            ui.label("Air (dB)");
+        });
+        let mut air = params.air.value();
+        if ui.add(egui::Slider::new(&mut air, 0.0..=12.0).handle_shape(egui::style::HandleShape::Circle).max_decimals(1)).changed() {
+            setter.begin_set_parameter(&params.air);
+            setter.set_parameter(&params.air, air);
+            setter.end_set_parameter(&params.air);
+        }
+        ui.end_row();
+        if params.tempo_sync.value() {
+            draw_note_value_combo(ui, "Time Spread", &params.time_spread_note, setter);
+            draw_note_value_combo(ui, "Vowel Delay", &params.vowel_delay_note, setter);
+        }
+        else {
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+                ui.label("Time Spread (ms)");
+            });
+            let mut spread = params.time_spread.value();
+            if ui.add(egui::Slider::new(&mut spread, 0..=100).handle_shape(egui::style::HandleShape::Circle)).changed() {
+                setter.begin_set_parameter(&params.time_spread);
+                setter.set_parameter(&params.time_spread, spread);
+                setter.end_set_parameter(&params.time_spread);
+            }
+            ui.end_row();
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+                ui.label("Vowel Delay (ms)");
+            });
+            let mut delay = params.vowel_delay.value();
+            if ui.add(egui::Slider::new(&mut delay, 0..=250).handle_shape(egui::style::HandleShape::Circle)).changed() {
+                setter.begin_set_parameter(&params.vowel_delay);
+                setter.set_parameter(&params.vowel_delay, delay);
+                setter.end_set_parameter(&params.vowel_delay);
+            }
+            ui.end_row();
+        }
+        draw_note_value_combo(ui, "Vowel Grid", &params.vowel_grid, setter);
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+            ui.label("Note Priority");
+        });
+        let mut priority = params.note_priority.value();
+        egui::ComboBox::from_id_salt("Note Priority").selected_text(format!("{:?}", priority)).show_ui(ui, |ui| {
+            ui.selectable_value(&mut priority, NotePriority::Last, "Last");
+            ui.selectable_value(&mut priority, NotePriority::Highest, "Highest");
+            ui.selectable_value(&mut priority, NotePriority::Lowest, "Lowest");
+        });
+        if priority != params.note_priority.value() {
+            setter.begin_set_parameter(&params.note_priority);
+            setter.set_parameter(&params.note_priority, priority);
+            setter.end_set_parameter(&params.note_priority);
+        }
+        ui.end_row();
         ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-            ui.label("Time Spread (ms)");
+            ui.label("Legato Mode");
+        });
+        let mut legato_mode = params.legato_mode.value();
+        egui::ComboBox::from_id_salt("Legato Mode").selected_text(format!("{:?}", legato_mode)).show_ui(ui, |ui| {
+            ui.selectable_value(&mut legato_mode, LegatoMode::Auto, "Auto");
+            ui.selectable_value(&mut legato_mode, LegatoMode::Legato, "Legato");
+            ui.selectable_value(&mut legato_mode, LegatoMode::Retrigger, "Retrigger");
         });
-        let mut spread = params.time_spread.value();
-        if ui.add(egui::Slider::new(&mut spread, 0..=100).handle_shape(egui::style::HandleShape::Circle)).changed() {
-            setter.begin_set_parameter(&params.time_spread);
-            setter.set_parameter(&params.time_spread, spread);
-            setter.end_set_parameter(&params.time_spread);
+        if legato_mode != params.legato_mode.value() {
+            setter.begin_set_parameter(&params.legato_mode);
+            setter.set_parameter(&params.legato_mode, legato_mode);
+            setter.end_set_parameter(&params.legato_mode);
         }
         ui.end_row();
         ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-            ui.label("Vowel Delay (ms)");
+            ui.label("Advance By");
+        });
+        let mut advance_by = params.advance_by.value();
+        egui::ComboBox::from_id_salt("Advance By").selected_text(format!("{:?}", advance_by)).show_ui(ui, |ui| {
+            ui.selectable_value(&mut advance_by, SyllableGrouping::Syllable, "Syllable");
+            ui.selectable_value(&mut advance_by, SyllableGrouping::Word, "Word");
+        });
+        if advance_by != params.advance_by.value() {
+            setter.begin_set_parameter(&params.advance_by);
+            setter.set_parameter(&params.advance_by, advance_by);
+            setter.end_set_parameter(&params.advance_by);
+        }
+        ui.end_row();
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+            ui.label("Resampling Quality");
+        });
+        let mut quality = params.resample_quality.value();
+        egui::ComboBox::from_id_salt("Resampling Quality").selected_text(format!("{:?}", quality)).show_ui(ui, |ui| {
+            ui.selectable_value(&mut quality, ResampleQuality::Fast, "Fast");
+            ui.selectable_value(&mut quality, ResampleQuality::Good, "Good");
+            ui.selectable_value(&mut quality, ResampleQuality::Best, "Best");
         });
-        let mut delay = params.vowel_delay.value();
-        if ui.add(egui::Slider::new(&mut delay, 0..=250).handle_shape(egui::style::HandleShape::Circle)).changed() {
-            setter.begin_set_parameter(&params.vowel_delay);
-            setter.set_parameter(&params.vowel_delay, delay);
-            setter.end_set_parameter(&params.vowel_delay);
+        if quality != params.resample_quality.value() {
+            setter.begin_set_parameter(&params.resample_quality);
+            setter.set_parameter(&params.resample_quality, quality);
+            setter.end_set_parameter(&params.resample_quality);
         }
         ui.end_row();
         let mut accent = params.accent.value();
@@ -153,7 +311,141 @@ fn draw_controls_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, s
             setter.set_parameter(&params.advance_syllable, advance_syllable);
             setter.end_set_parameter(&params.advance_syllable);
         }
+        ui.end_row();
+        let mut hum = params.hum.value();
+        if ui.checkbox(&mut hum, "Hum").changed() {
+            setter.begin_set_parameter(&params.hum);
+            setter.set_parameter(&params.hum, hum);
+            setter.end_set_parameter(&params.hum);
+        }
+        let mut tempo_sync = params.tempo_sync.value();
+        if ui.checkbox(&mut tempo_sync, "Tempo Sync").changed() {
+            setter.begin_set_parameter(&params.tempo_sync);
+            setter.set_parameter(&params.tempo_sync, tempo_sync);
+            setter.end_set_parameter(&params.tempo_sync);
+        }
+        ui.end_row();
+        let mut mono = params.mono.value();
+        if ui.checkbox(&mut mono, "Mono").changed() {
+            setter.begin_set_parameter(&params.mono);
+            setter.set_parameter(&params.mono, mono);
+            setter.end_set_parameter(&params.mono);
+        }
+        let mut spectral_tilt = params.spectral_tilt.value();
+        if ui.checkbox(&mut spectral_tilt, "Spectral Tilt").changed() {
+            setter.begin_set_parameter(&params.spectral_tilt);
+            setter.set_parameter(&params.spectral_tilt, spectral_tilt);
+            setter.end_set_parameter(&params.spectral_tilt);
+        }
+        ui.end_row();
+        let mut oversample = params.oversample.value();
+        if ui.checkbox(&mut oversample, "Oversample").changed() {
+            setter.begin_set_parameter(&params.oversample);
+            setter.set_parameter(&params.oversample, oversample);
+            setter.end_set_parameter(&params.oversample);
+        }
+    });
+    ui.add_space(5.0);
+    if ui.button("Randomize").clicked() {
+        randomize_controls(params, setter, &mut state.random);
+    }
+    ui.add_space(5.0);
+    draw_preview_keyboard(ui, params, &sender.lock().unwrap(), state);
+    ui.add_space(20.0);
+    ui.label(egui::RichText::new("Save all settings, including the phrases, to a file").italics());
+    ui.add_space(5.0);
+    ui.horizontal(|ui| {
+        ui.label("Preset File");
+        ui.add(egui::TextEdit::singleline(&mut state.preset_path).desired_width(250.0));
+        if ui.button("Save").clicked() {
+            state.preset_status = match Preset::capture(params).save(&state.preset_path) {
+                Ok(()) => "Saved".to_string(),
+                Err(error) => error
+            };
+        }
+        if ui.button("Load").clicked() {
+            state.preset_status = match Preset::load(&state.preset_path) {
+                Ok(preset) => {
+                    preset.apply(params, setter, &sender.lock().unwrap());
+                    "Loaded".to_string()
+                }
+                Err(error) => error
+            };
+        }
+    });
+    ui.label(&state.preset_status);
+    ui.add_space(20.0);
+    ui.label(egui::RichText::new("Switch between two sets of settings to compare them").italics());
+    ui.add_space(5.0);
+    ui.horizontal(|ui| {
+        for (slot, name) in ["A", "B"].iter().enumerate() {
+            if ui.selectable_label(state.compare_slot == slot, *name).clicked() && state.compare_slot != slot {
+                select_compare_slot(slot, params, setter, &sender.lock().unwrap(), state);
+            }
+        }
+        ui.add_space(10.0);
+        ui.checkbox(&mut state.compare_phrases, "Include Phrases");
     });
+    ui.add_space(20.0);
+    draw_level_meter(ui, level_meter);
+}
+
+/// Draw a bar for each channel showing the RMS level, with a line marking the peak level.
+/// The scale runs from -60 dB to 0 dB, and the peak line turns red if the output clips.
+fn draw_level_meter(ui: &mut egui::Ui, level_meter: &LevelMeter) {
+    let to_fraction = |amplitude: f32| ((20.0*amplitude.max(1e-6).log10()+60.0)/60.0).clamp(0.0, 1.0);
+    for (channel, name) in ["L", "R"].iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(*name);
+            let (response, painter) = ui.allocate_painter(egui::vec2((ui.available_width()-10.0).max(100.0), 10.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+            let rms = to_fraction(level_meter.get_rms(channel));
+            let bar = egui::Rect::from_min_size(rect.min, egui::vec2(rms*rect.width(), rect.height()));
+            painter.rect_filled(bar, 0.0, ui.visuals().selection.bg_fill);
+            let peak = level_meter.get_peak(channel);
+            let x = rect.left() + to_fraction(peak)*rect.width();
+            let color = if peak >= 1.0 {egui::Color32::RED} else {ui.visuals().text_color()};
+            painter.line_segment([egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())], egui::Stroke::new(2.0, color));
+        });
+    }
+    ui.ctx().request_repaint();
+}
+
+/// Switch to a different A/B comparison slot.  The current settings are stored in the slot being
+/// left, and the ones stored in the new slot are applied.  A slot that has never been selected
+/// starts out as a copy of the current settings.
+fn select_compare_slot(slot: usize, params: &Arc<ChorusExMachinaParams>, setter: &ParamSetter, sender: &mpsc::Sender<Message>, state: &mut UIState) {
+    state.compare_presets[state.compare_slot] = Some(Preset::capture(params));
+    state.compare_slot = slot;
+    if let Some(preset) = &state.compare_presets[slot] {
+        let mut preset = preset.clone();
+        if !state.compare_phrases {
+            preset.phrases.clear();
+        }
+        preset.apply(params, setter, sender);
+    }
+}
+
+/// Set the continuous controls to random values.  Each one is kept within a range that produces
+/// a usable sound, so every click gives something worth listening to.
+fn randomize_controls(params: &Arc<ChorusExMachinaParams>, setter: &ParamSetter, random: &mut Random) {
+    let ranges = [
+        (&params.dynamics, 0.4, 1.0),
+        (&params.vibrato, 0.0, 0.8),
+        (&params.intensity, 0.2, 0.8),
+        (&params.brightness, 0.3, 1.0),
+        (&params.consonant_volume, 0.3, 0.8),
+        (&params.attack_rate, 0.3, 1.0),
+        (&params.release_rate, 0.2, 0.8),
+        (&params.stereo_width, 0.3, 1.0),
+        (&params.exciter_strength, 0.0, 0.8)
+    ];
+    for (param, min, max) in ranges {
+        setter.begin_set_parameter(param);
+        setter.set_parameter(param, min + (max-min)*random.get_uniform());
+        setter.end_set_parameter(param);
+    }
 }
 
 fn draw_param_slider(ui: &mut egui::Ui, param: &FloatParam, setter: &ParamSetter) {
@@ -169,7 +461,73 @@ fn draw_param_slider(ui: &mut egui::Ui, param: &FloatParam, setter: &ParamSetter
     ui.end_row();
 }
 
-fn draw_text_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, setter: &ParamSetter, state: &mut UIState) {
+fn draw_note_value_combo(ui: &mut egui::Ui, label: &str, param: &EnumParam<NoteValue>, setter: &ParamSetter) {
+    ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+        ui.label(label);
+    });
+    let mut value = param.value();
+    egui::ComboBox::from_id_salt(label).selected_text(NoteValue::variants()[value.to_index()]).show_ui(ui, |ui| {
+        for (i, name) in NoteValue::variants().iter().enumerate() {
+            ui.selectable_value(&mut value, NoteValue::from_index(i), *name);
+        }
+    });
+    if value != param.value() {
+        setter.begin_set_parameter(param);
+        setter.set_parameter(param, value);
+        setter.end_set_parameter(param);
+    }
+    ui.end_row();
+}
+
+fn draw_text_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, setter: &ParamSetter, state: &mut UIState, playback: &Arc<Mutex<PlaybackState>>) {
+    let playback = *playback.lock().unwrap();
+    ui.horizontal(|ui| {
+        ui.label("Now Singing");
+        match playback.director.note {
+            Some(note) => {
+                let phrase = match params.phrases.lock().unwrap().get(playback.phrase as usize) {
+                    Some(phrase) => phrase.clone(),
+                    None => "".to_string()
+                };
+                let syllables: Vec<&str> = phrase_syllables(&phrase).collect();
+                let syllable_index = playback.director.syllable_index;
+                let index = if syllable_index < 0 || syllable_index as usize >= syllables.len() {0} else {syllable_index as usize};
+                ui.strong(format!("{} {}", note_name(note), syllables.get(index).unwrap_or(&"")));
+                ui.label(format!("(phrase {}, syllable {})", playback.phrase, index));
+                if let Some(c) = playback.director.active_consonant {
+                    ui.label(format!("consonant {c}"));
+                }
+            }
+            None => {
+                ui.label("-");
+            }
+        }
+    });
+    ui.add_space(5.0);
+    ui.ctx().request_repaint();
+    ui.horizontal(|ui| {
+        ui.label("Phrase File");
+        ui.add(egui::TextEdit::singleline(&mut state.phrase_path).desired_width(200.0));
+        if ui.button("Export").clicked() {
+            state.phrase_status = match export_phrases(&params.phrases.lock().unwrap(), &state.phrase_path) {
+                Ok(()) => "Exported".to_string(),
+                Err(error) => error
+            };
+        }
+        if ui.button("Import").clicked() {
+            let mut phrases = params.phrases.lock().unwrap();
+            state.phrase_status = match import_phrases(&state.phrase_path, phrases.len()) {
+                Ok(imported) => {
+                    *phrases = imported;
+                    params.phrases_dirty.store(true, Ordering::Relaxed);
+                    "Imported".to_string()
+                }
+                Err(error) => error
+            };
+        }
+        ui.label(&state.phrase_status);
+    });
+    ui.add_space(5.0);
     let table = TableBuilder::new(ui)
         .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
         .column(Column::auto())
@@ -199,13 +557,17 @@ fn draw_text_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, sette
                     let response = ui.add_sized(ui.available_size(), egui::TextEdit::singleline(&mut phrases[row_index]));
                     response.request_focus();
                     clicked = response.clicked();
+                    if response.changed() {
+                        params.phrases_dirty.store(true, Ordering::Relaxed);
+                    }
                 }
                 else {
                     let width = ui.fonts_mut(|f| f.glyph_width(&egui::TextStyle::Body.resolve(ui.style()), ' '));
                     ui.spacing_mut().item_spacing.x = width;
-                    for s in phrases[row_index].split_whitespace() {
-                        match Syllable::build(s) {
-                            Ok(_) => {
+                    for s in phrase_syllables(&phrases[row_index]) {
+                        let result = if s == REST {Ok(())} else {Syllable::build(s).map(|_| ())};
+                        match result {
+                            Ok(()) => {
                                 clicked |= ui.label(s).clicked();
                             }
                             Err(error) => {
@@ -234,6 +596,85 @@ fn draw_text_panel(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, sette
     }
 }
 
+/// Draw a one octave keyboard in the middle of the voice part's range.  Holding down a key sings
+/// the first syllable of the selected phrase, so the sound can be auditioned without a MIDI
+/// controller.
+fn draw_preview_keyboard(ui: &mut egui::Ui, params: &Arc<ChorusExMachinaParams>, sender: &mpsc::Sender<Message>, state: &mut UIState) {
+    let (lowest, highest) = chorus::VoicePart::from(params.voice_part.value()).note_range();
+    let first_note = (lowest+highest)/2-6;
+    let mut pressed = None;
+    ui.horizontal(|ui| {
+        ui.label("Test Note");
+        for note in first_note..first_note+12 {
+            if ui.button(note_name(note)).is_pointer_button_down_on() {
+                pressed = Some(note);
+            }
+        }
+    });
+    if pressed != state.preview_note {
+        match pressed {
+            Some(note) => {
+                let phrase = match params.phrases.lock().unwrap().get(params.selected_phrase.value() as usize) {
+                    Some(phrase) => phrase.clone(),
+                    None => "".to_string()
+                };
+                if let Some(syllable) = phrase_syllables(&phrase).next() {
+                    let _ = sender.send(Message::NoteOn {syllable: syllable.to_string(), note_index: note, velocity: 1.0, continue_syllable: false, vowel_start: None});
+                }
+            }
+            None => {
+                let _ = sender.send(Message::NoteOff);
+            }
+        }
+        state.preview_note = pressed;
+    }
+}
+
+/// Get the name of a MIDI note, such as C4 for note 60.
+fn note_name(note: i32) -> String {
+    const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    format!("{}{}", NAMES[note.rem_euclid(12) as usize], note.div_euclid(12)-1)
+}
+
+fn draw_tract_panel(ui: &mut egui::Ui, vocal_shape: &Arc<Mutex<(Vec<f32>, f32, Vec<f32>)>>) {
+    let (shape, coupling, pressure) = vocal_shape.lock().unwrap().clone();
+    ui.label(egui::RichText::new("Cross section of the first voice's vocal tract, from the glottis (left) to the lips (right)").italics());
+    ui.label(egui::RichText::new("The line shows the sound pressure in each segment").italics());
+    ui.add_space(5.0);
+    ui.label(format!("Nasal Coupling: {:.2}", coupling));
+    ui.add_space(5.0);
+
+    // Draw each segment as a bar whose height is proportional to its diameter.
+
+    let size = egui::vec2(ui.available_width(), ui.available_height().max(100.0));
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    if shape.len() > 0 {
+        let max_diameter = 3.5;
+        let width = rect.width() / shape.len() as f32;
+        let color = ui.visuals().selection.bg_fill;
+        for (i, area) in shape.iter().enumerate() {
+            let height = (area.max(0.0).sqrt()/max_diameter).min(1.0)*rect.height();
+            let x = rect.left() + i as f32*width;
+            let segment = egui::Rect::from_center_size(egui::pos2(x+0.5*width, rect.center().y), egui::vec2(width-1.0, height));
+            painter.rect_filled(segment, 0.0, color);
+        }
+    }
+
+    // Draw the instantaneous pressure along the tract as a line, scaled to fill the height.
+
+    let max_pressure = pressure.iter().fold(0.0_f32, |max, p| max.max(p.abs()));
+    if pressure.len() > 1 && max_pressure > 0.0 {
+        let width = rect.width() / pressure.len() as f32;
+        let points: Vec<egui::Pos2> = pressure.iter().enumerate().map(|(i, p)| {
+            egui::pos2(rect.left() + (i as f32+0.5)*width, rect.center().y - 0.45*rect.height()*p/max_pressure)
+        }).collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(2.0, ui.visuals().text_color())));
+    }
+    ui.ctx().request_repaint();
+}
+
 fn draw_help_panel(ui: &mut egui::Ui) {
     let mut cache = CommonMarkCache::default();
     let text = include_str!("help.md");
@@ -13,24 +13,37 @@
 // You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
 // If not, see <https://www.gnu.org/licenses/>.
 
+#[cfg(feature = "gui")]
 mod editor;
+mod meter;
+#[cfg(feature = "gui")]
+mod preset;
 
 use chorus;
-use chorus::director::{Director, Message};
+use chorus::director::{BrightnessMode, Director, DirectorState, Message};
+use chorus::performer::{Event, Performer};
 use chorus::resampler::Resampler;
+use meter::LevelMeter;
 use nih_plug::prelude::*;
+#[cfg(feature = "gui")]
 use nih_plug_egui::EguiState;
 use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 pub struct ChorusExMachina {
     params: Arc<ChorusExMachinaParams>,
     director: Arc<Mutex<Director>>,
     sender: Arc<Mutex<mpsc::Sender<Message>>>,
+    #[cfg(feature = "gui")]
     editor_state: Arc<Mutex<editor::UIState>>,
+    vocal_shape: Arc<Mutex<(Vec<f32>, f32, Vec<f32>)>>,
+    level_meter: Arc<LevelMeter>,
+    playback: Arc<Mutex<PlaybackState>>,
     need_resample: bool,
     resample_left: Resampler,
     resample_right: Resampler,
-    last_note: u8,
+    resample_quality: ResampleQuality,
+    performer: Performer,
     last_dynamics: f32,
     last_vibrato: f32,
     last_intensity: f32,
@@ -40,23 +53,58 @@ pub struct ChorusExMachina {
     last_release_rate: f32,
     last_stereo_width: f32,
     last_exciter_strength: f32,
-    last_time_spread: i32,
-    last_vowel_delay: i32,
+    last_ensemble: f32,
+    last_output_gain: f32,
+    last_air: f32,
+    last_time_spread: i64,
+    last_vowel_delay: i64,
     last_accent: bool,
+    last_accent_strength: f32,
+    last_hum: bool,
+    last_spectral_tilt: bool,
+    last_oversample: bool,
+    last_mono: bool,
+    last_whisper: f32,
+    last_subglottal: f32,
+    last_resonance: f32,
+    last_humanize: f32,
+    last_legato_mode: Option<LegatoMode>,
     last_phrase: i32,
-    last_syllable_index: i32
+    current_phrase: i32
 }
 
+/// What is currently being sung.  This is updated by the audio thread and displayed by the editor.
+#[derive(Copy, Clone, Default)]
+pub struct PlaybackState {
+    pub director: DirectorState,
+    pub phrase: i32
+}
+
+/// The persisted state of the editor window.  Without the gui feature there is no editor, but
+/// the field is still present so the set of persisted fields does not change.
+#[cfg(feature = "gui")]
+type EditorState = Arc<EguiState>;
+#[cfg(not(feature = "gui"))]
+type EditorState = Mutex<()>;
+
 #[derive(Params)]
 struct ChorusExMachinaParams {
     #[persist = "editor_state"]
-    editor_state: Arc<EguiState>,
+    editor_state: EditorState,
     #[persist = "phrases"]
     pub phrases: Mutex<Vec<String>>,
+    /// Set by the editor and preset loader when they send a Reinitialize message, so process()
+    /// knows to send every parameter to the rebuilt voices.
+    pub params_dirty: AtomicBool,
+    /// Set whenever the phrases are edited, so process() knows to give the Performer the new text
+    /// of the current phrase.
+    pub phrases_dirty: AtomicBool,
     #[id = "voice_part"]
     pub voice_part: EnumParam<VoicePart>,
     #[id = "voice_count"]
     pub voice_count: IntParam,
+    #[id = "transpose"]
+    pub transpose: IntParam,
     #[id = "dynamics"]
     pub dynamics: FloatParam,
     #[id = "vibrato"]
@@ -65,6 +113,8 @@ struct ChorusExMachinaParams {
     pub intensity: FloatParam,
     #[id = "brightness"]
     pub brightness: FloatParam,
+    #[id = "spectral_tilt"]
+    pub spectral_tilt: BoolParam,
     #[id = "consonant_volume"]
     pub consonant_volume: FloatParam,
     #[id = "attack_rate"]
@@ -75,16 +125,54 @@ struct ChorusExMachinaParams {
     pub stereo_width: FloatParam,
     #[id = "exciter_strength"]
     pub exciter_strength: FloatParam,
+    #[id = "ensemble"]
+    pub ensemble: FloatParam,
+    #[id = "output_gain"]
+    pub output_gain: FloatParam,
+    #[id = "air"]
+    pub air: FloatParam,
     #[id = "time_spread"]
     pub time_spread: IntParam,
     #[id = "vowel_delay"]
     pub vowel_delay: IntParam,
+    #[id = "tempo_sync"]
+    pub tempo_sync: BoolParam,
+    #[id = "time_spread_note"]
+    pub time_spread_note: EnumParam<NoteValue>,
+    #[id = "vowel_delay_note"]
+    pub vowel_delay_note: EnumParam<NoteValue>,
+    #[id = "vowel_grid"]
+    pub vowel_grid: EnumParam<NoteValue>,
     #[id = "accent"]
     pub accent: BoolParam,
+    #[id = "accent_strength"]
+    pub accent_strength: FloatParam,
+    #[id = "whisper"]
+    pub whisper: FloatParam,
+    #[id = "subglottal"]
+    pub subglottal: FloatParam,
+    #[id = "resonance"]
+    pub resonance: FloatParam,
+    #[id = "humanize"]
+    pub humanize: FloatParam,
+    #[id = "hum"]
+    pub hum: BoolParam,
+    #[id = "mono"]
+    pub mono: BoolParam,
+    #[id = "oversample"]
+    pub oversample: BoolParam,
     #[id = "selected_phrase"]
     pub selected_phrase: IntParam,
     #[id = "advance_syllable"]
-    pub advance_syllable: BoolParam
+    pub advance_syllable: BoolParam,
+    #[id = "advance_by"]
+    pub advance_by: EnumParam<SyllableGrouping>,
+    #[id = "note_priority"]
+    pub note_priority: EnumParam<NotePriority>,
+    #[id = "legato_mode"]
+    pub legato_mode: EnumParam<LegatoMode>,
+    #[id = "resample_quality"]
+    pub resample_quality: EnumParam<ResampleQuality>
 }
 
 #[derive(Copy, Clone, Enum, Debug, PartialEq)]
@@ -99,18 +187,172 @@ pub enum VoicePart {
     Bass,
 }
 
+impl From<VoicePart> for chorus::VoicePart {
+    fn from(part: VoicePart) -> Self {
+        match part {
+            VoicePart::Soprano => chorus::VoicePart::Soprano,
+            VoicePart::Alto => chorus::VoicePart::Alto,
+            VoicePart::Tenor => chorus::VoicePart::Tenor,
+            VoicePart::Bass => chorus::VoicePart::Bass,
+        }
+    }
+}
+
+impl From<chorus::VoicePart> for VoicePart {
+    fn from(part: chorus::VoicePart) -> Self {
+        match part {
+            chorus::VoicePart::Soprano => VoicePart::Soprano,
+            chorus::VoicePart::Alto => VoicePart::Alto,
+            chorus::VoicePart::Tenor => VoicePart::Tenor,
+            chorus::VoicePart::Bass => VoicePart::Bass,
+        }
+    }
+}
+
+/// Which note to play when several keys are held at once.
+#[derive(Copy, Clone, Enum, Debug, PartialEq)]
+pub enum NotePriority {
+    #[id = "last"]
+    Last,
+    #[id = "highest"]
+    Highest,
+    #[id = "lowest"]
+    Lowest,
+}
+
+impl From<NotePriority> for chorus::performer::NotePriority {
+    fn from(priority: NotePriority) -> Self {
+        match priority {
+            NotePriority::Last => chorus::performer::NotePriority::Last,
+            NotePriority::Highest => chorus::performer::NotePriority::Highest,
+            NotePriority::Lowest => chorus::performer::NotePriority::Lowest,
+        }
+    }
+}
+
+/// How many syllables of the phrase each note sings.
+#[derive(Copy, Clone, Enum, Debug, PartialEq)]
+pub enum SyllableGrouping {
+    #[id = "syllable"]
+    Syllable,
+    #[id = "word"]
+    Word,
+}
+
+impl From<SyllableGrouping> for chorus::performer::SyllableGrouping {
+    fn from(grouping: SyllableGrouping) -> Self {
+        match grouping {
+            SyllableGrouping::Syllable => chorus::performer::SyllableGrouping::Syllable,
+            SyllableGrouping::Word => chorus::performer::SyllableGrouping::Word,
+        }
+    }
+}
+
+/// How to connect a new note to one that is already playing.
+#[derive(Copy, Clone, Enum, Debug, PartialEq)]
+pub enum LegatoMode {
+    #[id = "auto"]
+    Auto,
+    #[id = "legato"]
+    Legato,
+    #[id = "retrigger"]
+    Retrigger,
+}
+
+impl From<LegatoMode> for chorus::director::LegatoMode {
+    fn from(mode: LegatoMode) -> Self {
+        match mode {
+            LegatoMode::Auto => chorus::director::LegatoMode::Auto,
+            LegatoMode::Legato => chorus::director::LegatoMode::AlwaysLegato,
+            LegatoMode::Retrigger => chorus::director::LegatoMode::AlwaysRetrigger,
+        }
+    }
+}
+
+/// How carefully to convert the output when the host's sample rate differs from the
+/// synthesizer's.  Higher quality removes more artifacts from high harmonics, but uses more CPU
+/// time and adds latency.
+#[derive(Copy, Clone, Enum, Debug, PartialEq)]
+pub enum ResampleQuality {
+    #[id = "fast"]
+    Fast,
+    #[id = "good"]
+    Good,
+    #[id = "best"]
+    Best,
+}
+
+impl ResampleQuality {
+    /// Get the number of input samples spanned by the resampling filter.
+    fn filter_length(&self) -> usize {
+        match self {
+            ResampleQuality::Fast => 2,
+            ResampleQuality::Good => 16,
+            ResampleQuality::Best => 64
+        }
+    }
+}
+
+/// A duration specified as a fraction of a whole note, used when Time Spread and Vowel Delay are
+/// synced to the host's tempo.
+#[derive(Copy, Clone, Enum, Debug, PartialEq)]
+pub enum NoteValue {
+    #[id = "none"]
+    #[name = "None"]
+    Zero,
+    #[id = "quarter"]
+    #[name = "1/4"]
+    Quarter,
+    #[id = "eighth"]
+    #[name = "1/8"]
+    Eighth,
+    #[id = "sixteenth"]
+    #[name = "1/16"]
+    Sixteenth,
+    #[id = "thirty_second"]
+    #[name = "1/32"]
+    ThirtySecond,
+    #[id = "sixty_fourth"]
+    #[name = "1/64"]
+    SixtyFourth,
+}
+
+impl NoteValue {
+    /// Get the length of this note value in beats (quarter notes).
+    fn beats(&self) -> f64 {
+        match self {
+            NoteValue::Zero => 0.0,
+            NoteValue::Quarter => 1.0,
+            NoteValue::Eighth => 0.5,
+            NoteValue::Sixteenth => 0.25,
+            NoteValue::ThirtySecond => 0.125,
+            NoteValue::SixtyFourth => 0.0625
+        }
+    }
+
+    /// Get the length of this note value in samples at a given tempo.
+    fn samples(&self, tempo: f64) -> i64 {
+        (self.beats()*60.0*chorus::SAMPLE_RATE as f64/tempo).round() as i64
+    }
+}
+
 impl Default for ChorusExMachina {
     fn default() -> Self {
         let (sender, receiver) = mpsc::channel();
         Self {
             params: Arc::new(ChorusExMachinaParams::default()),
             director: Arc::new(Mutex::new(Director::new(chorus::VoicePart::Soprano, 1, receiver))),
+            performer: Performer::new(sender.clone()),
             sender: Arc::new(Mutex::new(sender)),
+            #[cfg(feature = "gui")]
             editor_state: Arc::new(Mutex::new(editor::UIState::new())),
+            vocal_shape: Arc::new(Mutex::new((vec![], 0.0, vec![]))),
+            level_meter: Arc::new(LevelMeter::new()),
+            playback: Arc::new(Mutex::new(PlaybackState::default())),
             need_resample: false,
             resample_left: Resampler::new(chorus::SAMPLE_RATE as f32),
             resample_right: Resampler::new(chorus::SAMPLE_RATE as f32),
-            last_note: 255,
+            resample_quality: ResampleQuality::Fast,
             last_dynamics: -1.0,
             last_vibrato: -1.0,
             last_intensity: -1.0,
@@ -120,11 +362,24 @@ impl Default for ChorusExMachina {
             last_release_rate: -1.0,
             last_stereo_width: -1.0,
             last_exciter_strength: -1.0,
+            last_ensemble: -1.0,
+            last_output_gain: -1000.0,
+            last_air: -1000.0,
             last_time_spread: -1,
             last_vowel_delay: -1,
             last_accent: false,
+            last_accent_strength: -1.0,
+            last_hum: false,
+            last_spectral_tilt: false,
+            last_oversample: false,
+            last_mono: false,
+            last_whisper: -1.0,
+            last_subglottal: -1.0,
+            last_resonance: -1.0,
+            last_humanize: -1.0,
+            last_legato_mode: None,
             last_phrase: -1,
-            last_syllable_index: -1
+            current_phrase: 0
         }
     }
 }
@@ -132,30 +387,113 @@ impl Default for ChorusExMachina {
 impl Default for ChorusExMachinaParams {
     fn default() -> Self {
         let result = Self {
+            #[cfg(feature = "gui")]
             editor_state: EguiState::from_size(600, 400),
+            #[cfg(not(feature = "gui"))]
+            editor_state: Mutex::new(()),
             phrases: Mutex::new(vec!["".to_string(); 128]),
+            params_dirty: AtomicBool::new(false),
+            phrases_dirty: AtomicBool::new(true),
             voice_part: EnumParam::new("Voice Part", VoicePart::Soprano).non_automatable(),
             voice_count: IntParam::new("Voices", 8, IntRange::Linear {min: 1, max: 8}).non_automatable(),
+            transpose: IntParam::new("Transpose", 0, IntRange::Linear {min: -24, max: 24}).with_unit(" st").non_automatable(),
             dynamics: FloatParam::new("Dynamics", 1.0, FloatRange::Linear {min: 0.0, max: 1.0}),
             vibrato: FloatParam::new("Vibrato", 0.4, FloatRange::Linear {min: 0.0, max: 1.0}),
             intensity: FloatParam::new("Intensity", 0.5, FloatRange::Linear {min: 0.0, max: 1.0}),
             brightness: FloatParam::new("Brightness", 1.0, FloatRange::Linear {min: 0.0, max: 1.0}),
+            spectral_tilt: BoolParam::new("Spectral Tilt", false),
             consonant_volume: FloatParam::new("Consonant Volume", 0.5, FloatRange::Linear {min: 0.0, max: 1.0}),
             attack_rate: FloatParam::new("Attack Rate", 0.75, FloatRange::Linear {min: 0.0, max: 1.0}),
             release_rate: FloatParam::new("Release Rate", 0.5, FloatRange::Linear {min: 0.0, max: 1.0}),
             stereo_width: FloatParam::new("Stereo Width", 0.7, FloatRange::Linear {min: 0.0, max: 1.0}),
             exciter_strength: FloatParam::new("Exciter Strength", 0.5, FloatRange::Linear {min: 0.0, max: 1.0}),
+            ensemble: FloatParam::new("Ensemble", 0.0, FloatRange::Linear {min: 0.0, max: 1.0}),
+            output_gain: FloatParam::new("Output Gain", 0.0, FloatRange::Linear {min: -24.0, max: 12.0}).with_unit(" dB"),
+            air: FloatParam::new("Air", 0.0, FloatRange::Linear {min: 0.0, max: 12.0}).with_unit(" dB"),
             time_spread: IntParam::new("Time Spread", 50, IntRange::Linear {min: 0, max: 100}),
             vowel_delay: IntParam::new("Vowel Delay", 0, IntRange::Linear {min: 0, max: 250}),
+            tempo_sync: BoolParam::new("Tempo Sync", false),
+            time_spread_note: EnumParam::new("Time Spread (Synced)", NoteValue::SixtyFourth),
+            vowel_delay_note: EnumParam::new("Vowel Delay (Synced)", NoteValue::Zero),
+            vowel_grid: EnumParam::new("Vowel Grid", NoteValue::Zero),
             accent: BoolParam::new("Accent", false),
+            accent_strength: FloatParam::new("Accent Strength", 0.5, FloatRange::Linear {min: 0.0, max: 1.0}),
+            whisper: FloatParam::new("Whisper", 0.0, FloatRange::Linear {min: 0.0, max: 1.0}),
+            subglottal: FloatParam::new("Subglottal Coupling", 0.0, FloatRange::Linear {min: 0.0, max: 1.0}),
+            resonance: FloatParam::new("Resonance", 0.5, FloatRange::Linear {min: 0.0, max: 1.0}),
+            humanize: FloatParam::new("Humanize", 0.5, FloatRange::Linear {min: 0.0, max: 1.0}),
+            hum: BoolParam::new("Hum", false),
+            mono: BoolParam::new("Mono", false),
+            oversample: BoolParam::new("Oversample", false).non_automatable(),
             selected_phrase: IntParam::new("Selected Phrase", 0, IntRange::Linear {min: 0, max: 127}),
             advance_syllable: BoolParam::new("Advance Syllable", true),
+            advance_by: EnumParam::new("Advance By", SyllableGrouping::Syllable),
+            note_priority: EnumParam::new("Note Priority", NotePriority::Last),
+            legato_mode: EnumParam::new("Legato Mode", LegatoMode::Auto),
+            resample_quality: EnumParam::new("Resampling Quality", ResampleQuality::Fast).non_automatable(),
         };
         result.phrases.lock().unwrap()[0] = "A".to_string();
         result
     }
 }
 
+impl ChorusExMachina {
+    /// Clear the cached values of all parameters, so every one of them is sent to the Director
+    /// at the start of the next call to process().
+    fn invalidate_params(&mut self) {
+        self.last_dynamics = -1.0;
+        self.last_vibrato = -1.0;
+        self.last_intensity = -1.0;
+        self.last_brightness = -1.0;
+        self.last_consonant_volume = -1.0;
+        self.last_attack_rate = -1.0;
+        self.last_release_rate = -1.0;
+        self.last_stereo_width = -1.0;
+        self.last_exciter_strength = -1.0;
+        self.last_ensemble = -1.0;
+        self.last_output_gain = -1000.0;
+        self.last_air = -1000.0;
+        self.last_time_spread = -1;
+        self.last_vowel_delay = -1;
+        self.last_accent = !self.params.accent.value();
+        self.last_mono = !self.params.mono.value();
+        self.last_accent_strength = -1.0;
+        self.last_hum = !self.params.hum.value();
+        self.last_spectral_tilt = !self.params.spectral_tilt.value();
+        self.last_oversample = !self.params.oversample.value();
+        self.last_whisper = -1.0;
+        self.last_subglottal = -1.0;
+        self.last_resonance = -1.0;
+        self.last_humanize = -1.0;
+        self.last_legato_mode = None;
+    }
+
+    /// Create the Resamplers for the host's sample rate, using the selected quality.  Returns the
+    /// latency they introduce, measured in samples at the host's rate.
+    fn create_resamplers(&mut self, sample_rate: f32) -> u32 {
+        self.resample_quality = self.params.resample_quality.value();
+        let length = self.resample_quality.filter_length();
+        self.resample_left = Resampler::with_filter_length(sample_rate, length);
+        self.resample_right = Resampler::with_filter_length(sample_rate, length);
+        if self.need_resample {
+            (self.resample_left.latency() as f32*sample_rate/chorus::SAMPLE_RATE as f32).round() as u32
+        }
+        else {
+            0
+        }
+    }
+}
+
+/// Give the Performer the text of a phrase.  This is called from the audio thread, so it does not
+/// wait if the editor is holding the lock.  Instead the phrases are marked dirty again, and the
+/// Performer keeps its current phrase until the next block.
+fn update_phrase(params: &ChorusExMachinaParams, performer: &mut Performer, index: i32) {
+    match params.phrases.try_lock() {
+        Ok(phrases) => performer.set_phrase(phrases.get(index as usize).map_or("", String::as_str)),
+        Err(_) => params.phrases_dirty.store(true, Ordering::Relaxed)
+    }
+}
+
 impl Plugin for ChorusExMachina {
     const NAME: &'static str = "Chorus Ex Machina";
     const VENDOR: &'static str = "Peter Eastman";
@@ -182,26 +520,35 @@ impl Plugin for ChorusExMachina {
         self.params.clone()
     }
 
-    fn initialize(&mut self, _audio_io_layout: &AudioIOLayout, buffer_config: &BufferConfig, _context: &mut impl InitContext<Self>) -> bool {
+    fn initialize(&mut self, _audio_io_layout: &AudioIOLayout, buffer_config: &BufferConfig, context: &mut impl InitContext<Self>) -> bool {
         self.need_resample = buffer_config.sample_rate != chorus::SAMPLE_RATE as f32;
-        self.resample_left = Resampler::new(buffer_config.sample_rate);
-        self.resample_right = Resampler::new(buffer_config.sample_rate);
-        let voice_part = match self.params.voice_part.value() {
-            VoicePart::Soprano => chorus::VoicePart::Soprano,
-            VoicePart::Alto => chorus::VoicePart::Alto,
-            VoicePart::Tenor => chorus::VoicePart::Tenor,
-            VoicePart::Bass => chorus::VoicePart::Bass,
-        };
+        context.set_latency_samples(self.create_resamplers(buffer_config.sample_rate));
+        let voice_part: chorus::VoicePart = self.params.voice_part.value().into();
         let voice_count = self.params.voice_count.value() as usize;
+
+        // A state saved by an older version could contain fewer phrases than the table holds.
+
+        self.params.phrases.lock().unwrap().resize(128, "".to_string());
+        self.params.phrases_dirty.store(true, Ordering::Relaxed);
         let _ = self.sender.lock().unwrap().send(Message::Reinitialize {voice_part: voice_part, voice_count: voice_count});
+        self.invalidate_params();
         true
     }
 
     fn reset(&mut self) {
-        self.last_syllable_index = -1;
+        self.performer.reset();
     }
 
     fn process(&mut self, buffer: &mut Buffer, _aux: &mut AuxiliaryBuffers, context: &mut impl ProcessContext<Self>) -> ProcessStatus {
+        if self.resample_quality != self.params.resample_quality.value() {
+            // Resamplers store their history in fixed size arrays, so this does not allocate.
+
+            let latency = self.create_resamplers(context.transport().sample_rate);
+            context.set_latency_samples(latency);
+        }
+        if self.params.params_dirty.swap(false, Ordering::Relaxed) {
+            self.invalidate_params();
+        }
         let mut director = self.director.lock().unwrap();
         let sender = self.sender.lock().unwrap();
         let mut next_event = context.next_event();
@@ -215,7 +562,7 @@ impl Plugin for ChorusExMachina {
         }
         if self.last_intensity != self.params.intensity.value() {
             self.last_intensity = self.params.intensity.value();
-            let _ = sender.send(Message::SetIntensity {intensity: self.last_intensity});
+            self.performer.set_intensity(self.last_intensity);
         }
         if self.last_brightness != self.params.brightness.value() {
             self.last_brightness = self.params.brightness.value();
@@ -231,7 +578,7 @@ impl Plugin for ChorusExMachina {
         }
         if self.last_release_rate != self.params.release_rate.value() {
             self.last_release_rate = self.params.release_rate.value();
-            let _ = sender.send(Message::SetReleaseRate {release: self.last_release_rate});
+            self.performer.set_release_rate(self.last_release_rate);
         }
         if self.last_stereo_width != self.params.stereo_width.value() {
             self.last_stereo_width = self.params.stereo_width.value();
@@ -241,72 +588,149 @@ impl Plugin for ChorusExMachina {
             self.last_exciter_strength = self.params.exciter_strength.value();
             let _ = sender.send(Message::SetExciterStrength {strength: self.last_exciter_strength*0.9});
         }
-        if self.last_time_spread != self.params.time_spread.value() {
-            self.last_time_spread = self.params.time_spread.value();
-            let _ = sender.send(Message::SetMaxVoiceDelay {max_delay: (self.last_time_spread*chorus::SAMPLE_RATE/1000) as i64});
+        if self.last_ensemble != self.params.ensemble.value() {
+            self.last_ensemble = self.params.ensemble.value();
+            let _ = sender.send(Message::SetEnsemble {depth: self.last_ensemble, voices: 3});
+        }
+        if self.last_output_gain != self.params.output_gain.value() {
+            self.last_output_gain = self.params.output_gain.value();
+            let _ = sender.send(Message::SetOutputGain {gain_db: self.last_output_gain});
+        }
+        if self.last_air != self.params.air.value() {
+            self.last_air = self.params.air.value();
+            let _ = sender.send(Message::SetAir {gain_db: self.last_air});
+        }
+
+        // Time Spread and Vowel Delay are specified either in ms or, if synced to tempo, as note
+        // values.  If the host does not report a tempo, fall back to the values in ms.
+
+        let tempo = if self.params.tempo_sync.value() {context.transport().tempo.filter(|&t| t > 0.0)} else {None};
+        let (time_spread, vowel_delay) = match tempo {
+            Some(tempo) => (self.params.time_spread_note.value().samples(tempo), self.params.vowel_delay_note.value().samples(tempo)),
+            None => ((self.params.time_spread.value()*chorus::SAMPLE_RATE/1000) as i64, (self.params.vowel_delay.value()*chorus::SAMPLE_RATE/1000) as i64)
+        };
+        if self.last_time_spread != time_spread {
+            self.last_time_spread = time_spread;
+            let _ = sender.send(Message::SetMaxVoiceDelay {max_delay: time_spread});
         }
-        if self.last_vowel_delay != self.params.vowel_delay.value() {
-            self.last_vowel_delay = self.params.vowel_delay.value();
-            let _ = sender.send(Message::SetMinVowelStartTime {samples: (self.last_vowel_delay*chorus::SAMPLE_RATE/1000) as i64});
+        if self.last_vowel_delay != vowel_delay {
+            self.last_vowel_delay = vowel_delay;
+            let _ = sender.send(Message::SetMinVowelStartTime {samples: vowel_delay});
         }
         if self.last_accent != self.params.accent.value() {
             self.last_accent = self.params.accent.value();
             let _ = sender.send(Message::SetAccent {accent: self.last_accent});
         }
+        if self.last_accent_strength != self.params.accent_strength.value() {
+            self.last_accent_strength = self.params.accent_strength.value();
+            let _ = sender.send(Message::SetAccentAmount {gain: 5.0*self.last_accent_strength, decay_ms: 83});
+        }
+        if self.last_hum != self.params.hum.value() {
+            self.last_hum = self.params.hum.value();
+            let _ = sender.send(Message::SetHum {enabled: self.last_hum});
+        }
+        if self.last_spectral_tilt != self.params.spectral_tilt.value() {
+            self.last_spectral_tilt = self.params.spectral_tilt.value();
+            let mode = if self.last_spectral_tilt {BrightnessMode::SpectralTilt} else {BrightnessMode::ShapeBlend};
+            let _ = sender.send(Message::SetBrightnessMode {mode: mode});
+        }
+        if self.last_oversample != self.params.oversample.value() {
+            self.last_oversample = self.params.oversample.value();
+            let _ = sender.send(Message::SetOversampling {factor: if self.last_oversample {2} else {1}});
+        }
+        if self.last_mono != self.params.mono.value() {
+            self.last_mono = self.params.mono.value();
+            let _ = sender.send(Message::SetMono {enabled: self.last_mono});
+        }
+        if self.last_whisper != self.params.whisper.value() {
+            self.last_whisper = self.params.whisper.value();
+            let _ = sender.send(Message::SetWhisper {amount: self.last_whisper});
+        }
+        if self.last_subglottal != self.params.subglottal.value() {
+            self.last_subglottal = self.params.subglottal.value();
+            let _ = sender.send(Message::SetSubglottalCoupling {amount: self.last_subglottal});
+        }
+        if self.last_resonance != self.params.resonance.value() {
+            self.last_resonance = self.params.resonance.value();
+            let _ = sender.send(Message::SetDamping {vocal: 0.99+0.01*self.last_resonance, nasal: 0.97+0.02*self.last_resonance});
+        }
+        if self.last_humanize != self.params.humanize.value() {
+            self.last_humanize = self.params.humanize.value();
+            let _ = sender.send(Message::SetHumanize {amount: 2.0*self.last_humanize});
+        }
+        if self.last_legato_mode != Some(self.params.legato_mode.value()) {
+            self.last_legato_mode = Some(self.params.legato_mode.value());
+            let _ = sender.send(Message::SetLegatoMode {mode: self.params.legato_mode.value().into()});
+        }
         if self.last_phrase != self.params.selected_phrase.value() {
+            // The editor sets the parameter to match a phrase selected by a program change.  Only
+            // restart the phrase if it is really a different one.
+
             self.last_phrase = self.params.selected_phrase.value();
-            self.last_syllable_index = -1;
+            if self.current_phrase != self.last_phrase {
+                self.current_phrase = self.last_phrase;
+                self.params.phrases_dirty.store(true, Ordering::Relaxed);
+                self.performer.restart_phrase();
+            }
+        }
+        if self.params.phrases_dirty.swap(false, Ordering::Relaxed) {
+            update_phrase(&self.params, &mut self.performer, self.current_phrase);
         }
+        self.performer.note_priority = self.params.note_priority.value().into();
+        self.performer.advance_syllable = self.params.advance_syllable.value();
+        self.performer.grouping = self.params.advance_by.value().into();
+        self.performer.transpose = self.params.transpose.value();
+
+        // If a Vowel Grid is selected and the host is playing, find the position (in beats) at
+        // the start of this buffer so vowels can be aligned to the grid.
+
+        let grid = self.params.vowel_grid.value().beats();
+        let transport = context.transport();
+        let grid_position = match (transport.playing, transport.tempo, transport.pos_beats()) {
+            (true, Some(tempo), Some(position)) if grid > 0.0 && tempo > 0.0 => Some((position, tempo, transport.sample_rate as f64)),
+            _ => None
+        };
+        let mut peak = [0.0_f32; 2];
+        let mut sum_squares = [0.0_f32; 2];
+        let num_samples = buffer.samples();
         for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
-            let mut send_note_off = false;
+            if let Some((position, tempo, sample_rate)) = grid_position {
+                // Delay the vowel of any note started now so it begins on the next grid line.
+
+                let beat = position + sample_id as f64*tempo/(60.0*sample_rate);
+                let mut wait = (beat/grid).ceil()*grid - beat;
+                if wait > grid-1e-6 {
+                    wait = 0.0;
+                }
+                self.performer.vowel_start = Some((wait*60.0*chorus::SAMPLE_RATE as f64/tempo).round() as i64);
+            }
+            else {
+                self.performer.vowel_start = None;
+            }
             while let Some(event) = next_event {
                 if event.timing() != sample_id as u32 {
                     break;
                 }
                 match event {
-                    NoteEvent::NoteOn { note, velocity, .. } => {
-                        let phrase = self.params.phrases.lock().unwrap()[self.params.selected_phrase.value() as usize].clone();
-                        let replaced = phrase.replace(".", " ");
-                        let syllables: Vec<&str> = replaced.split_whitespace().collect();
-                        if syllables.len() > 0 {
-                            if self.params.advance_syllable.value() {
-                                self.last_syllable_index = (self.last_syllable_index+1)%syllables.len() as i32;
-                            }
-                            let syllable_index: usize;
-                            if self.last_syllable_index < 0 || self.last_syllable_index >= syllables.len() as i32 {
-                                syllable_index = 0;
-                            }
-                            else {
-                                syllable_index = self.last_syllable_index as usize;
-                            }
-                            let _ = sender.send(Message::NoteOn {
-                                syllable: syllables[syllable_index].to_string(),
-                                note_index: note as i32,
-                                velocity: velocity,
-                                continue_syllable: !self.params.advance_syllable.value()});
-                            self.last_note = note;
-
-                            // If we get both a NoteOn and a NoteOff and the same time, skip the NoteOff
-                            // to allow legato playing.
-
-                            send_note_off = false;
-                        }
-                    },
-                    NoteEvent::NoteOff { note, .. } => {
-                        if note == self.last_note {
-                            send_note_off = true;
-                        }
-                    },
-                    NoteEvent::MidiPitchBend { value, .. } => {
-                        let _ = sender.send(Message::SetPitchBend {semitones: 4.0*(value-0.5)});
+                    NoteEvent::NoteOn { note, velocity, .. } => self.performer.handle_event(Event::NoteOn {note, velocity}),
+                    NoteEvent::NoteOff { note, velocity, .. } => self.performer.handle_event(Event::NoteOff {note, velocity}),
+                    NoteEvent::MidiCC { cc: 64, value, .. } => self.performer.handle_event(Event::Sustain {pressed: value >= 0.5}),
+                    NoteEvent::MidiCC { cc: 11, value, .. } => self.performer.handle_event(Event::Expression {value}),
+                    NoteEvent::MidiChannelPressure { pressure, .. } => self.performer.handle_event(Event::Pressure {pressure}),
+                    NoteEvent::MidiPitchBend { value, .. } => self.performer.handle_event(Event::PitchBend {value}),
+                    NoteEvent::MidiProgramChange { program, .. } => {
+                        // A program change selects a phrase until the Selected Phrase parameter
+                        // is next changed.
+
+                        self.current_phrase = program as i32;
+                        update_phrase(&self.params, &mut self.performer, self.current_phrase);
+                        self.performer.restart_phrase();
                     },
                     _ => (),
                 }
                 next_event = context.next_event();
             }
-            if send_note_off {
-                let _ = sender.send(Message::NoteOff);
-            }
+            self.performer.flush();
             let left;
             let right;
             if self.need_resample {
@@ -321,6 +745,10 @@ impl Plugin for ChorusExMachina {
             else {
                 (left, right) = director.generate();
             }
+            peak[0] = peak[0].max(left.abs());
+            peak[1] = peak[1].max(right.abs());
+            sum_squares[0] += left*left;
+            sum_squares[1] += right*right;
             let mut i = 0;
             for sample in channel_samples {
                 if i == 0 {
@@ -332,14 +760,40 @@ impl Plugin for ChorusExMachina {
                 i += 1;
             }
         }
+
+        // Update the level meter.  Peaks decay by a factor of 10 per second.
+
+        if num_samples > 0 {
+            let decay = 0.1_f32.powf(num_samples as f32/context.transport().sample_rate);
+            for i in 0..2 {
+                self.level_meter.update(i, peak[i], (sum_squares[i]/num_samples as f32).sqrt(), decay);
+            }
+        }
+
+        // Publish the current vocal tract shape and pressure for the editor to display.  Never wait
+        // for the lock, since that could block the audio thread.
+
+        if let (Some((shape, coupling)), Ok(mut vocal_shape)) = (director.get_vocal_shape(), self.vocal_shape.try_lock()) {
+            vocal_shape.0.clone_from(shape);
+            vocal_shape.1 = coupling;
+            director.get_tract_pressure(&mut vocal_shape.2);
+        }
+        if let Ok(mut playback) = self.playback.try_lock() {
+            playback.director = director.current_state();
+            playback.phrase = self.current_phrase;
+        }
         ProcessStatus::KeepAlive
     }
 
+    #[cfg(feature = "gui")]
     fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         let params = Arc::clone(&self.params);
         let sender = Arc::clone(&self.sender);
         let state = Arc::clone(&self.editor_state);
-        editor::draw_editor(params, sender, state)
+        let vocal_shape = Arc::clone(&self.vocal_shape);
+        let level_meter = Arc::clone(&self.level_meter);
+        let playback = Arc::clone(&self.playback);
+        editor::draw_editor(params, sender, state, vocal_shape, level_meter, playback)
     }
 }
 
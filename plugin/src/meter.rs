@@ -0,0 +1,52 @@
+// Copyright 2025-2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// A LevelMeter records the output level of each channel.  It is updated by the audio thread
+/// and read by the editor.  Values are stored as the bits of f32s in atomic integers, so they
+/// can be shared without locking.
+pub struct LevelMeter {
+    peak: [AtomicU32; 2],
+    rms: [AtomicU32; 2]
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self {
+            peak: [AtomicU32::new(0), AtomicU32::new(0)],
+            rms: [AtomicU32::new(0), AtomicU32::new(0)]
+        }
+    }
+
+    /// Get the peak amplitude of a channel.  This decays slowly so that brief peaks remain visible.
+    pub fn get_peak(&self, channel: usize) -> f32 {
+        f32::from_bits(self.peak[channel].load(Ordering::Relaxed))
+    }
+
+    /// Get the RMS amplitude of a channel over the most recent block.
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn get_rms(&self, channel: usize) -> f32 {
+        f32::from_bits(self.rms[channel].load(Ordering::Relaxed))
+    }
+
+    /// Record the levels measured for a block of samples.  decay is the factor by which the
+    /// previous peak should be reduced over the duration of the block.
+    pub fn update(&self, channel: usize, peak: f32, rms: f32, decay: f32) {
+        let peak = peak.max(decay*self.get_peak(channel));
+        self.peak[channel].store(peak.to_bits(), Ordering::Relaxed);
+        self.rms[channel].store(rms.to_bits(), Ordering::Relaxed);
+    }
+}
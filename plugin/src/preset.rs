@@ -0,0 +1,131 @@
+// Copyright 2025-2026 by Peter Eastman
+//
+// This file is part of Chorus Ex Machina.
+//
+// Chorus Ex Machina is free software: you can redistribute it and/or modify it under the terms
+// of the GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// Chorus Ex Machina is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License along with Chorus Ex Machina.
+// If not, see <https://www.gnu.org/licenses/>.
+
+use crate::ChorusExMachinaParams;
+use chorus::director::Message;
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::atomic::Ordering;
+
+/// A Preset records the values of all parameters, along with the phrase table.  Parameters are
+/// identified by their IDs and stored as normalized values, so presets remain valid when new
+/// parameters are added.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Preset {
+    pub values: BTreeMap<String, f32>,
+    pub phrases: Vec<String>
+}
+
+impl Preset {
+    /// Create a Preset from the current values of the parameters.
+    pub fn capture(params: &ChorusExMachinaParams) -> Self {
+        let mut values = BTreeMap::new();
+        for (id, ptr, _) in params.param_map() {
+            values.insert(id, unsafe { ptr.unmodulated_normalized_value() });
+        }
+        Self {
+            values: values,
+            phrases: params.phrases.lock().unwrap().clone()
+        }
+    }
+
+    /// Set the parameters to the values stored in this Preset.  Any parameter not contained in it
+    /// is left unchanged.  If the voice part or number of voices changes, the Director is
+    /// reinitialized.
+    pub fn apply(&self, params: &ChorusExMachinaParams, setter: &ParamSetter, sender: &mpsc::Sender<Message>) {
+        let mut voice_part = params.voice_part.value();
+        let mut voice_count = params.voice_count.value();
+        if let Some(&value) = self.values.get("voice_part") {
+            voice_part = params.voice_part.preview_plain(value);
+        }
+        if let Some(&value) = self.values.get("voice_count") {
+            voice_count = params.voice_count.preview_plain(value);
+        }
+        let reinitialize = voice_part != params.voice_part.value() || voice_count != params.voice_count.value();
+        for (id, ptr, _) in params.param_map() {
+            if let Some(&value) = self.values.get(&id) {
+                unsafe {
+                    setter.raw_context.raw_begin_set_parameter(ptr);
+                    setter.raw_context.raw_set_parameter_normalized(ptr, value);
+                    setter.raw_context.raw_end_set_parameter(ptr);
+                }
+            }
+        }
+        if self.phrases.len() > 0 {
+            let mut phrases = params.phrases.lock().unwrap();
+            for i in 0..phrases.len() {
+                phrases[i] = match self.phrases.get(i) {
+                    Some(phrase) => phrase.clone(),
+                    None => "".to_string()
+                };
+            }
+            params.phrases_dirty.store(true, Ordering::Relaxed);
+        }
+        if reinitialize {
+            let _ = sender.send(Message::Reinitialize {voice_part: voice_part.into(), voice_count: voice_count as usize});
+            params.params_dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Write this Preset to a JSON file.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    /// Read a Preset from a JSON file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    }
+}
+
+/// Write the phrase table to a text file.  Each nonempty phrase is written on its own line,
+/// preceded by its index and a tab.
+pub fn export_phrases(phrases: &[String], path: &str) -> Result<(), String> {
+    let mut text = String::new();
+    for (i, phrase) in phrases.iter().enumerate() {
+        if phrase.trim().len() > 0 {
+            text.push_str(&format!("{i}\t{phrase}\n"));
+        }
+    }
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
+
+/// Read a phrase table from a text file.  A line that begins with an index and a tab is stored
+/// at that index.  Any other line is stored at the index following the previous line, so a
+/// plain list of phrases fills successive slots starting from 0.
+pub fn import_phrases(path: &str, count: usize) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut phrases = vec!["".to_string(); count];
+    let mut index = 0;
+    for line in text.lines() {
+        let mut phrase = line;
+        if let Some((prefix, rest)) = line.split_once('\t') {
+            if let Ok(i) = prefix.trim().parse::<usize>() {
+                index = i;
+                phrase = rest;
+            }
+        }
+        if index >= count {
+            return Err(format!("Phrase index {index} is out of range"));
+        }
+        phrases[index] = phrase.trim().to_string();
+        index += 1;
+    }
+    Ok(phrases)
+}
@@ -1,9 +1,14 @@
+// The MIDI helpers are shared with the player.
+
+#[path = "../../player/src/midi.rs"]
+mod midi;
+
 use chorus::VoicePart;
 use chorus::voice::Voice;
 use chorus::SAMPLE_RATE;
 
 use rodio::{OutputStream, Source};
-use midir::MidiInput;
+use midi::{connect_midi, default_midi_port, midi_port_names, option_value};
 use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -465,15 +470,10 @@ fn main() -> Result<(), eframe::Error> {
     let (_stream, handle) = OutputStream::try_default().unwrap();
     let _result = handle.play_raw(source.convert_samples());
 
-    let midi_in = MidiInput::new("Chorus").unwrap();
-    let in_ports = midi_in.ports();
-    let in_port = &in_ports[1];
-    let _conn_in = midi_in.connect(
-        in_port,
-        "midir-read-input",
-        process_midi_message,
-        Arc::clone(&player),
-    ).unwrap();
+    let midi_ports = midi_port_names();
+    let args: Vec<String> = std::env::args().collect();
+    let midi_port = default_midi_port(&midi_ports, option_value(&args, "--midi-port"));
+    let _midi_connection = connect_midi(midi_port, process_midi_message, Arc::clone(&player));
     let options = NativeOptions::default();
     let gui = MainGui {
         player_ref: Arc::clone(&player)